@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS reviewlog_deck_id;
+DROP INDEX IF EXISTS reviewlog_card_id;
+DROP TABLE IF EXISTS reviewlog;
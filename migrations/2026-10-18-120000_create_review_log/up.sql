@@ -0,0 +1,34 @@
+-- one row per answer given in a review session
+
+CREATE TABLE reviewlog (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    card_id             INTEGER NOT NULL,
+    deck_id             INTEGER NOT NULL,
+    -- UTC time the answer was recorded
+    reviewed_at         TIMESTAMP NOT NULL,
+    -- -1, 0, 1 for AM-1 deadlines, 1-5 for ankiboxes
+    score               INTEGER NOT NULL,
+    user_answer         TEXT NOT NULL,
+    stack_before        TEXT NOT NULL,
+    stack_after         TEXT NOT NULL,
+    -- AM-1 state
+    box_before          INTEGER,
+    box_after           INTEGER,
+    -- Anki state
+    repetitions_before  INTEGER,
+    repetitions_after   INTEGER,
+    easiness_before     REAL,
+    easiness_after      REAL,
+    interval_before     INTEGER,
+    interval_after      INTEGER,
+    next_practice_before DATE,
+    next_practice_after DATE,
+    -- time from showing the card to answering it
+    elapsed_ms          INTEGER,
+
+    FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE,
+    FOREIGN KEY (deck_id) REFERENCES decks (id) ON DELETE CASCADE
+);
+
+CREATE INDEX reviewlog_card_id ON reviewlog (card_id);
+CREATE INDEX reviewlog_deck_id ON reviewlog (deck_id);
@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE reviewlog DROP COLUMN last_review_after;
+ALTER TABLE reviewlog DROP COLUMN last_review_before;
+ALTER TABLE reviewlog DROP COLUMN difficulty_after;
+ALTER TABLE reviewlog DROP COLUMN difficulty_before;
+ALTER TABLE reviewlog DROP COLUMN stability_after;
+ALTER TABLE reviewlog DROP COLUMN stability_before;
+
+ALTER TABLE cards DROP COLUMN last_review;
+ALTER TABLE cards DROP COLUMN difficulty;
+ALTER TABLE cards DROP COLUMN stability;
+
+ALTER TABLE deadlines DROP COLUMN target_retention;
+ALTER TABLE deadlines DROP COLUMN is_fsrs;
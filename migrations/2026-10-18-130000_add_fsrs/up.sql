@@ -0,0 +1,16 @@
+-- FSRS boxes are ankiboxes scheduled by FSRS instead of SM-2
+
+ALTER TABLE deadlines ADD COLUMN is_fsrs BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE deadlines ADD COLUMN target_retention REAL CHECK (target_retention > 0 AND target_retention < 1);
+
+-- FSRS fields
+ALTER TABLE cards ADD COLUMN stability REAL;
+ALTER TABLE cards ADD COLUMN difficulty REAL;
+ALTER TABLE cards ADD COLUMN last_review DATE;
+
+ALTER TABLE reviewlog ADD COLUMN stability_before REAL;
+ALTER TABLE reviewlog ADD COLUMN stability_after REAL;
+ALTER TABLE reviewlog ADD COLUMN difficulty_before REAL;
+ALTER TABLE reviewlog ADD COLUMN difficulty_after REAL;
+ALTER TABLE reviewlog ADD COLUMN last_review_before DATE;
+ALTER TABLE reviewlog ADD COLUMN last_review_after DATE;
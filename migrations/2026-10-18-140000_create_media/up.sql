@@ -0,0 +1,11 @@
+-- images and audio shown on cards; the files live in the `media` directory
+-- next to the database, named after the SHA-1 of their contents
+
+CREATE TABLE media (
+    -- `<sha1>.<extension>`, referenced from cards as `adam-media:<file_name>`
+    file_name   TEXT PRIMARY KEY NOT NULL,
+    mime_type   TEXT NOT NULL,
+    byte_size   INTEGER NOT NULL,
+    -- UTC time the file was first stored
+    created_at  TIMESTAMP NOT NULL
+);
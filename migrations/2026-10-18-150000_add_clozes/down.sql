@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS cards_cloze_id;
+
+ALTER TABLE cards DROP COLUMN cloze_index;
+ALTER TABLE cards DROP COLUMN cloze_id;
+
+DROP TABLE IF EXISTS clozes;
@@ -0,0 +1,14 @@
+-- a cloze is one text with `{{c1::...}}` markers, expanded into one card per
+-- cloze index; the cards keep their own scheduling state
+
+CREATE TABLE clozes (
+    id          INTEGER PRIMARY KEY NOT NULL,
+    deck_id     INTEGER NOT NULL,
+    source      TEXT NOT NULL,
+    FOREIGN KEY (deck_id) REFERENCES decks (id) ON DELETE CASCADE
+);
+
+ALTER TABLE cards ADD COLUMN cloze_id INTEGER REFERENCES clozes (id) ON DELETE CASCADE;
+ALTER TABLE cards ADD COLUMN cloze_index INTEGER;
+
+CREATE INDEX cards_cloze_id ON cards (cloze_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS cards_reverse_of;
+
+ALTER TABLE cards DROP COLUMN reverse_of;
@@ -0,0 +1,6 @@
+-- reverse cards of bidirectional cards show the back and ask for the front;
+-- they are scheduled on their own but edited and deleted with their card
+
+ALTER TABLE cards ADD COLUMN reverse_of INTEGER REFERENCES cards (id) ON DELETE CASCADE;
+
+CREATE INDEX cards_reverse_of ON cards (reverse_of);
@@ -0,0 +1,4 @@
+-- how `front` and `back` are written: 'html' from the editor or 'markdown';
+-- either way they are rendered to sanitized HTML by adam-core
+
+ALTER TABLE cards ADD COLUMN format TEXT NOT NULL DEFAULT 'html';
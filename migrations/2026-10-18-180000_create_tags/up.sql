@@ -0,0 +1,17 @@
+-- tags label cards across decks, e.g. by lecture; names are single words
+-- compared without case
+
+CREATE TABLE tags (
+    id          INTEGER PRIMARY KEY NOT NULL,
+    name        TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+CREATE TABLE card_tags (
+    card_id     INTEGER NOT NULL,
+    tag_id      INTEGER NOT NULL,
+    PRIMARY KEY (card_id, tag_id),
+    FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE
+);
+
+CREATE INDEX card_tags_tag_id ON card_tags (tag_id);
@@ -0,0 +1,15 @@
+-- SM-2 settings of an ankibox or one of its decks; a deck without a row
+-- uses its ankibox's, and an ankibox without one the defaults
+
+CREATE TABLE ankioptions (
+    entry_id            INTEGER PRIMARY KEY NOT NULL,
+    starting_ease       REAL NOT NULL,
+    minimum_ease        REAL NOT NULL CHECK (minimum_ease >= 1.0 AND minimum_ease <= starting_ease),
+    first_interval      INTEGER NOT NULL CHECK (first_interval >= 1),
+    second_interval     INTEGER NOT NULL CHECK (second_interval >= first_interval),
+    easy_bonus          REAL NOT NULL CHECK (easy_bonus >= 1.0),
+    hard_multiplier     REAL NOT NULL CHECK (hard_multiplier > 0),
+    interval_modifier   REAL NOT NULL CHECK (interval_modifier > 0),
+    maximum_interval    INTEGER NOT NULL CHECK (maximum_interval >= second_interval),
+    FOREIGN KEY (entry_id) REFERENCES entries (id) ON DELETE CASCADE
+);
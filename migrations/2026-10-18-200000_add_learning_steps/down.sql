@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE reviewlog DROP COLUMN lapses_after;
+ALTER TABLE reviewlog DROP COLUMN lapses_before;
+ALTER TABLE reviewlog DROP COLUMN due_at_after;
+ALTER TABLE reviewlog DROP COLUMN due_at_before;
+ALTER TABLE reviewlog DROP COLUMN learning_step_after;
+ALTER TABLE reviewlog DROP COLUMN learning_step_before;
+
+ALTER TABLE ankioptions DROP COLUMN relearning_steps;
+ALTER TABLE ankioptions DROP COLUMN learning_steps;
+
+ALTER TABLE cards DROP COLUMN lapses;
+ALTER TABLE cards DROP COLUMN due_at;
+ALTER TABLE cards DROP COLUMN learning_step;
@@ -0,0 +1,20 @@
+-- ankibox cards answered "again", or new and not yet through their learning
+-- steps, wait minutes rather than days: `learning_step` is the step they are
+-- on and `due_at` the UTC time they are shown again. `lapses` counts how
+-- often a reviewed card was forgotten
+
+ALTER TABLE cards ADD COLUMN learning_step INTEGER;
+ALTER TABLE cards ADD COLUMN due_at TIMESTAMP;
+ALTER TABLE cards ADD COLUMN lapses INTEGER;
+UPDATE cards SET lapses = 0 WHERE repetitions IS NOT NULL;
+
+-- steps are waits such as "1m 10m" or "1h"
+ALTER TABLE ankioptions ADD COLUMN learning_steps TEXT NOT NULL DEFAULT '1m 10m';
+ALTER TABLE ankioptions ADD COLUMN relearning_steps TEXT NOT NULL DEFAULT '10m';
+
+ALTER TABLE reviewlog ADD COLUMN learning_step_before INTEGER;
+ALTER TABLE reviewlog ADD COLUMN learning_step_after INTEGER;
+ALTER TABLE reviewlog ADD COLUMN due_at_before TIMESTAMP;
+ALTER TABLE reviewlog ADD COLUMN due_at_after TIMESTAMP;
+ALTER TABLE reviewlog ADD COLUMN lapses_before INTEGER;
+ALTER TABLE reviewlog ADD COLUMN lapses_after INTEGER;
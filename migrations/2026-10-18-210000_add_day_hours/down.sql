@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE userconfig DROP COLUMN deadline_cutoff_hour;
+ALTER TABLE userconfig DROP COLUMN new_day_hour;
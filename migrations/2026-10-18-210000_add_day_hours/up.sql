@@ -0,0 +1,7 @@
+-- hour (0-23, local time) at which one study day rolls over to the next, and
+-- hour from which a deadline's own day counts as a day to study on
+
+ALTER TABLE userconfig ADD COLUMN new_day_hour INTEGER NOT NULL DEFAULT 2
+    CHECK (new_day_hour >= 0 AND new_day_hour <= 23);
+ALTER TABLE userconfig ADD COLUMN deadline_cutoff_hour INTEGER NOT NULL DEFAULT 14
+    CHECK (deadline_cutoff_hour >= new_day_hour AND deadline_cutoff_hour <= 23);
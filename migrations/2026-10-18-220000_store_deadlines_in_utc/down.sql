@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE deadlines DROP COLUMN deadline_tz;
+UPDATE deadlines SET deadline_date = datetime(deadline_date, 'localtime') WHERE deadline_date IS NOT NULL;
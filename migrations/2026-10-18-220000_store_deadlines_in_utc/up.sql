@@ -0,0 +1,6 @@
+-- deadlines were stored as local wall-clock times; they become UTC instants,
+-- converted with this machine's zone rules for each date. `deadline_tz` is the
+-- IANA zone a deadline was set in, unknown for deadlines set before this
+
+UPDATE deadlines SET deadline_date = datetime(deadline_date, 'utc') WHERE deadline_date IS NOT NULL;
+ALTER TABLE deadlines ADD COLUMN deadline_tz TEXT;
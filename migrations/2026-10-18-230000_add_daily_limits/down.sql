@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE deadlines DROP COLUMN reviews_per_day;
+ALTER TABLE deadlines DROP COLUMN new_per_day;
+ALTER TABLE decks DROP COLUMN reviews_per_day;
@@ -0,0 +1,6 @@
+-- daily cap on reviews of a deck, and daily caps on new cards and reviews
+-- shared by all decks of an ankibox; NULL means no limit
+
+ALTER TABLE decks ADD COLUMN reviews_per_day INTEGER CHECK (reviews_per_day >= 0);
+ALTER TABLE deadlines ADD COLUMN new_per_day INTEGER CHECK (new_per_day >= 0);
+ALTER TABLE deadlines ADD COLUMN reviews_per_day INTEGER CHECK (reviews_per_day >= 0);
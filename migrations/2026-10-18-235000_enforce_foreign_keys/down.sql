@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`; the orphaned rows are gone for good
+CREATE TABLE old_ankiquotas (
+    deck_id           INTEGER NOT NULL,
+    date_practiced    DATE NOT NULL,
+    new_practiced     INTEGER NOT NULL,
+    review_practiced  INTEGER NOT NULL,
+    PRIMARY KEY (deck_id, date_practiced)
+    FOREIGN KEY (deck_id) REFERENCES decks(id)
+);
+INSERT INTO old_ankiquotas SELECT * FROM ankiquotas;
+DROP TABLE ankiquotas;
+ALTER TABLE old_ankiquotas RENAME TO ankiquotas;
@@ -0,0 +1,28 @@
+-- foreign keys are enforced from now on: remove the rows left behind while
+-- they were not, and let ankiquotas go with their deck like the other tables
+
+DELETE FROM parents WHERE parent_id NOT IN (SELECT id FROM entries) OR child_id NOT IN (SELECT id FROM entries);
+DELETE FROM folders WHERE id NOT IN (SELECT id FROM entries);
+DELETE FROM deadlines WHERE id NOT IN (SELECT id FROM entries);
+DELETE FROM decks WHERE id NOT IN (SELECT id FROM entries);
+DELETE FROM ankioptions WHERE entry_id NOT IN (SELECT id FROM entries);
+DELETE FROM quotas WHERE id NOT IN (SELECT id FROM decks);
+DELETE FROM clozes WHERE deck_id NOT IN (SELECT id FROM decks);
+DELETE FROM cards WHERE deck_id NOT IN (SELECT id FROM decks) OR cloze_id NOT IN (SELECT id FROM clozes);
+DELETE FROM cards WHERE reverse_of NOT IN (SELECT id FROM cards);
+DELETE FROM card_tags WHERE card_id NOT IN (SELECT id FROM cards) OR tag_id NOT IN (SELECT id FROM tags);
+DELETE FROM reviewlog WHERE card_id NOT IN (SELECT id FROM cards) OR deck_id NOT IN (SELECT id FROM decks);
+
+CREATE TABLE new_ankiquotas (
+    deck_id           INTEGER NOT NULL,
+    date_practiced    DATE NOT NULL,
+    new_practiced     INTEGER NOT NULL,
+    review_practiced  INTEGER NOT NULL,
+    PRIMARY KEY (deck_id, date_practiced),
+    FOREIGN KEY (deck_id) REFERENCES decks (id) ON DELETE CASCADE
+);
+INSERT INTO new_ankiquotas
+    SELECT deck_id, date_practiced, new_practiced, review_practiced FROM ankiquotas
+    WHERE deck_id IN (SELECT id FROM decks);
+DROP TABLE ankiquotas;
+ALTER TABLE new_ankiquotas RENAME TO ankiquotas;
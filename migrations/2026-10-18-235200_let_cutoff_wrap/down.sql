@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`; a wrapped cutoff becomes the new-day hour
+CREATE TABLE old_userconfig (
+    config_id             INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    is_dark_mode          BOOLEAN NOT NULL,
+    is_text_field         BOOLEAN NOT NULL,
+    new_day_hour          INTEGER NOT NULL DEFAULT 2
+        CHECK (new_day_hour >= 0 AND new_day_hour <= 23),
+    deadline_cutoff_hour  INTEGER NOT NULL DEFAULT 14
+        CHECK (deadline_cutoff_hour >= new_day_hour AND deadline_cutoff_hour <= 23)
+);
+INSERT INTO old_userconfig
+    SELECT config_id, is_dark_mode, is_text_field, new_day_hour, MAX(deadline_cutoff_hour, new_day_hour) FROM userconfig;
+DROP TABLE userconfig;
+ALTER TABLE old_userconfig RENAME TO userconfig;
@@ -0,0 +1,17 @@
+-- a deadline cutoff earlier than the new-day hour falls on the next morning, so
+-- the check keeping it at or after the new-day hour goes; SQLite can only drop
+-- a check by rebuilding the table
+
+CREATE TABLE new_userconfig (
+    config_id             INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    is_dark_mode          BOOLEAN NOT NULL,
+    is_text_field         BOOLEAN NOT NULL,
+    new_day_hour          INTEGER NOT NULL DEFAULT 2
+        CHECK (new_day_hour >= 0 AND new_day_hour <= 23),
+    deadline_cutoff_hour  INTEGER NOT NULL DEFAULT 14
+        CHECK (deadline_cutoff_hour >= 0 AND deadline_cutoff_hour <= 23)
+);
+INSERT INTO new_userconfig
+    SELECT config_id, is_dark_mode, is_text_field, new_day_hour, deadline_cutoff_hour FROM userconfig;
+DROP TABLE userconfig;
+ALTER TABLE new_userconfig RENAME TO userconfig;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM reviewlog WHERE undone_at IS NOT NULL;
+ALTER TABLE reviewlog DROP COLUMN undone_at;
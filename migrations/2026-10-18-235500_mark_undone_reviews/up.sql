@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- answers taken back in a review session are kept, marked with when they were taken back
+ALTER TABLE reviewlog ADD COLUMN undone_at TIMESTAMP;
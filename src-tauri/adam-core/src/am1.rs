@@ -0,0 +1,300 @@
+use diesel::update;
+use diesel::prelude::*;
+
+use rand::distributions::Distribution;
+use rand::distributions::WeightedIndex;
+
+use crate::home::{Quota, compute_num_boxes_from_id};
+use crate::edit::{get_days_to_go, write_quotas};
+use crate::models::NewReviewLog;
+use crate::render::render_card;
+use crate::review::{ReviewCard, get_queue_score};
+use crate::review_log::CardState;
+use crate::scheduler::Scheduler;
+use crate::utils::{handle_missed_days, study_today};
+use crate::error::{Error, Result, Context};
+
+
+/**
+ * AM-1: cards climb through boxes towards a deadline, and each deck's
+ * progressions are spread over the remaining days in `quotas`
+ */
+pub struct Am1Scheduler {
+    pub deadline_id: i32,
+    // fixed when the scheduler is created, so a session keeps its day
+    pub days_to_go: i32
+}
+
+impl Am1Scheduler {
+    pub fn new(conn: &mut SqliteConnection, deadline_id: i32) -> Result<Self> {
+        let days_to_go = get_days_to_go(conn, deadline_id)?;
+        Ok(Am1Scheduler { deadline_id, days_to_go })
+    }
+}
+
+
+impl Scheduler for Am1Scheduler {
+    fn new_card_state(&self, conn: &mut SqliteConnection, _deck_id: i32) -> Result<CardState> {
+        Ok(CardState {
+            box_position: Some(0),
+            repetitions: None,
+            easiness: None,
+            interval: None,
+            next_practice: Some(study_today(conn)?),
+            stability: None,
+            difficulty: None,
+            last_review: None,
+            learning_step: None,
+            due_at: None,
+            lapses: None
+        })
+    }
+
+    fn cards_created(&self, conn: &mut SqliteConnection, deck_id: i32, num_cards: i32) -> Result<()> {
+        write_quotas(conn, self.deadline_id, deck_id, num_cards)
+    }
+
+    fn card_deleted(&self, conn: &mut SqliteConnection, deck_id: i32, state: &CardState) -> Result<()> {
+        use crate::schema::quotas;
+
+        let box_pos = state.box_position
+            .ok_or_else(|| Error::IntegrityViolation(format!("card of deck {} has no box position", deck_id)))?;
+
+        // quotas of a passed deadline are rebuilt by `reset_deadline`
+        let days_to_go = self.days_to_go;
+        if days_to_go < 0 {
+            return Ok(());
+        }
+        let num_boxes = compute_num_boxes_from_id(conn, self.deadline_id)?;
+
+        let mut quota_records = quotas::table
+            .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.lt(days_to_go + 1)))
+            .select((quotas::days_to_go, quotas::new_assigned, quotas::review_assigned))
+            .get_results::<(i32, i32, i32)>(conn)?;
+
+        quota_records.sort_by_key(|&record| record.0);
+
+        let mut q_to_sub = num_boxes - box_pos;
+        if q_to_sub <= 0 || quota_records.is_empty() { return Ok(()); }
+
+        // subtract from final day quotas
+        quota_records[0].2 -= 1;
+        q_to_sub -= 1;
+
+        // subtract one from new quota, starting from the day furthest from the deadline
+        if q_to_sub == 0 { return write_back_quotas(conn, deck_id, quota_records); }
+        if box_pos == 0 {
+            for record in quota_records.iter_mut().rev() {
+                if record.1 > 0 {
+                    record.1 -= 1;
+                    q_to_sub -= 1;
+                    break;
+                }
+            }
+        }
+
+        // subtract rest from review quotas
+        for record in quota_records.iter_mut() {
+            if q_to_sub == 0 {
+                break;
+            }
+            if record.2 > 0 {
+                let sub_amt = std::cmp::min(record.2, q_to_sub);
+                record.2 -= sub_amt;
+                q_to_sub -= sub_amt;
+            }
+        }
+
+        write_back_quotas(conn, deck_id, quota_records)
+    }
+
+    fn deck_quota(&self, conn: &mut SqliteConnection, deck_id: i32) -> Result<Option<Quota>> {
+        use crate::schema::quotas;
+
+        handle_missed_days(conn, deck_id, &self.days_to_go)?;
+
+        let entry_quota = quotas::table
+            .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(self.days_to_go)))
+            .select((quotas::new_assigned, quotas::review_assigned, quotas::new_practiced, quotas::review_practiced))
+            .get_result::<(i32, i32, i32, i32)>(conn)
+            .optional()?;
+
+        Ok(entry_quota.map(|(new_assigned, review_assigned, new_prac, review_prac)| Quota {
+            new_left: new_assigned,
+            review_left: review_assigned,
+            num_progressed: new_prac + review_prac
+        }))
+    }
+
+    fn select_new_cards(&self, conn: &mut SqliteConnection, deck_id: i32, limit: i32) -> Result<Vec<i32>> {
+        use crate::schema::cards;
+
+        Ok(cards::table
+            .filter(cards::deck_id.eq(deck_id).and(cards::box_position.eq(0)))
+            .select(cards::id)
+            .limit(limit as i64)
+            .get_results::<i32>(conn)?)
+    }
+
+    fn pop_review_card(&self, conn: &mut SqliteConnection, deck_id: i32, only: Option<&[i32]>) -> Result<Option<ReviewCard>> {
+        use crate::schema::{cards, entries};
+
+        let mut query = cards::table
+            .filter(cards::deck_id.eq(deck_id))
+            .select(cards::id)
+            .into_boxed();
+        if let Some(only) = only {
+            query = query.filter(cards::id.eq_any(only));
+        }
+        let card_ids = query.get_results::<i32>(conn)?;
+
+        // in terms of SQL
+        // let box_counts = diesel::sql_query("SELECT box_position, COUNT ( * ) FROM quotas GROUP BY box_position")
+        //     .load(conn)
+        //     .expect("failed to load box counts");
+
+        let box_counts = cards::table
+            .filter(cards::id.eq_any(&card_ids).and(cards::box_position.is_not_null()))
+            .select((cards::box_position, diesel::dsl::sql::<diesel::sql_types::BigInt>("count(*)"))) // https://github.com/diesel-rs/diesel/issues/1781#issuecomment-633174958
+            .group_by(cards::box_position)
+            .get_results::<(Option<i32>, i64)>(conn)?;
+        if box_counts.is_empty() {
+            return Ok(None);
+        }
+
+        // choose box with probability weighted by number of cards in the box
+        let box_pos = choose_weighted_index(&box_counts)?;
+
+        let card = cards::table
+            .filter(cards::id.eq_any(card_ids).and(cards::box_position.eq(box_pos)))
+            .select((cards::id, cards::front, cards::back, cards::format))
+            .order(cards::queue_score.asc())
+            .first::<(i32, String, String, String)>(conn)
+            .context(&format!("review card in deck {}", deck_id))?;
+
+        let deck_name = entries::table
+            .filter(entries::id.eq(deck_id))
+            .select(entries::name)
+            .get_result::<String>(conn)
+            .context(&format!("deck {}", deck_id))?;
+
+        Ok(Some(ReviewCard {
+            stack_before: String::from("review"),
+            deck_name,
+            card: render_card(card.0, card.1, card.2, card.3)
+        }))
+    }
+
+    // score is -1, 0, or 1
+    fn grade(&self, conn: &mut SqliteConnection, card: &ReviewCard, score: i32) -> Result<String> {
+        use crate::schema::cards;
+        // update card box_pos
+        let box_pos_delta = get_box_pos_delta(conn, score, &card.card.id)?;
+
+        // update card's contents and box pos, returning new box pos
+        update(cards::table)
+            .filter(cards::id.eq(card.card.id))
+            .set((cards::box_position.eq(cards::box_position + box_pos_delta), cards::front.eq(&card.card.front), cards::back.eq(&card.card.back), cards::queue_score.eq(get_queue_score())))
+            .execute(conn)?;
+
+        // get deck id
+        let deck_id = cards::table
+            .filter(cards::id.eq(card.card.id))
+            .select(cards::deck_id)
+            .get_result::<i32>(conn)?;
+
+        // update quota
+        self.add_progressions(conn, deck_id, &card.stack_before, box_pos_delta)?;
+
+        // append card to responseStack
+        let stack_after = if score == 1 {
+            "done"
+        } else {
+            &card.stack_before
+        };
+        Ok(String::from(stack_after))
+    }
+
+    fn shift_quota(&self, conn: &mut SqliteConnection, log: &NewReviewLog, sign: i32) -> Result<()> {
+        let (Some(box_before), Some(box_after)) = (log.box_before, log.box_after) else {
+            return Err(Error::IntegrityViolation(format!("answer to card {} has no box positions", log.card_id)));
+        };
+
+        self.add_progressions(conn, log.deck_id, &log.stack_before, sign * (box_after - box_before))
+    }
+}
+
+
+impl Am1Scheduler {
+    // moves `delta` progressions of today's quota from assigned to practiced
+    fn add_progressions(&self, conn: &mut SqliteConnection, deck_id: i32, stack_before: &str, delta: i32) -> Result<()> {
+        use crate::schema::quotas;
+
+        if stack_before == "new" {
+            update(quotas::table)
+                .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(self.days_to_go)))
+                .set((quotas::new_assigned.eq(quotas::new_assigned - delta), quotas::new_practiced.eq(quotas::new_practiced + delta)))
+                .execute(conn)?;
+        } else {
+            update(quotas::table)
+                .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(self.days_to_go)))
+                .set((quotas::review_assigned.eq(quotas::review_assigned - delta), quotas::review_practiced.eq(quotas::review_practiced + delta)))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+// writes (days_to_go, new_assigned, review_assigned) records of `deck_id` back to `quotas`
+fn write_back_quotas(conn: &mut SqliteConnection, deck_id: i32, quota_records: Vec<(i32, i32, i32)>) -> Result<()> {
+    use crate::schema::quotas;
+
+    for quota_record in quota_records {
+        update(quotas::table)
+            .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(quota_record.0)))
+            .set((quotas::new_assigned.eq(quota_record.1), quotas::review_assigned.eq(quota_record.2)))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+// returns box position
+fn choose_weighted_index(pos_weights: &[(Option<i32>, i64)]) -> Result<i32> {
+    let mut v = Vec::new();
+    for w in pos_weights {
+        v.push(w.1 as i32);
+    }
+
+    let n = v.len();
+    let weights = (0..n).map(|i| 1.0 / (i as f32 + 1.0)).collect::<Vec<_>>();
+    let dist = WeightedIndex::new(&weights)
+        .map_err(|err| Error::SchedulingError(format!("cannot choose a box: {}", err)))?;
+    let mut rng = rand::thread_rng();
+    let idx = dist.sample(&mut rng);
+
+
+    pos_weights[idx].0
+        .ok_or_else(|| Error::IntegrityViolation(String::from("review card has no box position")))
+}
+
+fn get_box_pos_delta(conn: &mut SqliteConnection, score: i32, card_id: &i32) -> Result<i32> {
+    use crate::schema::cards;
+
+    let box_pos = cards::table
+        .filter(cards::id.eq(card_id))
+        .select(cards::box_position)
+        .get_result::<Option<i32>>(conn)
+        .context(&format!("card {}", card_id))?
+        .ok_or_else(|| Error::IntegrityViolation(format!("card {} has no box position", card_id)))?;
+
+    let mut box_pos_delta = 0;
+    if score == -1 && box_pos > 1 {
+        box_pos_delta = -1;
+    } else if score == 1 {
+        box_pos_delta = 1;
+    }
+    Ok(box_pos_delta)
+}
@@ -0,0 +1,951 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use crate::review::{ReviewCard, get_queue_score};
+use crate::home::Quota;
+use crate::models::NewReviewLog;
+use crate::render::render_card;
+use crate::review_log::CardState;
+use crate::scheduler::Scheduler;
+use crate::utils::study_today;
+use crate::error::{Error, Result, Context};
+
+
+use diesel::{delete, insert_into, update};
+use diesel::prelude::*;
+use rand::Rng;
+// use diesel::serialize::ToSql;
+
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+
+/**
+ * SM-2 settings of an ankibox or one of its decks. Intervals are in days,
+ * steps are space separated durations such as "1m 10m 1h", see `parse_steps`
+ */
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone, PartialEq)]
+pub struct AnkiOptions {
+    // ease of new cards
+    pub starting_ease: f32,
+    // lapses and hard answers never take the ease below this
+    pub minimum_ease: f32,
+    // graduating intervals: after the first and the second successful answer
+    pub first_interval: i32,
+    pub second_interval: i32,
+    // extra factor on the interval of an easy answer
+    pub easy_bonus: f32,
+    // factor on the previous interval of a hard answer
+    pub hard_multiplier: f32,
+    // factor on every review interval, to trade retention for workload
+    pub interval_modifier: f32,
+    pub maximum_interval: i32,
+    // new cards are repeated after each of these delays before graduating
+    #[serde(default = "default_learning_steps")]
+    pub learning_steps: String,
+    // same for lapsed cards; without steps they are repeated once today
+    #[serde(default = "default_relearning_steps")]
+    pub relearning_steps: String
+}
+
+fn default_learning_steps() -> String {
+    String::from("1m 10m")
+}
+
+fn default_relearning_steps() -> String {
+    String::from("10m")
+}
+
+impl Default for AnkiOptions {
+    fn default() -> Self {
+        AnkiOptions {
+            starting_ease: 2.5,
+            minimum_ease: 1.4,
+            first_interval: 1,
+            second_interval: 6,
+            easy_bonus: 1.3,
+            hard_multiplier: 1.2,
+            interval_modifier: 1.0,
+            maximum_interval: 36500,
+            learning_steps: default_learning_steps(),
+            relearning_steps: default_relearning_steps()
+        }
+    }
+}
+
+
+pub fn pop_review_anki_card(conn: &mut SqliteConnection, deck_id: i32, only: Option<&[i32]>) -> Result<Option<ReviewCard>> {
+    use crate::schema::{cards, entries};
+
+    // get all cards that are due today, relearning cards once their step is over
+    let today = study_today(conn)?;
+    let now = Utc::now().naive_utc();
+    let (_, reviews_left) = limits_left(conn, deck_id, today)?;
+    let mut query = cards::table
+        .filter(cards::next_practice.le(today).and(cards::deck_id.eq(deck_id))) // filter cards whose next_practice is in the past
+        .filter(cards::repetitions.gt(0))
+        .filter(cards::due_at.is_null().or(cards::due_at.le(now)))
+        // relearning cards first, in the order they became due
+        .order((cards::due_at.is_null(), cards::due_at.asc(), cards::queue_score.asc()))
+        .select((cards::id, cards::front, cards::back, cards::format))
+        .into_boxed();
+    if let Some(only) = only {
+        query = query.filter(cards::id.eq_any(only));
+    }
+    // past the daily review limit, only relearning cards are left to finish
+    if reviews_left.is_some_and(|reviews_left| reviews_left <= 0) {
+        query = query.filter(cards::due_at.is_not_null());
+    }
+    let Some(popped_card) = query.first::<(i32, String, String, String)>(conn).optional()? else {
+        return Ok(None);
+    };
+
+    let deck_name = entries::table
+        .filter(entries::id.eq(deck_id))
+        .select(entries::name)
+        .get_result::<String>(conn)?;
+
+    Ok(Some(ReviewCard { 
+        stack_before: String::from("review"), 
+        deck_name, 
+        card: render_card(popped_card.0, popped_card.1, popped_card.2, popped_card.3)
+    }))
+}
+
+
+pub struct SmResponse {
+    interval: i32, // The interval between repetitions (in days).
+    repetitions: i32,// The number of times a user has seen the flashcard.
+    ease_factor: f32,// The easiness factor for the flashcard.
+}
+
+// quality is score, and it ranges 1-5. A score of 3 or more is a success
+// again hard okay good easy
+pub fn calculate_sm(
+    quality: i32,
+    repetitions: i32,
+    previous_interval: i32,
+    previous_ease_factor: f32,
+    options: &AnkiOptions,
+) -> SmResponse {
+    let (interval, repetitions, ease_factor) = if quality >= 3 {
+        let interval = match repetitions {
+            0 => options.first_interval,
+            1 => options.second_interval,
+            _ => {
+                let easy_bonus = if quality == 5 { options.easy_bonus } else { 1.0 };
+                (previous_interval as f32 * previous_ease_factor * options.interval_modifier * easy_bonus).round() as i32
+            }
+        };
+
+        let repetitions = repetitions + 1;
+        let ease_factor =
+            previous_ease_factor + (0.1 - (5 - quality) as f32 * (0.08 + (5 - quality) as f32 * 0.02));
+
+        (interval.max(1), repetitions, ease_factor)
+    } else if quality == 1 {
+        // repeat if pressing again
+        (0, repetitions, previous_ease_factor)
+    } else {
+        let interval = (previous_interval as f32 * options.hard_multiplier * options.interval_modifier).round() as i32;
+        (interval.max(1), repetitions, previous_ease_factor)
+    };
+
+    let ease_factor = ease_factor.max(options.minimum_ease);
+    let interval = interval.min(options.maximum_interval);
+
+    SmResponse {
+        interval,
+        repetitions,
+        ease_factor,
+    }
+}
+
+
+
+/**
+ * Grades an ankibox card. New cards go through the learning steps and lapsed
+ * cards through the relearning steps before they are scheduled in days;
+ * returns "done" once the card is not due again today
+ */
+pub fn update_card_anki(conn: &mut SqliteConnection, card: &ReviewCard, score: i32) -> Result<String> {
+    use crate::schema::cards;
+
+    // get card's current stats
+    let (deck_id, ease_factor, interval, repetitions, learning_step, lapses) = cards::table
+        .filter(cards::id.eq(card.card.id))
+        .select((cards::deck_id, cards::easiness, cards::interval, cards::repetitions, cards::learning_step, cards::lapses))
+        .get_result::<(i32, Option<f32>, Option<i32>, Option<i32>, Option<i32>, Option<i32>)>(conn)
+        .context(&format!("card {}", card.card.id))?;
+
+    let (repetitions, interval, ease_factor) = match (repetitions, interval, ease_factor) {
+        (Some(r), Some(i), Some(e)) => (r, i, e),
+        _ => return Err(Error::IntegrityViolation(format!("card {} has no SM-2 state", card.card.id)))
+    };
+    let options = read_anki_options(conn, deck_id)?;
+
+    let mut step = schedule_step(score, repetitions, interval, ease_factor, learning_step, lapses.unwrap_or(0), &options)?;
+
+    // cards in a step are due again today
+    let today = study_today(conn)?;
+    if step.due_at.is_none() {
+        step.interval = balance_interval(conn, deck_id, card.card.id, step.interval, today, options.maximum_interval)?;
+    }
+    let next_practice = match step.due_at {
+        Some(_) => today,
+        None => today + Duration::days(i64::from(step.interval))
+    };
+
+    // update card's contents and scheduling
+    update(cards::table)
+        .filter(cards::id.eq(card.card.id))
+        .set((
+            cards::front.eq(&card.card.front), 
+            cards::back.eq(&card.card.back), 
+            cards::queue_score.eq(get_queue_score()),
+            cards::next_practice.eq(next_practice),
+            cards::easiness.eq(step.ease_factor),
+            cards::interval.eq(step.interval),
+            cards::repetitions.eq(step.repetitions),
+            cards::learning_step.eq(step.learning_step),
+            cards::due_at.eq(step.due_at),
+            cards::lapses.eq(step.lapses)
+        ))
+        .execute(conn)?;
+
+    if next_practice > today { 
+        add_ankiquota_practice(conn, deck_id, &card.stack_before)?;
+        Ok(String::from("done"))
+    } else { 
+        Ok(String::from(&card.stack_before))
+    }
+
+}
+
+
+// scheduling of a card after an answer; `due_at` is set while the card is
+// in a (re)learning step
+struct AnkiStep {
+    interval: i32,
+    repetitions: i32,
+    ease_factor: f32,
+    learning_step: Option<i32>,
+    due_at: Option<NaiveDateTime>,
+    lapses: i32
+}
+
+fn schedule_step(
+    quality: i32,
+    repetitions: i32,
+    interval: i32,
+    ease_factor: f32,
+    learning_step: Option<i32>,
+    lapses: i32,
+    options: &AnkiOptions
+) -> Result<AnkiStep> {
+    let learning_steps = parse_steps(&options.learning_steps)?;
+    let relearning_steps = parse_steps(&options.relearning_steps)?;
+    let now = Utc::now().naive_utc();
+
+    // new cards learn, lapsed cards relearn
+    let steps = if repetitions == 0 { &learning_steps } else { &relearning_steps };
+    let is_learning = !steps.is_empty() && (repetitions == 0 || learning_step.is_some());
+
+    if is_learning {
+        let current = learning_step.unwrap_or(0).clamp(0, steps.len() as i32 - 1);
+        // again restarts the steps, hard repeats the current one, easy graduates at once
+        let next = match quality {
+            1 => Some(0),
+            2 => Some(current),
+            5 => None,
+            _ => Some(current + 1).filter(|next| *next < steps.len() as i32)
+        };
+
+        return Ok(match next {
+            Some(next) => AnkiStep {
+                interval,
+                repetitions,
+                ease_factor,
+                learning_step: Some(next),
+                due_at: Some(now + steps[next as usize]),
+                lapses
+            },
+            None if repetitions == 0 => {
+                let sm = calculate_sm(quality, repetitions, interval, ease_factor, options);
+                AnkiStep {
+                    interval: sm.interval,
+                    repetitions: sm.repetitions,
+                    ease_factor: sm.ease_factor,
+                    learning_step: None,
+                    due_at: None,
+                    lapses
+                }
+            },
+            // relearnt cards keep the interval set when they lapsed
+            None => AnkiStep {
+                interval: interval.max(1),
+                repetitions,
+                ease_factor,
+                learning_step: None,
+                due_at: None,
+                lapses
+            }
+        });
+    }
+
+    let sm = calculate_sm(quality, repetitions, interval, ease_factor, options);
+    let is_lapse = quality == 1 && repetitions > 0;
+    let (learning_step, due_at) = match relearning_steps.first() {
+        Some(first) if is_lapse => (Some(0), Some(now + *first)),
+        _ => (None, None)
+    };
+
+    Ok(AnkiStep {
+        interval: sm.interval,
+        repetitions: sm.repetitions,
+        ease_factor: sm.ease_factor,
+        learning_step,
+        due_at,
+        lapses: lapses + is_lapse as i32
+    })
+}
+
+
+/**
+ * Spreads reviews over the days around `interval`: returns the interval
+ * within its fuzz range, see `fuzz_range`, on which the fewest other cards of
+ * the deck are due, picking at random among equally loaded days. This keeps
+ * cards learned together from staying due on the same days
+ */
+pub fn balance_interval(
+    conn: &mut SqliteConnection,
+    deck_id: i32,
+    card_id: i32,
+    interval: i32,
+    today: NaiveDate,
+    maximum_interval: i32
+) -> Result<i32> {
+    use crate::schema::cards;
+
+    let (shortest, longest) = fuzz_range(interval, maximum_interval);
+    if shortest == longest {
+        return Ok(shortest);
+    }
+
+    let due_dates = cards::table
+        .filter(cards::deck_id.eq(deck_id).and(cards::id.ne(card_id)))
+        .filter(cards::next_practice.between(today + Duration::days(i64::from(shortest)), today + Duration::days(i64::from(longest))))
+        .select(cards::next_practice)
+        .get_results::<Option<NaiveDate>>(conn)?;
+    let load = |interval: i32| due_dates.iter()
+        .filter(|date| **date == Some(today + Duration::days(i64::from(interval))))
+        .count();
+
+    let least_load = (shortest..=longest).map(load).min().unwrap_or(0);
+    let least_loaded: Vec<i32> = (shortest..=longest)
+        .filter(|interval| load(*interval) == least_load)
+        .collect();
+    Ok(least_loaded[rand::thread_rng().gen_range(0..least_loaded.len())])
+}
+
+// intervals a card may get instead of `interval`: none below 3 days, then
+// 15%, 10% and from 20 days 5% either way, at least a day, and never beyond
+// `maximum_interval`
+fn fuzz_range(interval: i32, maximum_interval: i32) -> (i32, i32) {
+    let interval = interval.min(maximum_interval);
+    if interval < 3 {
+        return (interval, interval);
+    }
+    let fuzz = if interval < 7 { 0.15 } else if interval < 20 { 0.1 } else { 0.05 };
+    let delta = ((interval as f32 * fuzz).round() as i32).max(1);
+    ((interval - delta).max(2).min(maximum_interval), (interval + delta).min(maximum_interval))
+}
+
+
+/**
+ * Parses learning steps such as "1m 10m 1h 1d": whole minutes, hours or
+ * days, a bare number being minutes. An empty string means no steps
+ */
+pub fn parse_steps(steps: &str) -> Result<Vec<Duration>> {
+    steps.split_whitespace()
+        .map(|step| {
+            let (number, unit) = match step.find(|c: char| !c.is_ascii_digit()) {
+                Some(i) => step.split_at(i),
+                None => (step, "m")
+            };
+            let number = number.parse::<i64>().ok().filter(|number| *number >= 1 && *number <= 100_000);
+            match (number, unit) {
+                (Some(n), "m") => Ok(Duration::minutes(n)),
+                (Some(n), "h") => Ok(Duration::hours(n)),
+                (Some(n), "d") => Ok(Duration::days(n)),
+                _ => Err(Error::InvalidInput(format!("step \"{}\" is not a duration such as 10m, 1h or 1d", step)))
+            }
+        })
+        .collect()
+}
+
+
+/**
+ * SM-2 options of an ankibox or deck: the deck's own, else its ankibox's,
+ * else the defaults
+ */
+pub fn read_anki_options(conn: &mut SqliteConnection, entry_id: i32) -> Result<AnkiOptions> {
+    use crate::schema::ankioptions;
+
+    let ankibox_id = get_options_ankibox(conn, entry_id)?;
+    for id in [entry_id, ankibox_id] {
+        let options = ankioptions::table
+            .find(id)
+            .select((ankioptions::starting_ease, ankioptions::minimum_ease, ankioptions::first_interval,
+                ankioptions::second_interval, ankioptions::easy_bonus, ankioptions::hard_multiplier,
+                ankioptions::interval_modifier, ankioptions::maximum_interval, ankioptions::learning_steps,
+                ankioptions::relearning_steps))
+            .get_result::<AnkiOptions>(conn)
+            .optional()?;
+        if let Some(options) = options {
+            return Ok(options);
+        }
+    }
+    Ok(AnkiOptions::default())
+}
+
+
+/**
+ * Sets the SM-2 options of an ankibox, or of one deck to override its
+ * ankibox's. Answers and cards created afterwards use them
+ */
+pub fn write_anki_options(conn: &mut SqliteConnection, entry_id: i32, options: &AnkiOptions) -> Result<()> {
+    use crate::schema::ankioptions;
+
+    get_options_ankibox(conn, entry_id)?;
+    check_anki_options(options)?;
+
+    conn.transaction(|conn| {
+        delete(ankioptions::table.find(entry_id)).execute(conn)?;
+        insert_into(ankioptions::table)
+            .values((
+                ankioptions::entry_id.eq(entry_id),
+                ankioptions::starting_ease.eq(options.starting_ease),
+                ankioptions::minimum_ease.eq(options.minimum_ease),
+                ankioptions::first_interval.eq(options.first_interval),
+                ankioptions::second_interval.eq(options.second_interval),
+                ankioptions::easy_bonus.eq(options.easy_bonus),
+                ankioptions::hard_multiplier.eq(options.hard_multiplier),
+                ankioptions::interval_modifier.eq(options.interval_modifier),
+                ankioptions::maximum_interval.eq(options.maximum_interval),
+                ankioptions::learning_steps.eq(options.learning_steps.trim()),
+                ankioptions::relearning_steps.eq(options.relearning_steps.trim())
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+
+/**
+ * Drops the SM-2 options of an ankibox or deck, so a deck goes back to its
+ * ankibox's and an ankibox to the defaults
+ */
+pub fn reset_anki_options(conn: &mut SqliteConnection, entry_id: i32) -> Result<()> {
+    use crate::schema::ankioptions;
+
+    get_options_ankibox(conn, entry_id)?;
+    delete(ankioptions::table.find(entry_id)).execute(conn)?;
+    Ok(())
+}
+
+
+// the ankibox `entry_id` is or belongs to; other entries have no SM-2 options
+fn get_options_ankibox(conn: &mut SqliteConnection, entry_id: i32) -> Result<i32> {
+    let (ankibox_id, is_fsrs) = get_entry_box(conn, entry_id)?;
+    if is_fsrs {
+        return Err(Error::InvalidInput(format!("entry {} is not an ankibox or one of its decks", entry_id)));
+    }
+    Ok(ankibox_id)
+}
+
+// the ankibox or FSRS box `entry_id` is or belongs to, and whether it is an FSRS box
+fn get_entry_box(conn: &mut SqliteConnection, entry_id: i32) -> Result<(i32, bool)> {
+    use crate::schema::{decks, deadlines, parents};
+
+    let is_deck = decks::table
+        .find(entry_id)
+        .select(decks::id)
+        .get_result::<i32>(conn)
+        .optional()?
+        .is_some();
+    let ankibox_id = if is_deck {
+        parents::table
+            .filter(parents::child_id.eq(entry_id))
+            .select(parents::parent_id)
+            .get_result::<i32>(conn)
+            .context(&format!("deadline of deck {}", entry_id))?
+    } else {
+        entry_id
+    };
+
+    let (is_anki, is_fsrs) = deadlines::table
+        .find(ankibox_id)
+        .select((deadlines::is_anki, deadlines::is_fsrs))
+        .get_result::<(bool, bool)>(conn)
+        .context(&format!("entry {}", entry_id))?;
+    if !is_anki {
+        return Err(Error::InvalidInput(format!("entry {} is not an ankibox or one of its decks", entry_id)));
+    }
+    Ok((ankibox_id, is_fsrs))
+}
+
+fn check_anki_options(options: &AnkiOptions) -> Result<()> {
+    parse_steps(&options.learning_steps)?;
+    parse_steps(&options.relearning_steps)?;
+
+    let factors = [options.starting_ease, options.minimum_ease, options.easy_bonus, options.hard_multiplier, options.interval_modifier];
+    let problem = if factors.iter().any(|factor| !factor.is_finite()) {
+        "eases and factors must be numbers"
+    } else if options.minimum_ease < 1.0 {
+        "minimum ease must be at least 1"
+    } else if options.starting_ease < options.minimum_ease {
+        "starting ease must be at least the minimum ease"
+    } else if options.first_interval < 1 {
+        "first interval must be at least 1 day"
+    } else if options.second_interval < options.first_interval {
+        "second interval must be at least the first"
+    } else if options.easy_bonus < 1.0 {
+        "easy bonus must be at least 1"
+    } else if options.hard_multiplier <= 0.0 {
+        "hard multiplier must be positive"
+    } else if options.interval_modifier <= 0.0 {
+        "interval modifier must be positive"
+    } else if options.maximum_interval < options.second_interval {
+        "maximum interval must be at least the second interval"
+    } else {
+        return Ok(());
+    };
+    Err(Error::InvalidInput(String::from(problem)))
+}
+
+
+/**
+ * Daily limits of an ankibox or FSRS box deck, or of a whole box: new cards
+ * learned and reviews done a day. None means no limit; decks always have a
+ * new card limit, and a box's limits are shared by all of its decks
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DailyLimits {
+    pub new_per_day: Option<i32>,
+    pub reviews_per_day: Option<i32>
+}
+
+
+/**
+ * Daily limits of an ankibox, an FSRS box or one of their decks
+ */
+pub fn read_daily_limits(conn: &mut SqliteConnection, entry_id: i32) -> Result<DailyLimits> {
+    use crate::schema::{decks, deadlines};
+
+    let (box_id, _) = get_entry_box(conn, entry_id)?;
+    let (new_per_day, reviews_per_day) = if box_id == entry_id {
+        deadlines::table
+            .find(entry_id)
+            .select((deadlines::new_per_day, deadlines::reviews_per_day))
+            .get_result::<(Option<i32>, Option<i32>)>(conn)?
+    } else {
+        decks::table
+            .find(entry_id)
+            .select((decks::new_per_day, decks::reviews_per_day))
+            .get_result::<(Option<i32>, Option<i32>)>(conn)?
+    };
+    Ok(DailyLimits { new_per_day, reviews_per_day })
+}
+
+
+/**
+ * Sets the daily limits of an ankibox, an FSRS box or one of their decks.
+ * Today's quotas follow at once, counting what was already studied today
+ */
+pub fn write_daily_limits(conn: &mut SqliteConnection, entry_id: i32, limits: &DailyLimits) -> Result<()> {
+    use crate::schema::{decks, deadlines};
+
+    let (box_id, _) = get_entry_box(conn, entry_id)?;
+    if [limits.new_per_day, limits.reviews_per_day].iter().flatten().any(|limit| *limit < 0) {
+        return Err(Error::InvalidInput(String::from("daily limits cannot be negative")));
+    }
+
+    if box_id == entry_id {
+        update(deadlines::table.find(entry_id))
+            .set((
+                deadlines::new_per_day.eq(limits.new_per_day),
+                deadlines::reviews_per_day.eq(limits.reviews_per_day)
+            ))
+            .execute(conn)?;
+    } else {
+        if limits.new_per_day.is_none() {
+            return Err(Error::InvalidInput(String::from("a deck needs a number of new cards per day")));
+        }
+        update(decks::table.find(entry_id))
+            .set((
+                decks::new_per_day.eq(limits.new_per_day),
+                decks::reviews_per_day.eq(limits.reviews_per_day)
+            ))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+
+// new cards and reviews `deck_id` may still take today under its own and its
+// box's limits, the reviews None if neither limits them
+fn limits_left(conn: &mut SqliteConnection, deck_id: i32, today: NaiveDate) -> Result<(i32, Option<i32>)> {
+    use crate::schema::{decks, parents};
+
+    let (new_per_day, reviews_per_day) = decks::table
+        .find(deck_id)
+        .select((decks::new_per_day, decks::reviews_per_day))
+        .get_result::<(Option<i32>, Option<i32>)>(conn)
+        .context(&format!("deck {}", deck_id))?;
+    let new_per_day = new_per_day
+        .ok_or_else(|| Error::IntegrityViolation(format!("ankibox deck {} has no new cards per day", deck_id)))?;
+    let (new_prac, rev_prac) = practiced_today(conn, &[deck_id], today)?;
+
+    let box_id = parents::table
+        .filter(parents::child_id.eq(deck_id))
+        .select(parents::parent_id)
+        .get_result::<i32>(conn)
+        .context(&format!("deadline of deck {}", deck_id))?;
+    let (box_new_left, box_reviews_left) = box_limits_left(conn, box_id, today)?;
+
+    let new_left = new_per_day - new_prac;
+    let reviews_left = reviews_per_day.map(|limit| limit - rev_prac);
+    Ok((
+        box_new_left.map_or(new_left, |box_left| new_left.min(box_left)),
+        [reviews_left, box_reviews_left].into_iter().flatten().min()
+    ))
+}
+
+// new cards and reviews all decks of box `box_id` together may still take
+// today, each None if the box does not limit it
+fn box_limits_left(conn: &mut SqliteConnection, box_id: i32, today: NaiveDate) -> Result<(Option<i32>, Option<i32>)> {
+    use crate::schema::{deadlines, parents};
+
+    let (new_per_day, reviews_per_day) = deadlines::table
+        .find(box_id)
+        .select((deadlines::new_per_day, deadlines::reviews_per_day))
+        .get_result::<(Option<i32>, Option<i32>)>(conn)
+        .context(&format!("deadline {}", box_id))?;
+    if new_per_day.is_none() && reviews_per_day.is_none() {
+        return Ok((None, None));
+    }
+
+    let deck_ids = parents::table
+        .filter(parents::parent_id.eq(box_id))
+        .select(parents::child_id)
+        .get_results::<i32>(conn)?;
+    let (new_prac, rev_prac) = practiced_today(conn, &deck_ids, today)?;
+    Ok((new_per_day.map(|limit| limit - new_prac), reviews_per_day.map(|limit| limit - rev_prac)))
+}
+
+// new cards and reviews of `deck_ids` counted as practiced on `today`
+fn practiced_today(conn: &mut SqliteConnection, deck_ids: &[i32], today: NaiveDate) -> Result<(i32, i32)> {
+    use crate::schema::ankiquotas;
+
+    let practiced = ankiquotas::table
+        .filter(ankiquotas::date_practiced.eq(today).and(ankiquotas::deck_id.eq_any(deck_ids)))
+        .select((ankiquotas::new_practiced, ankiquotas::review_practiced))
+        .get_results::<(i32, i32)>(conn)?;
+    Ok(practiced.into_iter().fold((0, 0), |(new, review), (new_prac, rev_prac)| (new + new_prac, review + rev_prac)))
+}
+
+// relearning cards of `deck_id` due today; review limits never hold them back
+fn count_relearning(conn: &mut SqliteConnection, deck_id: i32, today: NaiveDate) -> Result<i32> {
+    use crate::schema::cards;
+
+    let count = cards::table
+        .filter(cards::deck_id.eq(deck_id).and(cards::next_practice.le(today)))
+        .filter(cards::repetitions.gt(0).and(cards::due_at.is_not_null()))
+        .count()
+        .get_result::<i64>(conn)?;
+    Ok(count as i32)
+}
+
+// hands `left` cards out one at a time across decks wanting `wanted` each,
+// so that a box limit is shared rather than taken by the first deck
+fn share_box_limit(left: i32, wanted: &mut [i32]) {
+    let mut granted = vec![0; wanted.len()];
+    let mut left = left;
+    while left > 0 {
+        let mut any_granted = false;
+        for (granted, wanted) in granted.iter_mut().zip(wanted.iter()) {
+            if left > 0 && *granted < *wanted {
+                *granted += 1;
+                left -= 1;
+                any_granted = true;
+            }
+        }
+        if !any_granted {
+            break;
+        }
+    }
+    wanted.copy_from_slice(&granted);
+}
+
+
+// counts a card from `stack_before` as practiced today in `ankiquotas`
+pub fn add_ankiquota_practice(conn: &mut SqliteConnection, deck_id: i32, stack_before: &str) -> Result<()> {
+    use crate::schema::ankiquotas;
+
+    // get current day in anki quota
+    let today = study_today(conn)?;
+    let prac_new = (stack_before == "new") as i32;
+    let prac_review = (stack_before == "review") as i32;
+
+    update(ankiquotas::table)
+        .filter(ankiquotas::date_practiced.eq(today).and(ankiquotas::deck_id.eq(deck_id)))
+        .set((
+            ankiquotas::new_practiced.eq(ankiquotas::new_practiced + prac_new), 
+            ankiquotas::review_practiced.eq(ankiquotas::review_practiced + prac_review), 
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+// state of a card no one has answered yet, due on study day `today`
+pub(crate) fn new_sm2_state(options: &AnkiOptions, today: NaiveDate) -> CardState {
+    CardState {
+        box_position: None,
+        repetitions: Some(0),
+        easiness: Some(options.starting_ease),
+        interval: Some(options.first_interval),
+        next_practice: Some(today),
+        stability: None,
+        difficulty: None,
+        last_review: None,
+        learning_step: None,
+        due_at: None,
+        lapses: Some(0)
+    }
+}
+
+
+/**
+ * SM-2 ankibox: cards are due on their `next_practice` date and each deck
+ * learns up to `new_per_day` new cards a day, counted in `ankiquotas`
+ */
+pub struct Sm2Scheduler;
+
+impl Scheduler for Sm2Scheduler {
+    fn new_card_state(&self, conn: &mut SqliteConnection, deck_id: i32) -> Result<CardState> {
+        Ok(new_sm2_state(&read_anki_options(conn, deck_id)?, study_today(conn)?))
+    }
+
+    // ankibox quotas are counted from the cards due each day
+    fn cards_created(&self, _conn: &mut SqliteConnection, _deck_id: i32, _num_cards: i32) -> Result<()> {
+        Ok(())
+    }
+
+    fn card_deleted(&self, _conn: &mut SqliteConnection, _deck_id: i32, _state: &CardState) -> Result<()> {
+        Ok(())
+    }
+
+    // new cards and reviews are capped by the deck's and its ankibox's daily
+    // limits; relearning cards are always left to finish
+    fn deck_quota(&self, conn: &mut SqliteConnection, deck_id: i32) -> Result<Option<Quota>> {
+        use crate::schema::{cards, ankiquotas};
+
+        // forgetting to find whether next_practice is before today
+        let today = study_today(conn)?;
+        let card_reps = cards::table
+            .filter(cards::deck_id.eq(deck_id).and(cards::next_practice.le(today).or(cards::next_practice.is_null())))
+            .select(cards::repetitions)
+            .get_results::<Option<i32>>(conn)?;
+
+        let (mut num_new, mut num_review) = (0, 0);
+        for rep in card_reps {
+            if rep.unwrap_or(0) > 0 {
+                num_review += 1;
+            } else {
+                num_new += 1;
+            }
+        }
+
+        let results: Option<(i32, i32)> = ankiquotas::table
+            .filter(ankiquotas::date_practiced.eq(today).and(ankiquotas::deck_id.eq(deck_id)))
+            .select((ankiquotas::new_practiced, ankiquotas::review_practiced))
+            .get_result::<(i32, i32)>(conn)
+            .optional()?;
+
+        let mut num_progressed = 0;
+        if let Some((new_prac, rev_prac)) = results {
+            num_progressed = new_prac + rev_prac;
+        } else {
+            insert_into(ankiquotas::table)
+                .values((
+                        ankiquotas::deck_id.eq(deck_id), 
+                        ankiquotas::date_practiced.eq(today), 
+                        ankiquotas::new_practiced.eq(0), 
+                        ankiquotas::review_practiced.eq(0)
+                ))
+                .execute(conn)?;
+        }
+
+        let (new_left, reviews_left) = limits_left(conn, deck_id, today)?;
+        num_new = num_new.min(new_left).max(0);
+        if let Some(reviews_left) = reviews_left {
+            let num_relearning = count_relearning(conn, deck_id, today)?;
+            num_review = num_relearning + (num_review - num_relearning).min(reviews_left).max(0);
+        }
+
+        Ok(Some(Quota {
+            new_left: num_new,
+            review_left: num_review,
+            num_progressed
+        }))
+    }
+
+    // the decks share what the ankibox's own limits leave, turn by turn
+    fn cap_deadline_quotas(&self, conn: &mut SqliteConnection, deadline_id: i32, deck_ids: &[i32], quotas: &mut [Quota]) -> Result<()> {
+        let today = study_today(conn)?;
+        let (box_new_left, box_reviews_left) = box_limits_left(conn, deadline_id, today)?;
+
+        if let Some(box_new_left) = box_new_left {
+            let mut new_left: Vec<i32> = quotas.iter().map(|quota| quota.new_left).collect();
+            share_box_limit(box_new_left, &mut new_left);
+            for (quota, new_left) in quotas.iter_mut().zip(new_left) {
+                quota.new_left = new_left;
+            }
+        }
+
+        if let Some(box_reviews_left) = box_reviews_left {
+            let mut num_relearning = Vec::new();
+            for deck_id in deck_ids {
+                num_relearning.push(count_relearning(conn, *deck_id, today)?);
+            }
+            let mut review_left: Vec<i32> = quotas.iter().zip(&num_relearning)
+                .map(|(quota, num_relearning)| (quota.review_left - num_relearning).max(0))
+                .collect();
+            share_box_limit(box_reviews_left, &mut review_left);
+            for ((quota, review_left), num_relearning) in quotas.iter_mut().zip(review_left).zip(num_relearning) {
+                quota.review_left = quota.review_left.min(num_relearning + review_left);
+            }
+        }
+        Ok(())
+    }
+
+    // cards already in their learning steps are picked first
+    fn select_new_cards(&self, conn: &mut SqliteConnection, deck_id: i32, limit: i32) -> Result<Vec<i32>> {
+        use crate::schema::cards;
+
+        Ok(cards::table
+            .filter(cards::deck_id.eq(deck_id).and(cards::repetitions.eq(0)))
+            .order((cards::learning_step.is_null(), cards::id.asc()))
+            .select(cards::id)
+            .limit(limit as i64)
+            .get_results::<i32>(conn)?)
+    }
+
+    fn pop_review_card(&self, conn: &mut SqliteConnection, deck_id: i32, only: Option<&[i32]>) -> Result<Option<ReviewCard>> {
+        pop_review_anki_card(conn, deck_id, only)
+    }
+
+    // score is 1-5
+    fn grade(&self, conn: &mut SqliteConnection, card: &ReviewCard, score: i32) -> Result<String> {
+        update_card_anki(conn, card, score)
+    }
+
+    // only answers that scheduled the card for a later day count as practiced
+    fn shift_quota(&self, conn: &mut SqliteConnection, log: &NewReviewLog, sign: i32) -> Result<()> {
+        use crate::schema::ankiquotas;
+
+        if log.stack_after != "done" {
+            return Ok(());
+        }
+
+        let today = study_today(conn)?;
+        let prac_new = sign * (log.stack_before == "new") as i32;
+        let prac_review = sign * (log.stack_before == "review") as i32;
+
+        update(ankiquotas::table)
+            .filter(ankiquotas::date_practiced.eq(today).and(ankiquotas::deck_id.eq(log.deck_id)))
+            .set((
+                ankiquotas::new_practiced.eq(ankiquotas::new_practiced + prac_new), 
+                ankiquotas::review_practiced.eq(ankiquotas::review_practiced + prac_review), 
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{test_connection, root_id, create_test_entry};
+
+    const MAX_INTERVAL: i32 = 36500;
+
+    #[test]
+    fn no_fuzz_below_three_days() {
+        for interval in 0..3 {
+            assert_eq!(fuzz_range(interval, MAX_INTERVAL), (interval, interval));
+        }
+    }
+
+    #[test]
+    fn fuzz_at_small_intervals() {
+        // 15% of 3 to 6 days rounds to a day, and the range starts at 2 days
+        assert_eq!(fuzz_range(3, MAX_INTERVAL), (2, 4));
+        assert_eq!(fuzz_range(6, MAX_INTERVAL), (5, 7));
+        assert_eq!(fuzz_range(7, MAX_INTERVAL), (6, 8));
+        assert_eq!(fuzz_range(19, MAX_INTERVAL), (17, 21));
+        assert_eq!(fuzz_range(20, MAX_INTERVAL), (19, 21));
+        assert_eq!(fuzz_range(100, MAX_INTERVAL), (95, 105));
+    }
+
+    #[test]
+    fn fuzz_stays_within_the_maximum_interval() {
+        assert_eq!(fuzz_range(MAX_INTERVAL, MAX_INTERVAL), (MAX_INTERVAL - 1825, MAX_INTERVAL));
+        assert_eq!(fuzz_range(MAX_INTERVAL - 10, MAX_INTERVAL), (MAX_INTERVAL - 1835, MAX_INTERVAL));
+        assert_eq!(fuzz_range(10, 10), (9, 10));
+        // an interval above the maximum is fuzzed as the maximum
+        assert_eq!(fuzz_range(4, 3), (2, 3));
+        assert_eq!(fuzz_range(40, 10), (9, 10));
+        assert_eq!(fuzz_range(5, 2), (2, 2));
+        assert_eq!(fuzz_range(MAX_INTERVAL + 100, MAX_INTERVAL), (MAX_INTERVAL - 1825, MAX_INTERVAL));
+
+        for interval in 3..=MAX_INTERVAL {
+            let (shortest, longest) = fuzz_range(interval, MAX_INTERVAL);
+            assert!(shortest >= 2 && shortest < interval && interval <= longest && longest <= MAX_INTERVAL, "{}", interval);
+        }
+    }
+
+    #[test]
+    fn balanced_interval_avoids_loaded_days() {
+        use crate::schema::cards;
+
+        let conn = &mut test_connection();
+        let root_id = root_id(conn);
+        let ankibox_id = create_test_entry(conn, "ankibox", root_id, "ankibox", 0);
+        let deck_id = create_test_entry(conn, "deck", ankibox_id, "deck", 0);
+        let empty_deck_id = create_test_entry(conn, "empty", ankibox_id, "deck", 0);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        // days 9 and 11 of the 9-11 range of 10 days already have cards due
+        for days in [9, 9, 10, 11, 11] {
+            insert_into(cards::table)
+                .values((cards::deck_id.eq(deck_id), cards::front.eq(""), cards::back.eq(""),
+                    cards::next_practice.eq(today + Duration::days(days))))
+                .execute(conn)
+                .unwrap();
+        }
+
+        for _ in 0..20 {
+            assert_eq!(balance_interval(conn, deck_id, 0, 10, today, MAX_INTERVAL).unwrap(), 10);
+            assert_eq!(balance_interval(conn, deck_id, 0, 2, today, MAX_INTERVAL).unwrap(), 2);
+            assert_eq!(balance_interval(conn, deck_id, 0, 10, today, 10).unwrap(), 10);
+            assert_eq!(balance_interval(conn, deck_id, 0, 10, today, 2).unwrap(), 2);
+            let interval = balance_interval(conn, empty_deck_id, 0, 10, today, MAX_INTERVAL).unwrap();
+            assert!((9..=11).contains(&interval));
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use serde::{
+    Serialize,
+    Deserialize
+};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+
+// numbers within this fraction of the expected one count as equal
+const NUMERIC_TOLERANCE: f64 = 0.01;
+
+
+/**
+ * How a typed answer compares to the back of a card
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnswerCheck {
+    // 1.0 for a match after normalization, down to 0.0
+    pub similarity: f32,
+    // score to preselect in the review screen; the user may still pick another
+    pub suggested_score: i32,
+    pub diff: Vec<DiffToken>,
+    // `diff` as HTML, one `<span class="answer-...">` per token
+    pub diff_html: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiffToken {
+    pub text: String,
+    pub kind: DiffKind
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    // in both the answer and the back
+    Correct,
+    // on the back but not in the answer
+    Missing,
+    // in the answer but not on the back
+    Wrong
+}
+
+
+/**
+ * Compares `answer` to `expected`, both as card HTML. Case, diacritics,
+ * punctuation and spacing are ignored, and numbers match within 1%. Cloze
+ * backs are compared on the hidden text only. The suggested score is on the
+ * AM-1 scale (-1, 0, 1) unless `is_anki`, then on the SM-2 scale (1-5).
+ * Returns None when nothing was typed
+ */
+pub fn check_answer(expected: &str, answer: &str, is_anki: bool) -> Option<AnswerCheck> {
+    let answer_tokens = tokenize(&html_to_text(answer));
+    if answer_tokens.is_empty() {
+        return None;
+    }
+    let expected = cloze_answer(expected).unwrap_or_else(|| html_to_text(expected));
+    let expected_tokens = tokenize(&expected);
+    let diff = diff_tokens(&expected_tokens, &answer_tokens);
+
+    let similarity = if expected_tokens.len() == 1 && answer_tokens.len() == 1
+        && numbers_match(&number_values(&expected_tokens[0]), &number_values(&answer_tokens[0])) {
+        1.0
+    } else {
+        similarity(&fold(&expected_tokens.join(" ")), &fold(&answer_tokens.join(" ")))
+    };
+
+    Some(AnswerCheck {
+        similarity,
+        suggested_score: suggest_score(similarity, is_anki),
+        diff_html: diff_to_html(&diff),
+        diff
+    })
+}
+
+
+/**
+ * Score to suggest for an answer of `similarity`, on the AM-1 or SM-2 scale
+ */
+pub fn suggest_score(similarity: f32, is_anki: bool) -> i32 {
+    if is_anki {
+        match similarity {
+            s if s >= 1.0 => 5,
+            s if s >= 0.9 => 4,
+            s if s >= 0.75 => 3,
+            s if s >= 0.5 => 2,
+            _ => 1
+        }
+    } else {
+        match similarity {
+            s if s >= 0.9 => 1,
+            s if s >= 0.6 => 0,
+            _ => -1
+        }
+    }
+}
+
+
+// plain text of card HTML; tags that break lines become spaces
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut tag = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                tag.clear();
+            },
+            '>' if in_tag => {
+                in_tag = false;
+                let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+                if ["br", "div", "p", "li", "tr", "td", "h1", "h2", "h3", "h4", "h5", "h6"].contains(&name.to_lowercase().as_str()) {
+                    text.push(' ');
+                }
+            },
+            c if in_tag => tag.push(c),
+            c => text.push(c)
+        }
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// hidden text of a cloze back, see `cloze::render_cloze`
+fn cloze_answer(back: &str) -> Option<String> {
+    const OPEN: &str = "<span class=\"cloze\">";
+
+    let parts: Vec<String> = back.match_indices(OPEN)
+        .filter_map(|(idx, _)| {
+            let rest = &back[idx + OPEN.len()..];
+            rest.find("</span>").map(|end| html_to_text(&rest[..end]))
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+// words of `text`, without the punctuation around them
+fn tokenize(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            // a sign or leading point belongs to a number
+            let is_number = word.chars().any(|c| c.is_ascii_digit());
+            word.trim_start_matches(|c: char| c.is_ascii_punctuation() && !(is_number && (c == '-' || c == '.')))
+                .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        })
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// lowercase without diacritics, so "Éclair" matches "eclair"
+fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+// values of a number written with a decimal point or comma, and optionally
+// with the other as thousands separator: "3.14", "3,14", "1,000.5", "1.000,5".
+// "1,000" and "1.000" may be either, so they have both values; words have none
+fn number_values(token: &str) -> Vec<f64> {
+    let is_digits = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+    let parse = |decimal: Option<char>, thousands: Option<char>| {
+        let number: String = token.chars()
+            .filter(|c| Some(*c) != thousands)
+            .map(|c| if Some(c) == decimal { '.' } else { c })
+            .collect();
+        number.parse::<f64>().ok().filter(|number| number.is_finite())
+    };
+
+    let readings = match (token.rfind(','), token.rfind('.')) {
+        (Some(comma), Some(dot)) if comma > dot => vec![(Some(','), Some('.'))],
+        (Some(_), Some(_)) => vec![(Some('.'), Some(','))],
+        (None, None) => vec![(None, None)],
+        (comma, _) => {
+            let separator = if comma.is_some() { ',' } else { '.' };
+            let Some((before, after)) = token.rsplit_once(separator) else { return Vec::new() };
+            let lead = before.trim_start_matches('-');
+            if before.contains(separator) {
+                // only thousands come in groups, as in "1,000,000"
+                vec![(None, Some(separator))]
+            } else if is_digits(lead) && lead.len() <= 3 && lead != "0" && after.len() == 3 && is_digits(after) {
+                vec![(Some(separator), None), (None, Some(separator))]
+            } else {
+                vec![(Some(separator), None)]
+            }
+        }
+    };
+    readings.into_iter()
+        .filter_map(|(decimal, thousands)| parse(decimal, thousands))
+        .collect()
+}
+
+fn numbers_match(expected: &[f64], answer: &[f64]) -> bool {
+    expected.iter().any(|expected| answer.iter()
+        .any(|answer| (expected - answer).abs() <= NUMERIC_TOLERANCE * expected.abs().max(f64::EPSILON)))
+}
+
+// a token as the diff compares it: folded, and its value if it is a number
+struct ComparedToken {
+    folded: String,
+    numbers: Vec<f64>
+}
+
+fn compared_tokens(tokens: &[String]) -> Vec<ComparedToken> {
+    tokens.iter()
+        .map(|token| ComparedToken { folded: fold(token), numbers: number_values(token) })
+        .collect()
+}
+
+fn tokens_match(expected: &ComparedToken, answer: &ComparedToken) -> bool {
+    expected.folded == answer.folded || numbers_match(&expected.numbers, &answer.numbers)
+}
+
+// 1 - Levenshtein distance / length of the longer string, over characters
+fn similarity(expected: &str, answer: &str) -> f32 {
+    let expected: Vec<char> = expected.chars().collect();
+    let answer: Vec<char> = answer.chars().collect();
+    let max_len = expected.len().max(answer.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    let mut prev: Vec<usize> = (0..=answer.len()).collect();
+    let mut curr = vec![0; answer.len() + 1];
+    for i in 1..=expected.len() {
+        curr[0] = i;
+        for j in 1..=answer.len() {
+            let substitution = prev[j - 1] + usize::from(expected[i - 1] != answer[j - 1]);
+            curr[j] = substitution.min(prev[j] + 1).min(curr[j - 1] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    1.0 - prev[answer.len()] as f32 / max_len as f32
+}
+
+// aligns the tokens on their longest common subsequence
+fn diff_tokens(expected: &[String], answer: &[String]) -> Vec<DiffToken> {
+    let (n, m) = (expected.len(), answer.len());
+    let (expected_compared, answer_compared) = (compared_tokens(expected), compared_tokens(answer));
+    // lcs[i][j]: length of the longest common subsequence of expected[i..] and answer[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if tokens_match(&expected_compared[i], &answer_compared[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let token = |text: &String, kind| DiffToken { text: text.clone(), kind };
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if tokens_match(&expected_compared[i], &answer_compared[j]) {
+            diff.push(token(&expected[i], DiffKind::Correct));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(token(&expected[i], DiffKind::Missing));
+            i += 1;
+        } else {
+            diff.push(token(&answer[j], DiffKind::Wrong));
+            j += 1;
+        }
+    }
+    diff.extend(expected[i..].iter().map(|text| token(text, DiffKind::Missing)));
+    diff.extend(answer[j..].iter().map(|text| token(text, DiffKind::Wrong)));
+    diff
+}
+
+fn diff_to_html(diff: &[DiffToken]) -> String {
+    diff.iter()
+        .map(|token| {
+            let class = match token.kind {
+                DiffKind::Correct => "answer-correct",
+                DiffKind::Missing => "answer-missing",
+                DiffKind::Wrong => "answer-wrong"
+            };
+            let text = token.text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            format!("<span class=\"{}\">{}</span>", class, text)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(expected: &str, answer: &str) -> f32 {
+        check_answer(expected, answer, true).unwrap().similarity
+    }
+
+    #[test]
+    fn case_diacritics_and_punctuation_are_ignored() {
+        assert_eq!(score("<div>Éclair au chocolat</div>", "eclair  au chocolat!"), 1.0);
+        assert_eq!(score("<div>Salt &amp; pepper</div>", "salt & Pepper"), 1.0);
+    }
+
+    #[test]
+    fn similarity_falls_with_edits() {
+        let one_typo = score("<div>Paris</div>", "pariss");
+        assert!(one_typo > 0.8 && one_typo < 1.0);
+        assert!(score("<div>Paris</div>", "london") < 0.5);
+        assert!(check_answer("<div>Paris</div>", " ", true).is_none());
+    }
+
+    #[test]
+    fn numbers_match_within_tolerance() {
+        assert_eq!(score("<div>3.14159</div>", "3.14"), 1.0);
+        assert!(score("<div>3.14159</div>", "3.0") < 1.0);
+        assert_eq!(score("<div>-0.5</div>", "-.5"), 1.0);
+    }
+
+    #[test]
+    fn decimal_commas() {
+        assert_eq!(number_values("2,5"), vec![2.5]);
+        assert_eq!(number_values("1.000,5"), vec![1000.5]);
+        assert_eq!(number_values("1,000.5"), vec![1000.5]);
+        assert_eq!(number_values("1,000,000"), vec![1000000.0]);
+        assert_eq!(number_values("0,125"), vec![0.125]);
+        assert_eq!(number_values("1,000"), vec![1.0, 1000.0]);
+        assert!(number_values("word").is_empty());
+
+        assert_eq!(score("<div>2.75</div>", "2,75"), 1.0);
+        assert!(score("<div>15</div>", "1,5") < 1.0);
+        assert_eq!(score("<div>1,000</div>", "1000"), 1.0);
+    }
+
+    #[test]
+    fn diff_marks_missing_and_wrong_words() {
+        let diff = check_answer("<div>the quick brown fox</div>", "the quik fox jumps", true).unwrap().diff;
+        let kinds: Vec<(&str, DiffKind)> = diff.iter().map(|token| (token.text.as_str(), token.kind)).collect();
+        assert_eq!(kinds, vec![
+            ("the", DiffKind::Correct),
+            ("quick", DiffKind::Missing),
+            ("brown", DiffKind::Missing),
+            ("quik", DiffKind::Wrong),
+            ("fox", DiffKind::Correct),
+            ("jumps", DiffKind::Wrong)
+        ]);
+    }
+
+    #[test]
+    fn cloze_backs_compare_the_hidden_text() {
+        assert_eq!(score("The capital is <span class=\"cloze\">Paris</span>.", "Paris"), 1.0);
+    }
+
+    #[test]
+    fn suggested_scores() {
+        assert_eq!(suggest_score(1.0, true), 5);
+        assert_eq!(suggest_score(0.8, true), 3);
+        assert_eq!(suggest_score(0.1, true), 1);
+        assert_eq!(suggest_score(0.95, false), 1);
+        assert_eq!(suggest_score(0.3, false), -1);
+    }
+}
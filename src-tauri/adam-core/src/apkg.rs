@@ -0,0 +1,878 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Text};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::edit::{insert_deck_contents, DeckNewContents};
+use crate::home::{create_deck, get_entry_type};
+use crate::media::{media_html, media_references, read_media, store_media, MediaFile, MEDIA_SCHEME};
+use crate::models::NewCard;
+use crate::render::{render_html, HTML_FORMAT};
+use crate::review_log::{write_card_state, CardState};
+use crate::scheduler::get_scheduler;
+use crate::tags::{add_imported_tags, read_card_tags};
+use crate::utils::{get_is_anki, study_today};
+use crate::error::{Error, Result};
+
+
+// fields of an Anki note are separated by the unit separator
+const FIELD_SEPARATOR: char = '\x1f';
+
+
+/**
+ * Outcome of an export: the number of cards exported, and the media they
+ * refer to that were missing from the media store and left out
+ */
+#[derive(Serialize, Debug)]
+pub struct ApkgExport {
+    pub num_cards: usize,
+    pub missing_media: Vec<String>
+}
+
+
+/**
+ * First card of an Anki note, with the note's fields and the card's scheduling
+ */
+#[derive(QueryableByName, Debug)]
+struct AnkiCard {
+    #[diesel(sql_type = BigInt)]
+    did: i64,
+    #[diesel(sql_type = Text)]
+    flds: String,
+    // separated by spaces
+    #[diesel(sql_type = Text)]
+    tags: String,
+    // 0 new, 1 learning, 2 review, 3 relearning
+    #[diesel(sql_type = Integer)]
+    ctype: i32,
+    // like `ctype`, but negative when suspended or buried
+    #[diesel(sql_type = Integer)]
+    queue: i32,
+    // position for new cards, epoch seconds for learning cards, days since `crt` otherwise
+    #[diesel(sql_type = BigInt)]
+    due: i64,
+    #[diesel(sql_type = Integer)]
+    ivl: i32,
+    // ease in permille, e.g. 2500
+    #[diesel(sql_type = Integer)]
+    factor: i32,
+    #[diesel(sql_type = Integer)]
+    reps: i32,
+    #[diesel(sql_type = Integer)]
+    lapses: i32
+}
+
+#[derive(QueryableByName)]
+struct AnkiCol {
+    // collection creation time in epoch seconds; review due dates count days from it
+    #[diesel(sql_type = BigInt)]
+    crt: i64,
+    #[diesel(sql_type = Text)]
+    decks: String
+}
+
+struct AnkiCollection {
+    crt: i64,
+    deck_names: HashMap<i64, String>,
+    cards: Vec<AnkiCard>,
+    // contents of the package's media files by the name the notes use
+    media: Vec<(String, Vec<u8>)>
+}
+
+
+/**
+ * Imports the notes of an Anki `.apkg` into a deadline or ankibox, creating one
+ * deck per Anki deck and returning the ids of the created decks. Ankiboxes
+ * keep each card's SM-2 state and due date; AM-1 deadlines start every card new.
+ * The package's images and audio go to the media store in `media_dir`, and
+ * the notes' references to them are pointed there
+ */
+pub fn import_apkg(conn: &mut SqliteConnection, path: &Path, deadline_id: i32, media_dir: &Path) -> Result<Vec<i32>> {
+    let collection = read_collection(path)?;
+
+    let scheduler = get_scheduler(conn, deadline_id)?;
+    let is_anki = get_is_anki(conn, deadline_id)?;
+
+    let today = study_today(conn)?;
+    let crt_date = local_date(collection.crt).unwrap_or(today);
+
+    // group notes by the Anki deck of their first card, in order of deck name
+    let mut decks: BTreeMap<String, Vec<&AnkiCard>> = BTreeMap::new();
+    for card in &collection.cards {
+        let deck_name = collection.deck_names.get(&card.did)
+            .cloned()
+            .unwrap_or_else(|| format!("Anki deck {}", card.did));
+        decks.entry(deck_name).or_default().push(card);
+    }
+
+    // media other than images and audio, e.g. fonts of a note type, are not
+    // kept; media of a failed import are unreferenced and collected later
+    let mut media_files = HashMap::new();
+    for (name, data) in &collection.media {
+        match store_media(conn, media_dir, name, data) {
+            Ok(file) => { media_files.insert(name.clone(), file); },
+            Err(Error::InvalidInput(_)) => {},
+            Err(err) => return Err(err)
+        }
+    }
+
+    // a failed import leaves no half-filled decks behind
+    conn.transaction(|conn| {
+        let mut deck_ids = Vec::new();
+        for (deck_name, anki_cards) in decks {
+            let deck_id = create_deck(conn, deadline_id, &deck_name)?;
+            let new_state = scheduler.new_card_state(conn, deck_id)?;
+
+            let deck_new_contents = DeckNewContents {
+                deck_id,
+                deck_name,
+                cards: anki_cards.iter().map(|card| note_to_card(&card.flds, &media_files)).collect()
+            };
+            let card_ids = insert_deck_contents(conn, deck_new_contents, &new_state)?;
+
+            for (card_id, anki_card) in card_ids.iter().zip(&anki_cards) {
+                let tags: Vec<String> = anki_card.tags.split_whitespace().map(String::from).collect();
+                add_imported_tags(conn, *card_id, &tags)?;
+            }
+
+            if is_anki {
+                for (card_id, anki_card) in card_ids.iter().zip(&anki_cards) {
+                    if let Some(state) = anki_card_state(anki_card, crt_date, today) {
+                        write_card_state(conn, *card_id, &state)?;
+                    }
+                }
+            }
+
+            scheduler.cards_created(conn, deck_id, card_ids.len() as i32)?;
+            deck_ids.push(deck_id);
+        }
+
+        Ok(deck_ids)
+    })
+}
+
+
+/**
+ * Exports a deck, or every deck of a deadline or ankibox, to an Anki `.apkg`
+ * at `path` as Basic notes. Cards with SM-2 repetitions keep their ease,
+ * interval and due date; all others, including every AM-1 card, are exported
+ * new. Media the cards refer to are read from `media_dir` and packed with
+ * them; those missing from it are exported without and returned
+ */
+pub fn export_apkg(conn: &mut SqliteConnection, entry_id: i32, path: &Path, media_dir: &Path) -> Result<ApkgExport> {
+    use crate::schema::{cards, entries, parents};
+
+    let entry_name = entries::table
+        .find(entry_id)
+        .select(entries::name)
+        .get_result::<String>(conn)
+        .map_err(|_| Error::NotFound(format!("entry {}", entry_id)))?;
+
+    // (id, Anki deck name) of the decks to export; decks of a deadline become its subdecks
+    let entry_type = get_entry_type(conn, entry_id)?;
+    let is_deck = entry_type == "deck";
+    let decks: Vec<(i32, String)> = match entry_type.as_str() {
+        "deck" => vec![(entry_id, entry_name.clone())],
+        "folder" => return Err(Error::InvalidInput(String::from("only decks, deadlines and ankiboxes can be exported"))),
+        _ => parents::table
+            .inner_join(entries::table.on(entries::id.eq(parents::child_id)))
+            .filter(parents::parent_id.eq(entry_id))
+            .select((entries::id, entries::name))
+            .order(entries::id.asc())
+            .get_results::<(i32, String)>(conn)?
+            .into_iter()
+            .map(|(deck_id, deck_name)| (deck_id, format!("{}::{}", entry_name, deck_name)))
+            .collect()
+    };
+
+    let now = Local::now();
+    let today = study_today(conn)?;
+    // Anki counts due days from the collection's creation; starting it today makes today day 0
+    let crt = Local.from_local_datetime(&today.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map_or(now.timestamp(), |dt| dt.timestamp());
+    let mod_secs = now.timestamp();
+    let base_id = now.timestamp_millis();
+    let model_id = base_id;
+
+    let temp = TempFile::new("anki2");
+    let temp_url = temp.0.to_string_lossy().to_string();
+    let mut anki = SqliteConnection::establish(&temp_url)
+        .map_err(|err| Error::IoError(format!("cannot create Anki collection: {}", err)))?;
+    anki.batch_execute(ANKI_SCHEMA)?;
+
+    let mut anki_decks = serde_json::Map::new();
+    anki_decks.insert(String::from("1"), anki_deck(1, "Default", mod_secs));
+    if !is_deck {
+        let parent_did = base_id + 1 + decks.len() as i64;
+        anki_decks.insert(parent_did.to_string(), anki_deck(parent_did, &entry_name, mod_secs));
+    }
+
+    // Anki refers to media by bare file name
+    let mut media_files = BTreeSet::new();
+    let mut num_cards = 0;
+    for (i, (deck_id, deck_name)) in decks.iter().enumerate() {
+        let did = base_id + 1 + i as i64;
+        anki_decks.insert(did.to_string(), anki_deck(did, deck_name, mod_secs));
+
+        let deck_cards = cards::table
+            .filter(cards::deck_id.eq(deck_id))
+            .select((cards::id, cards::front, cards::back, cards::format, cards::repetitions, cards::easiness, cards::interval, cards::next_practice, cards::lapses))
+            .order(cards::id.asc())
+            .get_results::<(i32, String, String, String, Option<i32>, Option<f32>, Option<i32>, Option<NaiveDate>, Option<i32>)>(conn)?;
+        let deck_card_ids: Vec<i32> = deck_cards.iter().map(|card| card.0).collect();
+        let card_tags = read_card_tags(conn, &deck_card_ids)?;
+
+        for (card_id, front, back, format, repetitions, easiness, interval, next_practice, lapses) in deck_cards {
+            let id = base_id + num_cards as i64;
+            // Anki shows fields as HTML, so Markdown goes out rendered
+            let front = render_html(&front, &format);
+            let back = render_html(&back, &format);
+            media_files.extend(media_references(&front).into_iter().chain(media_references(&back)));
+            let front = front.replace(MEDIA_SCHEME, "");
+            let back = back.replace(MEDIA_SCHEME, "");
+            let sort_field = strip_html(&front);
+            // Anki pads the tag list with spaces
+            let tags = card_tags.get(&card_id)
+                .map_or(String::new(), |tags| format!(" {} ", tags.join(" ")));
+
+            diesel::sql_query("INSERT INTO notes VALUES (?, ?, ?, ?, -1, ?, ?, ?, ?, 0, '')")
+                .bind::<BigInt, _>(id)
+                .bind::<Text, _>(format!("adam-{}", card_id))
+                .bind::<BigInt, _>(model_id)
+                .bind::<BigInt, _>(mod_secs)
+                .bind::<Text, _>(&tags)
+                .bind::<Text, _>(format!("{}{}{}", front, FIELD_SEPARATOR, back))
+                .bind::<Text, _>(&sort_field)
+                .bind::<BigInt, _>(field_checksum(&sort_field))
+                .execute(&mut anki)?;
+
+            // (type, queue, due, ivl, factor, reps); new cards are due in creation order
+            let scheduling = match repetitions {
+                Some(reps) if reps > 0 => {
+                    let due = next_practice.map_or(0, |date| (date - today).num_days());
+                    let factor = (easiness.unwrap_or(2.5) * 1000.0).round() as i32;
+                    (2, 2, due, interval.unwrap_or(1).max(1), factor, reps)
+                },
+                _ => (0, 0, num_cards as i64 + 1, 0, 0, 0)
+            };
+
+            diesel::sql_query("INSERT INTO cards VALUES (?, ?, ?, 0, ?, -1, ?, ?, ?, ?, ?, ?, ?, 0, 0, 0, 0, '')")
+                .bind::<BigInt, _>(id)
+                .bind::<BigInt, _>(id)
+                .bind::<BigInt, _>(did)
+                .bind::<BigInt, _>(mod_secs)
+                .bind::<Integer, _>(scheduling.0)
+                .bind::<Integer, _>(scheduling.1)
+                .bind::<BigInt, _>(scheduling.2)
+                .bind::<Integer, _>(scheduling.3)
+                .bind::<Integer, _>(scheduling.4)
+                .bind::<Integer, _>(scheduling.5)
+                .bind::<Integer, _>(lapses.unwrap_or(0))
+                .execute(&mut anki)?;
+
+            num_cards += 1;
+        }
+    }
+
+    let first_did = if decks.is_empty() { 1 } else { base_id + 1 };
+    let models = json!({ model_id.to_string(): basic_model(model_id, first_did, mod_secs) });
+
+    diesel::sql_query("INSERT INTO col VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}')")
+        .bind::<BigInt, _>(crt)
+        .bind::<BigInt, _>(mod_secs * 1000)
+        .bind::<BigInt, _>(mod_secs * 1000)
+        .bind::<Text, _>(anki_conf(first_did).to_string())
+        .bind::<Text, _>(models.to_string())
+        .bind::<Text, _>(serde_json::Value::Object(anki_decks).to_string())
+        .bind::<Text, _>(anki_dconf().to_string())
+        .execute(&mut anki)?;
+    drop(anki);
+
+    let mut archive = zip::ZipWriter::new(File::create(path)?);
+    let options = zip::write::FileOptions::default();
+    archive.start_file("collection.anki2", options).map_err(zip_error)?;
+    archive.write_all(&std::fs::read(&temp.0)?)?;
+
+    // media are stored as `0`, `1`, ... and named in the `media` map
+    let mut media_map = serde_json::Map::new();
+    let mut missing_media = Vec::new();
+    for file_name in media_files {
+        let data = match read_media(media_dir, &file_name) {
+            Ok((data, _)) => data,
+            Err(Error::NotFound(_)) => {
+                missing_media.push(file_name);
+                continue;
+            },
+            Err(err) => return Err(err)
+        };
+        let idx = media_map.len().to_string();
+        archive.start_file(idx.as_str(), options).map_err(zip_error)?;
+        archive.write_all(&data)?;
+        media_map.insert(idx, serde_json::Value::String(file_name));
+    }
+    archive.start_file("media", options).map_err(zip_error)?;
+    archive.write_all(serde_json::Value::Object(media_map).to_string().as_bytes())?;
+    archive.finish().map_err(zip_error)?;
+
+    Ok(ApkgExport { num_cards, missing_media })
+}
+
+
+// reads decks and notes from the collection inside an `.apkg`
+fn read_collection(path: &Path) -> Result<AnkiCollection> {
+    let file = File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(zip_error)?;
+
+    let names: Vec<String> = archive.file_names().map(String::from).collect();
+    let has = |name: &str| names.iter().any(|n| n == name);
+
+    // packages from Anki 2.1.50+ only hold a placeholder note in `collection.anki2`
+    let collection_name = if has("collection.anki21") {
+        "collection.anki21"
+    } else if has("collection.anki21b") {
+        return Err(Error::InvalidInput(String::from(
+            "package uses the compressed Anki 2.1.50+ format; export it again with \"Support older Anki versions\" checked"
+        )));
+    } else if has("collection.anki2") {
+        "collection.anki2"
+    } else {
+        return Err(Error::InvalidInput(String::from("not an Anki package: no collection inside")));
+    };
+
+    // SQLite can only open the collection from a file
+    let temp = TempFile::new("anki2");
+    let mut entry = archive.by_name(collection_name).map_err(zip_error)?;
+    let mut out = File::create(&temp.0)?;
+    std::io::copy(&mut entry, &mut out)?;
+    drop(entry);
+    drop(out);
+
+    let temp_url = temp.0.to_string_lossy().to_string();
+    let mut anki = SqliteConnection::establish(&temp_url)
+        .map_err(|err| Error::InvalidInput(format!("not an Anki package: {}", err)))?;
+
+    let col = diesel::sql_query("SELECT crt, decks FROM col")
+        .get_result::<AnkiCol>(&mut anki)
+        .map_err(|err| Error::InvalidInput(format!("not an Anki collection: {}", err)))?;
+
+    let cards = diesel::sql_query(
+        "SELECT c.did AS did, n.flds AS flds, n.tags AS tags, c.type AS ctype, c.queue AS queue, c.due AS due, \
+                c.ivl AS ivl, c.factor AS factor, c.reps AS reps, c.lapses AS lapses \
+         FROM cards c JOIN notes n ON n.id = c.nid \
+         WHERE c.ord = (SELECT MIN(ord) FROM cards WHERE nid = c.nid) \
+         ORDER BY n.id")
+        .load::<AnkiCard>(&mut anki)
+        .map_err(|err| Error::InvalidInput(format!("not an Anki collection: {}", err)))?;
+
+    let decks: HashMap<String, serde_json::Value> = serde_json::from_str(&col.decks)
+        .map_err(|err| Error::InvalidInput(format!("cannot read Anki decks: {}", err)))?;
+    let deck_names = decks.iter()
+        .filter_map(|(did, deck)| Some((did.parse::<i64>().ok()?, deck["name"].as_str()?.to_string())))
+        .collect();
+
+    // media are stored as `0`, `1`, ... and named in the `media` map; packages
+    // without media may leave the map out
+    let media_map: HashMap<String, String> = match archive.by_name("media") {
+        Ok(mut entry) => {
+            let mut json = String::new();
+            entry.read_to_string(&mut json)?;
+            serde_json::from_str(&json)
+                .map_err(|err| Error::InvalidInput(format!("cannot read Anki media: {}", err)))?
+        },
+        Err(zip::result::ZipError::FileNotFound) => HashMap::new(),
+        Err(err) => return Err(zip_error(err))
+    };
+    let mut media = Vec::new();
+    for (idx, name) in media_map {
+        let mut entry = match archive.by_name(&idx) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => continue,
+            Err(err) => return Err(zip_error(err))
+        };
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        media.push((name, data));
+    }
+
+    Ok(AnkiCollection { crt: col.crt, deck_names, cards, media })
+}
+
+
+// the first field is the front, the remaining non-empty fields the back
+fn note_to_card(flds: &str, media_files: &HashMap<String, MediaFile>) -> NewCard {
+    let mut fields = flds.split(FIELD_SEPARATOR);
+    let front = fields.next().unwrap_or("").to_string();
+    let back = fields
+        .filter(|field| !field.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("<br>");
+
+    NewCard {
+        front: link_media(&front, media_files),
+        back: link_media(&back, media_files),
+        is_bidirectional: false,
+        format: String::from(HTML_FORMAT)
+    }
+}
+
+// points `src="x.jpg"` and `[sound:x.mp3]` of a note at the stored files;
+// references to files the package did not hold are left as they are
+fn link_media(html: &str, media_files: &HashMap<String, MediaFile>) -> String {
+    let lookup = |name: &str| media_files.get(name).or_else(|| media_files.get(&percent_decode(name)));
+
+    let mut linked = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("[sound:") {
+        let after = &rest[start + "[sound:".len()..];
+        let Some(end) = after.find(']') else { break };
+        linked.push_str(&rest[..start]);
+        match lookup(&after[..end]) {
+            Some(file) => linked.push_str(&media_html(file)),
+            None => linked.push_str(&rest[start..start + "[sound:".len() + end + 1])
+        }
+        rest = &after[end + 1..];
+    }
+    linked.push_str(rest);
+
+    let html = linked;
+    let mut linked = String::new();
+    let mut rest = html.as_str();
+    while let Some(start) = rest.find("src=") {
+        let after = &rest[start + "src=".len()..];
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let value_start = quote.map_or(0, char::len_utf8);
+        let value_len = after[value_start..]
+            .find(|c: char| quote.map_or(c.is_whitespace() || c == '>', |quote| c == quote))
+            .unwrap_or(after.len() - value_start);
+        let value = &after[value_start..value_start + value_len];
+
+        linked.push_str(&rest[..start + "src=".len() + value_start]);
+        match lookup(value) {
+            Some(file) => linked.push_str(&format!("{}{}", MEDIA_SCHEME, file.file_name)),
+            None => linked.push_str(value)
+        }
+        rest = &after[value_start + value_len..];
+    }
+    linked.push_str(rest);
+    linked
+}
+
+// Anki writes file names in `src` URL-encoded, e.g. spaces as `%20`
+fn percent_decode(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+
+// SM-2 state of a card Anki has already studied; None for new cards
+fn anki_card_state(card: &AnkiCard, crt_date: NaiveDate, today: NaiveDate) -> Option<CardState> {
+    if card.ctype == 0 {
+        return None;
+    }
+
+    let next_practice = match (card.queue, card.ctype) {
+        (1, _) => local_date(card.due).unwrap_or(today),
+        (2, _) | (3, _) | (_, 2) => crt_date + Duration::days(card.due),
+        _ => today
+    };
+    let easiness = if card.factor > 0 { card.factor as f32 / 1000.0 } else { 2.5 };
+
+    Some(CardState {
+        box_position: None,
+        repetitions: Some(card.reps.max(1)),
+        easiness: Some(easiness),
+        // learning cards have negative intervals in seconds
+        interval: Some(card.ivl.max(1)),
+        next_practice: Some(next_practice),
+        stability: None,
+        difficulty: None,
+        last_review: None,
+        learning_step: None,
+        due_at: None,
+        lapses: Some(card.lapses)
+    })
+}
+
+
+// Anki sorts and detects duplicates on the first field without markup
+fn strip_html(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+// first 8 hex digits of the SHA-1 of the sort field, as Anki computes `notes.csum`
+fn field_checksum(sort_field: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(sort_field).digest().to_string();
+    i64::from_str_radix(&digest[..8], 16).unwrap_or(0)
+}
+
+fn local_date(epoch_secs: i64) -> Option<NaiveDate> {
+    Local.timestamp_opt(epoch_secs, 0).single().map(|dt| dt.date_naive())
+}
+
+fn zip_error(err: zip::result::ZipError) -> Error {
+    match err {
+        zip::result::ZipError::Io(err) => Error::from(err),
+        err => Error::InvalidInput(format!("not an Anki package: {}", err))
+    }
+}
+
+
+// file in the temp directory, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(extension: &str) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        TempFile(std::env::temp_dir().join(format!("adam-{}-{}.{}", std::process::id(), nanos, extension)))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+
+// tables of an Anki 2.1 (schema 11) collection
+const ANKI_SCHEMA: &str = "
+    CREATE TABLE col (
+        id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+        ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+        conf text not null, models text not null, decks text not null, dconf text not null, tags text not null
+    );
+    CREATE TABLE notes (
+        id integer primary key, guid text not null, mid integer not null, mod integer not null,
+        usn integer not null, tags text not null, flds text not null, sfld integer not null,
+        csum integer not null, flags integer not null, data text not null
+    );
+    CREATE TABLE cards (
+        id integer primary key, nid integer not null, did integer not null, ord integer not null,
+        mod integer not null, usn integer not null, type integer not null, queue integer not null,
+        due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+        lapses integer not null, left integer not null, odue integer not null, odid integer not null,
+        flags integer not null, data text not null
+    );
+    CREATE TABLE revlog (
+        id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+        ivl integer not null, lastIvl integer not null, factor integer not null, time integer not null,
+        type integer not null
+    );
+    CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+    CREATE INDEX ix_notes_usn ON notes (usn);
+    CREATE INDEX ix_cards_usn ON cards (usn);
+    CREATE INDEX ix_revlog_usn ON revlog (usn);
+    CREATE INDEX ix_cards_nid ON cards (nid);
+    CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+    CREATE INDEX ix_revlog_cid ON revlog (cid);
+    CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+fn anki_deck(did: i64, name: &str, mod_secs: i64) -> serde_json::Value {
+    json!({
+        "id": did, "name": name, "mod": mod_secs, "usn": -1, "desc": "", "dyn": 0, "conf": 1,
+        "collapsed": false, "browserCollapsed": false, "extendNew": 0, "extendRev": 0,
+        "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0]
+    })
+}
+
+// "Basic" note type: Front and Back fields, one card showing the front
+fn basic_model(model_id: i64, did: i64, mod_secs: i64) -> serde_json::Value {
+    let field = |name: &str, ord: i32| json!({
+        "name": name, "ord": ord, "sticky": false, "rtl": false, "font": "Arial", "size": 20, "media": []
+    });
+
+    json!({
+        "id": model_id, "name": "Basic (Adam)", "type": 0, "mod": mod_secs, "usn": -1, "sortf": 0,
+        "did": did, "tags": [], "vers": [], "req": [[0, "any", [0]]],
+        "flds": [field("Front", 0), field("Back", 1)],
+        "tmpls": [{
+            "name": "Card 1", "ord": 0, "did": null, "bqfmt": "", "bafmt": "",
+            "qfmt": "{{Front}}", "afmt": "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}"
+        }],
+        "css": ".card {\n font-family: arial;\n font-size: 20px;\n text-align: center;\n color: black;\n background-color: white;\n}\n",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}"
+    })
+}
+
+fn anki_conf(current_deck: i64) -> serde_json::Value {
+    json!({
+        "activeDecks": [current_deck], "curDeck": current_deck, "newSpread": 0, "collapseTime": 1200,
+        "timeLim": 0, "estTimes": true, "dueCounts": true, "curModel": null, "nextPos": 1,
+        "sortType": "noteFld", "sortBackwards": false, "addToCur": true
+    })
+}
+
+// Anki's default deck options
+fn anki_dconf() -> serde_json::Value {
+    json!({
+        "1": {
+            "id": 1, "name": "Default", "mod": 0, "usn": 0, "maxTaken": 60, "autoplay": true,
+            "timer": 0, "replayq": true, "dyn": false,
+            "new": {
+                "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500, "order": 1,
+                "perDay": 20, "bury": true, "separate": true
+            },
+            "lapse": { "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0 },
+            "rev": {
+                "perDay": 200, "ease4": 1.3, "fuzz": 0.05, "minSpace": 1, "ivlFct": 1,
+                "maxIvl": 36500, "bury": true, "hardFactor": 1.2
+            }
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_log::read_card_state;
+    use crate::testing::{create_test_entry, root_id, temp_dir, test_connection};
+
+    const PHOTO: &[u8] = b"not really a png";
+    const SOUND: &[u8] = b"not really an mp3";
+
+    // writes an `.apkg` of one Anki 2.1 collection with a deck "Animals": a
+    // new note with a photo, and a note reviewed 5 times that is due 35 days
+    // after `crt_date`, with a sound
+    fn write_apkg(path: &Path, crt_date: NaiveDate) {
+        let dir = path.with_extension("dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let collection_path = dir.join("collection.anki21");
+        let _ = std::fs::remove_file(&collection_path);
+
+        let mut anki = SqliteConnection::establish(&collection_path.to_string_lossy()).unwrap();
+        anki.batch_execute(ANKI_SCHEMA).unwrap();
+        let crt = Local.from_local_datetime(&crt_date.and_hms_opt(0, 0, 0).unwrap()).earliest().unwrap().timestamp();
+        let decks = json!({ "1": anki_deck(1, "Default", 0), "100": anki_deck(100, "Animals", 0) });
+        diesel::sql_query("INSERT INTO col VALUES (1, ?, 0, 0, 11, 0, 0, 0, '{}', '{}', ?, '{}', '{}')")
+            .bind::<BigInt, _>(crt)
+            .bind::<Text, _>(decks.to_string())
+            .execute(&mut anki)
+            .unwrap();
+
+        let notes = [
+            (1, "Cat<img src=\"cat%20photo.png\">\x1fchat", " animals "),
+            (2, "Dog\x1f[sound:bark.mp3] chien", "")
+        ];
+        for (id, flds, tags) in notes {
+            diesel::sql_query("INSERT INTO notes VALUES (?, ?, 1, 0, -1, ?, ?, '', 0, 0, '')")
+                .bind::<BigInt, _>(id)
+                .bind::<Text, _>(format!("note-{}", id))
+                .bind::<Text, _>(tags)
+                .bind::<Text, _>(flds)
+                .execute(&mut anki)
+                .unwrap();
+        }
+        // (note, type, queue, due, ivl, factor, reps, lapses)
+        let cards = [(1, 0, 0, 1, 0, 0, 0, 0), (2, 2, 2, 35, 10, 2300, 5, 1)];
+        for (nid, ctype, queue, due, ivl, factor, reps, lapses) in cards {
+            diesel::sql_query("INSERT INTO cards VALUES (?, ?, 100, 0, 0, -1, ?, ?, ?, ?, ?, ?, ?, 0, 0, 0, 0, '')")
+                .bind::<BigInt, _>(nid)
+                .bind::<BigInt, _>(nid)
+                .bind::<Integer, _>(ctype)
+                .bind::<Integer, _>(queue)
+                .bind::<BigInt, _>(due)
+                .bind::<Integer, _>(ivl)
+                .bind::<Integer, _>(factor)
+                .bind::<Integer, _>(reps)
+                .bind::<Integer, _>(lapses)
+                .execute(&mut anki)
+                .unwrap();
+        }
+        drop(anki);
+
+        let mut archive = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::FileOptions::default();
+        archive.start_file("collection.anki21", options).unwrap();
+        archive.write_all(&std::fs::read(&collection_path).unwrap()).unwrap();
+        archive.start_file("0", options).unwrap();
+        archive.write_all(PHOTO).unwrap();
+        archive.start_file("1", options).unwrap();
+        archive.write_all(SOUND).unwrap();
+        archive.start_file("media", options).unwrap();
+        archive.write_all(json!({ "0": "cat photo.png", "1": "bark.mp3" }).to_string().as_bytes()).unwrap();
+        archive.finish().unwrap();
+    }
+
+    // (id, front, back) of a deck's cards, oldest first
+    fn deck_cards(conn: &mut SqliteConnection, deck_id: i32) -> Vec<(i32, String, String)> {
+        use crate::schema::cards;
+
+        cards::table
+            .filter(cards::deck_id.eq(deck_id))
+            .order(cards::id.asc())
+            .select((cards::id, cards::front, cards::back))
+            .load(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_notes_with_their_media_and_sm2_state() {
+        use crate::schema::entries;
+
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let ankibox_id = create_test_entry(conn, "anki", root, "ankibox", 0);
+        let dir = temp_dir("apkg-import");
+        let media_dir = dir.join("media");
+        let crt_date = study_today(conn).unwrap() - Duration::days(30);
+        write_apkg(&dir.join("animals.apkg"), crt_date);
+
+        let deck_ids = import_apkg(conn, &dir.join("animals.apkg"), ankibox_id, &media_dir).unwrap();
+        assert_eq!(deck_ids.len(), 1);
+        let deck_name = entries::table.find(deck_ids[0]).select(entries::name).get_result::<String>(conn).unwrap();
+        assert_eq!(deck_name, "Animals");
+
+        // references to the package's files point at the media store
+        let photo = store_media(conn, &media_dir, "cat photo.png", PHOTO).unwrap();
+        let sound = store_media(conn, &media_dir, "bark.mp3", SOUND).unwrap();
+        let cards = deck_cards(conn, deck_ids[0]);
+        assert_eq!(cards[0].1, format!("Cat<img src=\"{}{}\">", MEDIA_SCHEME, photo.file_name));
+        assert_eq!(cards[0].2, "chat");
+        assert_eq!(cards[1].2, format!("{} chien", media_html(&sound)));
+        assert_eq!(std::fs::read_dir(&media_dir).unwrap().count(), 2);
+        assert_eq!(read_card_tags(conn, &[cards[0].0]).unwrap()[&cards[0].0], vec![String::from("animals")]);
+
+        // the reviewed card keeps its SM-2 state, the new one starts new
+        let new_state = get_scheduler(conn, ankibox_id).unwrap().new_card_state(conn, deck_ids[0]).unwrap();
+        assert_eq!(read_card_state(conn, cards[0].0).unwrap(), new_state);
+        let state = read_card_state(conn, cards[1].0).unwrap();
+        assert_eq!(state.repetitions, Some(5));
+        assert_eq!(state.easiness, Some(2.3));
+        assert_eq!(state.interval, Some(10));
+        assert_eq!(state.lapses, Some(1));
+        assert_eq!(state.next_practice, Some(crt_date + Duration::days(35)));
+
+        // AM-1 deadlines start every card new
+        let deadline_id = create_test_entry(conn, "exam", root, "deadline", 10);
+        let deck_ids = import_apkg(conn, &dir.join("animals.apkg"), deadline_id, &media_dir).unwrap();
+        for (card_id, _, _) in deck_cards(conn, deck_ids[0]) {
+            let state = read_card_state(conn, card_id).unwrap();
+            assert_eq!((state.box_position, state.repetitions), (Some(0), None));
+        }
+    }
+
+    #[test]
+    fn compressed_packages_are_rejected() {
+        use crate::schema::decks;
+
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let ankibox_id = create_test_entry(conn, "anki", root, "ankibox", 0);
+        let dir = temp_dir("apkg-anki21b");
+        let path = dir.join("new.apkg");
+
+        let mut archive = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        archive.start_file("collection.anki2", options).unwrap();
+        archive.write_all(b"placeholder").unwrap();
+        archive.start_file("collection.anki21b", options).unwrap();
+        archive.write_all(b"zstd").unwrap();
+        archive.finish().unwrap();
+
+        let result = import_apkg(conn, &path, ankibox_id, &dir.join("media"));
+        assert!(matches!(result, Err(Error::InvalidInput(message)) if message.contains("2.1.50")));
+        assert_eq!(decks::table.count().get_result::<i64>(conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn exported_decks_import_again() {
+        use crate::schema::entries;
+        use crate::tags::add_tag;
+        use crate::testing::create_test_cards;
+
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let ankibox_id = create_test_entry(conn, "anki", root, "ankibox", 0);
+        let deck_id = create_test_entry(conn, "spanish", ankibox_id, "deck", 0);
+        let dir = temp_dir("apkg-export");
+        let media_dir = dir.join("media");
+
+        let photo = store_media(conn, &media_dir, "perro.png", PHOTO).unwrap();
+        let missing = MediaFile { file_name: format!("{}.png", "0".repeat(40)), mime_type: String::from("image/png") };
+        let card_ids = create_test_cards(conn, ankibox_id, deck_id, &[
+            ("perro", &media_html(&photo)),
+            ("gato", &media_html(&missing)),
+            ("pez", "fish")
+        ]);
+        add_tag(conn, card_ids[0], "animals").unwrap();
+        let today = study_today(conn).unwrap();
+        let reviewed = CardState {
+            repetitions: Some(3),
+            easiness: Some(2.2),
+            interval: Some(8),
+            next_practice: Some(today + Duration::days(4)),
+            lapses: Some(1),
+            ..read_card_state(conn, card_ids[2]).unwrap()
+        };
+        write_card_state(conn, card_ids[2], &reviewed).unwrap();
+
+        let path = dir.join("spanish.apkg");
+        let export = export_apkg(conn, ankibox_id, &path, &media_dir).unwrap();
+        assert_eq!(export.num_cards, 3);
+        assert_eq!(export.missing_media, vec![missing.file_name.clone()]);
+
+        // the package holds the file that was there, named in the media map
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut media_map = String::new();
+        archive.by_name("media").unwrap().read_to_string(&mut media_map).unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&media_map).unwrap(), json!({ "0": photo.file_name }));
+        let mut data = Vec::new();
+        archive.by_name("0").unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, PHOTO);
+
+        let other_id = create_test_entry(conn, "imported", root, "ankibox", 0);
+        let deck_ids = import_apkg(conn, &path, other_id, &dir.join("imported-media")).unwrap();
+        assert_eq!(deck_ids.len(), 1);
+        let deck_name = entries::table.find(deck_ids[0]).select(entries::name).get_result::<String>(conn).unwrap();
+        assert_eq!(deck_name, "anki::spanish");
+
+        let cards = deck_cards(conn, deck_ids[0]);
+        assert_eq!(cards.len(), 3);
+        assert_eq!(cards[0].2, media_html(&photo));
+        assert_eq!(cards[1].2, format!("<img src=\"{}\">", missing.file_name));
+        assert_eq!(read_card_tags(conn, &[cards[0].0]).unwrap()[&cards[0].0], vec![String::from("animals")]);
+        let state = read_card_state(conn, cards[2].0).unwrap();
+        assert_eq!((state.repetitions, state.easiness, state.interval, state.lapses), (Some(3), Some(2.2), Some(8), Some(1)));
+        assert_eq!(state.next_practice, Some(today + Duration::days(4)));
+    }
+}
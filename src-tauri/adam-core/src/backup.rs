@@ -0,0 +1,844 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::insert_into;
+use diesel::prelude::*;
+
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::schema::{cards, quotas, ankiquotas};
+use crate::anki::{write_anki_options, AnkiOptions};
+use crate::home::{is_duplicate_name, write_day_hours};
+use crate::media::{media_references, read_media, store_media};
+use crate::render::{check_format, default_format};
+use crate::utils::{local_to_utc, DayHours};
+use crate::error::{Error, Result, Context};
+
+
+// marks a JSON document as an Adam backup
+pub const BACKUP_FORMAT: &str = "adam-backup";
+
+/**
+ * Version of the backup document written by this build. Bump it when a change
+ * to the document cannot be read by older code; fields added with a default
+ * only need `#[serde(default)]`
+ */
+pub const BACKUP_VERSION: u32 = 2;
+
+// backups before this version hold deadlines in local time rather than UTC
+const UTC_DEADLINES_VERSION: u32 = 2;
+
+
+/**
+ * The whole folder system, user config and media, with the ids of the
+ * database it was taken from. Review logs are not part of a backup
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Backup {
+    pub format: String,
+    pub version: u32,
+    pub created_at: NaiveDateTime,
+    pub entries: Vec<BackupEntry>,
+    pub folders: Vec<i32>,
+    pub deadlines: Vec<BackupDeadline>,
+    pub decks: Vec<BackupDeck>,
+    pub parents: Vec<BackupParent>,
+    pub cards: Vec<BackupCard>,
+    #[serde(default)]
+    pub clozes: Vec<BackupCloze>,
+    pub quotas: Vec<BackupQuota>,
+    #[serde(default)]
+    pub ankiquotas: Vec<BackupAnkiQuota>,
+    #[serde(default)]
+    pub ankioptions: Vec<BackupAnkiOptions>,
+    #[serde(default)]
+    pub tags: Vec<BackupTag>,
+    #[serde(default)]
+    pub card_tags: Vec<BackupCardTag>,
+    pub userconfig: Option<BackupUserConfig>,
+    // files cards refer to, base64-encoded
+    #[serde(default)]
+    pub media: Vec<BackupMedia>,
+    // files cards refer to that were missing from the media store when the
+    // backup was read; not written to the backup
+    #[serde(skip)]
+    pub missing_media: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupEntry {
+    pub id: i32,
+    pub name: String,
+    pub is_expanded: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupDeadline {
+    pub id: i32,
+    pub deadline_date: Option<NaiveDateTime>,
+    pub study_intensity: Option<i32>,
+    pub num_reset: Option<i32>,
+    pub is_anki: bool,
+    #[serde(default)]
+    pub is_fsrs: bool,
+    pub target_retention: Option<f32>,
+    #[serde(default)]
+    pub deadline_tz: Option<String>,
+    #[serde(default)]
+    pub new_per_day: Option<i32>,
+    #[serde(default)]
+    pub reviews_per_day: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupDeck {
+    pub id: i32,
+    pub num_boxes: Option<i32>,
+    pub new_per_day: Option<i32>,
+    #[serde(default)]
+    pub reviews_per_day: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupParent {
+    pub parent_id: i32,
+    pub child_id: i32
+}
+
+// restored cards get new ids; `id` only links reverse cards to their card
+#[derive(Serialize, Deserialize, Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = cards)]
+pub struct BackupCard {
+    #[serde(default)]
+    pub id: Option<i32>,
+    pub deck_id: i32,
+    pub front: String,
+    pub back: String,
+    pub queue_score: Option<i32>,
+    pub box_position: Option<i32>,
+    pub repetitions: Option<i32>,
+    pub easiness: Option<f32>,
+    pub interval: Option<i32>,
+    pub next_practice: Option<NaiveDate>,
+    pub rephrasing1: Option<String>,
+    pub rephrasing2: Option<String>,
+    pub rephrasing3: Option<String>,
+    pub rephrasing4: Option<String>,
+    pub rephrasing5: Option<String>,
+    pub explanation: Option<String>,
+    pub stability: Option<f32>,
+    pub difficulty: Option<f32>,
+    pub last_review: Option<NaiveDate>,
+    pub cloze_id: Option<i32>,
+    pub cloze_index: Option<i32>,
+    pub reverse_of: Option<i32>,
+    #[serde(default = "default_format")]
+    pub format: String,
+    #[serde(default)]
+    pub learning_step: Option<i32>,
+    #[serde(default)]
+    pub due_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub lapses: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupCloze {
+    pub id: i32,
+    pub deck_id: i32,
+    pub source: String
+}
+
+#[derive(Serialize, Deserialize, Queryable, Insertable, Debug, Clone, Copy)]
+#[diesel(table_name = quotas)]
+pub struct BackupQuota {
+    #[diesel(column_name = id)]
+    pub deck_id: i32,
+    pub days_to_go: i32,
+    pub new_assigned: i32,
+    pub review_assigned: i32,
+    pub new_quota_initial: i32,
+    pub review_quota_initial: i32,
+    pub new_practiced: i32,
+    pub review_practiced: i32
+}
+
+#[derive(Serialize, Deserialize, Queryable, Insertable, Debug, Clone, Copy)]
+#[diesel(table_name = ankiquotas)]
+pub struct BackupAnkiQuota {
+    pub deck_id: i32,
+    pub date_practiced: NaiveDate,
+    pub new_practiced: i32,
+    pub review_practiced: i32
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupAnkiOptions {
+    pub entry_id: i32,
+    #[serde(flatten)]
+    pub options: AnkiOptions
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupTag {
+    pub id: i32,
+    pub name: String
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupCardTag {
+    pub card_id: i32,
+    pub tag_id: i32
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct BackupUserConfig {
+    pub is_dark_mode: bool,
+    pub is_text_field: bool,
+    #[serde(default = "default_new_day_hour")]
+    pub new_day_hour: i32,
+    #[serde(default = "default_deadline_cutoff_hour")]
+    pub deadline_cutoff_hour: i32
+}
+
+// backups from before the hours were configurable used the defaults
+fn default_new_day_hour() -> i32 {
+    DayHours::default().new_day_hour
+}
+
+fn default_deadline_cutoff_hour() -> i32 {
+    DayHours::default().deadline_cutoff_hour
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupMedia {
+    pub file_name: String,
+    pub data: String
+}
+
+
+/**
+ * Reads the folder system, user config and the media in `media_dir` that
+ * cards refer to into a backup. Media missing from `media_dir` are left out
+ * and listed in `missing_media`
+ */
+pub fn read_backup(conn: &mut SqliteConnection, media_dir: &Path) -> Result<Backup> {
+    use crate::schema::{entries, folders, deadlines, decks, parents, clozes, tags, card_tags, userconfig, ankioptions};
+
+    let entries = entries::table
+        .select((entries::id, entries::name, entries::is_expanded))
+        .order(entries::id)
+        .load::<BackupEntry>(conn)?;
+    let folders = folders::table
+        .select(folders::id)
+        .order(folders::id)
+        .load::<i32>(conn)?;
+    let deadlines = deadlines::table
+        .select((deadlines::id, deadlines::deadline_date, deadlines::study_intensity, deadlines::num_reset,
+            deadlines::is_anki, deadlines::is_fsrs, deadlines::target_retention, deadlines::deadline_tz,
+            deadlines::new_per_day, deadlines::reviews_per_day))
+        .order(deadlines::id)
+        .load::<BackupDeadline>(conn)?;
+    let decks = decks::table
+        .select((decks::id, decks::num_boxes, decks::new_per_day, decks::reviews_per_day))
+        .order(decks::id)
+        .load::<BackupDeck>(conn)?;
+    let parents = parents::table
+        .select((parents::parent_id, parents::child_id))
+        .load::<BackupParent>(conn)?;
+    let cards = cards::table
+        .select((cards::id.nullable(), cards::deck_id, cards::front, cards::back, cards::queue_score, cards::box_position,
+            cards::repetitions, cards::easiness, cards::interval, cards::next_practice,
+            cards::rephrasing1, cards::rephrasing2, cards::rephrasing3, cards::rephrasing4, cards::rephrasing5,
+            cards::explanation, cards::stability, cards::difficulty, cards::last_review,
+            cards::cloze_id, cards::cloze_index, cards::reverse_of, cards::format,
+            cards::learning_step, cards::due_at, cards::lapses))
+        .order(cards::id)
+        .load::<BackupCard>(conn)?;
+    let clozes = clozes::table
+        .select((clozes::id, clozes::deck_id, clozes::source))
+        .order(clozes::id)
+        .load::<BackupCloze>(conn)?;
+    let quotas = quotas::table
+        .select((quotas::id, quotas::days_to_go, quotas::new_assigned, quotas::review_assigned,
+            quotas::new_quota_initial, quotas::review_quota_initial, quotas::new_practiced, quotas::review_practiced))
+        .load::<BackupQuota>(conn)?;
+    let ankiquotas = ankiquotas::table
+        .select((ankiquotas::deck_id, ankiquotas::date_practiced, ankiquotas::new_practiced, ankiquotas::review_practiced))
+        .load::<BackupAnkiQuota>(conn)?;
+    let ankioptions = ankioptions::table
+        .select((ankioptions::entry_id, (ankioptions::starting_ease, ankioptions::minimum_ease, ankioptions::first_interval,
+            ankioptions::second_interval, ankioptions::easy_bonus, ankioptions::hard_multiplier,
+            ankioptions::interval_modifier, ankioptions::maximum_interval, ankioptions::learning_steps,
+            ankioptions::relearning_steps)))
+        .order(ankioptions::entry_id)
+        .load::<(i32, AnkiOptions)>(conn)?
+        .into_iter()
+        .map(|(entry_id, options)| BackupAnkiOptions { entry_id, options })
+        .collect();
+    let tags = tags::table
+        .select((tags::id, tags::name))
+        .order(tags::id)
+        .load::<BackupTag>(conn)?;
+    let card_tags = card_tags::table
+        .select((card_tags::card_id, card_tags::tag_id))
+        .load::<BackupCardTag>(conn)?;
+    let userconfig = userconfig::table
+        .select((userconfig::is_dark_mode, userconfig::is_text_field, userconfig::new_day_hour, userconfig::deadline_cutoff_hour))
+        .first::<BackupUserConfig>(conn)
+        .optional()?;
+
+    let mut file_names: Vec<String> = cards.iter()
+        .flat_map(|card| media_references(&card.front).into_iter().chain(media_references(&card.back)))
+        .collect();
+    file_names.sort();
+    file_names.dedup();
+    let mut media = Vec::new();
+    let mut missing_media = Vec::new();
+    for file_name in file_names {
+        match read_media(media_dir, &file_name) {
+            Ok((data, _)) => media.push(BackupMedia { file_name, data: BASE64.encode(data) }),
+            Err(Error::NotFound(_)) => missing_media.push(file_name),
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(Backup {
+        format: String::from(BACKUP_FORMAT),
+        version: BACKUP_VERSION,
+        created_at: chrono::Utc::now().naive_utc(),
+        entries,
+        folders,
+        deadlines,
+        decks,
+        parents,
+        cards,
+        clozes,
+        quotas,
+        ankiquotas,
+        ankioptions,
+        tags,
+        card_tags,
+        userconfig,
+        media,
+        missing_media
+    })
+}
+
+
+/**
+ * Restores a backup, giving every entry a new id, and returns the id of the
+ * root folder. Into an empty database the backup's trunk becomes the trunk and
+ * its user config is kept. Otherwise the contents of the backup's trunk are
+ * added to the existing trunk, renaming top-level entries whose name is taken,
+ * and the existing user config stays. Media are stored in `media_dir`
+ */
+pub fn restore_backup(conn: &mut SqliteConnection, backup: &Backup, media_dir: &Path) -> Result<i32> {
+    use crate::schema::{entries, folders, deadlines, decks, parents, clozes, tags, card_tags, userconfig};
+
+    let root_ids = check_backup(backup)?;
+
+    // a failed restore leaves the database as it was
+    conn.transaction(|conn| {
+        let existing_root = find_root_folder(conn)?;
+
+        // old entry id -> new entry id
+        let mut new_ids: HashMap<i32, i32> = HashMap::new();
+        if let Some(existing_root) = existing_root {
+            for &root_id in &root_ids {
+                new_ids.insert(root_id, existing_root);
+            }
+        }
+
+        let top_level: HashSet<i32> = backup.parents.iter()
+            .filter(|pair| root_ids.contains(&pair.parent_id))
+            .map(|pair| pair.child_id)
+            .collect();
+
+        for entry in &backup.entries {
+            if new_ids.contains_key(&entry.id) {
+                continue;
+            }
+            let name = match existing_root {
+                Some(existing_root) if top_level.contains(&entry.id) => free_name(conn, existing_root, &entry.name)?,
+                _ => entry.name.clone()
+            };
+
+            insert_into(entries::table)
+                .values((entries::name.eq(&name), entries::is_expanded.eq(entry.is_expanded)))
+                .execute(conn)?;
+            let new_id = entries::table
+                .select(diesel::dsl::max(entries::id))
+                .get_result::<Option<i32>>(conn)?
+                .ok_or_else(|| Error::IntegrityViolation(format!("entry {} was not inserted", name)))?;
+            new_ids.insert(entry.id, new_id);
+        }
+        let new_id = |old_id: i32| new_ids[&old_id];
+
+        for &folder_id in &backup.folders {
+            if existing_root.is_some() && root_ids.contains(&folder_id) {
+                continue;
+            }
+            insert_into(folders::table)
+                .values(folders::id.eq(new_id(folder_id)))
+                .execute(conn)?;
+        }
+
+        for deadline in &backup.deadlines {
+            let deadline_date = if backup.version < UTC_DEADLINES_VERSION {
+                deadline.deadline_date.map(local_to_utc)
+            } else {
+                deadline.deadline_date
+            };
+            insert_into(deadlines::table)
+                .values((
+                    deadlines::id.eq(new_id(deadline.id)),
+                    deadlines::deadline_date.eq(deadline_date),
+                    deadlines::deadline_tz.eq(&deadline.deadline_tz),
+                    deadlines::study_intensity.eq(deadline.study_intensity),
+                    deadlines::num_reset.eq(deadline.num_reset),
+                    deadlines::is_anki.eq(deadline.is_anki),
+                    deadlines::is_fsrs.eq(deadline.is_fsrs),
+                    deadlines::target_retention.eq(deadline.target_retention),
+                    deadlines::new_per_day.eq(deadline.new_per_day),
+                    deadlines::reviews_per_day.eq(deadline.reviews_per_day)
+                ))
+                .execute(conn)?;
+        }
+
+        for deck in &backup.decks {
+            insert_into(decks::table)
+                .values((
+                    decks::id.eq(new_id(deck.id)),
+                    decks::num_boxes.eq(deck.num_boxes),
+                    decks::new_per_day.eq(deck.new_per_day),
+                    decks::reviews_per_day.eq(deck.reviews_per_day)
+                ))
+                .execute(conn)?;
+        }
+
+        for pair in &backup.parents {
+            insert_into(parents::table)
+                .values((parents::parent_id.eq(new_id(pair.parent_id)), parents::child_id.eq(new_id(pair.child_id))))
+                .execute(conn)?;
+        }
+
+        // old cloze id -> new cloze id
+        let mut new_cloze_ids: HashMap<i32, i32> = HashMap::new();
+        for cloze in &backup.clozes {
+            insert_into(clozes::table)
+                .values((clozes::deck_id.eq(new_id(cloze.deck_id)), clozes::source.eq(&cloze.source)))
+                .execute(conn)?;
+            let new_cloze_id = clozes::table
+                .select(diesel::dsl::max(clozes::id))
+                .get_result::<Option<i32>>(conn)?
+                .ok_or_else(|| Error::IntegrityViolation(format!("cloze {} was not inserted", cloze.id)))?;
+            new_cloze_ids.insert(cloze.id, new_cloze_id);
+        }
+
+        // old card id -> new card id; reverse cards are linked once every card has its id
+        let mut new_card_ids: HashMap<i32, i32> = HashMap::new();
+        for card in &backup.cards {
+            insert_into(cards::table)
+                .values(BackupCard {
+                    id: None,
+                    deck_id: new_id(card.deck_id),
+                    cloze_id: card.cloze_id.map(|cloze_id| new_cloze_ids[&cloze_id]),
+                    reverse_of: None,
+                    ..card.clone()
+                })
+                .execute(conn)?;
+            let new_card_id = cards::table
+                .select(diesel::dsl::max(cards::id))
+                .get_result::<Option<i32>>(conn)?
+                .ok_or_else(|| Error::IntegrityViolation(String::from("card was not inserted")))?;
+            if let Some(card_id) = card.id {
+                new_card_ids.insert(card_id, new_card_id);
+            }
+        }
+        for card in &backup.cards {
+            if let (Some(card_id), Some(reverse_of)) = (card.id, card.reverse_of) {
+                diesel::update(cards::table.filter(cards::id.eq(new_card_ids[&card_id])))
+                    .set(cards::reverse_of.eq(new_card_ids[&reverse_of]))
+                    .execute(conn)?;
+            }
+        }
+
+        for options in &backup.ankioptions {
+            write_anki_options(conn, new_id(options.entry_id), &options.options)?;
+        }
+
+        // old tag id -> new tag id; a tag whose name is taken joins the existing one
+        let mut new_tag_ids: HashMap<i32, i32> = HashMap::new();
+        for tag in &backup.tags {
+            insert_into(tags::table)
+                .values(tags::name.eq(&tag.name))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            let new_tag_id = tags::table
+                .filter(tags::name.eq(&tag.name))
+                .select(tags::id)
+                .get_result::<i32>(conn)
+                .context(&format!("tag {}", tag.name))?;
+            new_tag_ids.insert(tag.id, new_tag_id);
+        }
+        for pair in &backup.card_tags {
+            insert_into(card_tags::table)
+                .values((card_tags::card_id.eq(new_card_ids[&pair.card_id]), card_tags::tag_id.eq(new_tag_ids[&pair.tag_id])))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+
+        for quota in &backup.quotas {
+            insert_into(quotas::table)
+                .values(BackupQuota { deck_id: new_id(quota.deck_id), ..*quota })
+                .execute(conn)?;
+        }
+
+        for quota in &backup.ankiquotas {
+            insert_into(ankiquotas::table)
+                .values(BackupAnkiQuota { deck_id: new_id(quota.deck_id), ..*quota })
+                .execute(conn)?;
+        }
+
+        // media are stored under the hash of their contents, so cards keep referring to them
+        for file in &backup.media {
+            let data = BASE64.decode(&file.data)
+                .map_err(|err| Error::InvalidInput(format!("backup media {} is not base64: {}", file.file_name, err)))?;
+            let stored = store_media(conn, media_dir, &file.file_name, &data)?;
+            if stored.file_name != file.file_name {
+                return Err(Error::InvalidInput(format!("backup media {} does not match its contents", file.file_name)));
+            }
+        }
+
+        if let (None, Some(config)) = (existing_root, &backup.userconfig) {
+            diesel::delete(userconfig::table).execute(conn)?;
+            insert_into(userconfig::table)
+                .values((userconfig::is_dark_mode.eq(config.is_dark_mode), userconfig::is_text_field.eq(config.is_text_field)))
+                .execute(conn)?;
+            write_day_hours(conn, config.new_day_hour, config.deadline_cutoff_hour)?;
+        }
+
+        Ok(new_id(root_ids[0]))
+    })
+}
+
+
+/**
+ * Writes a backup of the whole database and its media to `path` as JSON,
+ * returning the media that were missing and left out
+ */
+pub fn export_backup(conn: &mut SqliteConnection, path: &Path, media_dir: &Path) -> Result<Vec<String>> {
+    let backup = read_backup(conn, media_dir)?;
+    let json = serde_json::to_string_pretty(&backup)
+        .map_err(|err| Error::InvalidInput(format!("cannot serialize backup: {}", err)))?;
+    std::fs::write(path, json)?;
+    Ok(backup.missing_media)
+}
+
+
+/**
+ * Restores the JSON backup at `path`, returning the id of the root folder
+ */
+pub fn import_backup(conn: &mut SqliteConnection, path: &Path, media_dir: &Path) -> Result<i32> {
+    let json = std::fs::read_to_string(path)?;
+    let backup: Backup = serde_json::from_str(&json)
+        .map_err(|err| Error::InvalidInput(format!("{} is not an Adam backup: {}", path.display(), err)))?;
+    restore_backup(conn, &backup, media_dir)
+}
+
+
+// checks that every id in the backup refers to an entry of the right type,
+// returning the ids of the root folders
+fn check_backup(backup: &Backup) -> Result<Vec<i32>> {
+    if backup.format != BACKUP_FORMAT {
+        return Err(Error::InvalidInput(format!("unknown backup format {:?}", backup.format)));
+    }
+    if backup.version > BACKUP_VERSION {
+        return Err(Error::InvalidInput(format!("backup version {} is newer than this version of Adam reads ({})",
+            backup.version, BACKUP_VERSION)));
+    }
+
+    let entry_ids: HashSet<i32> = backup.entries.iter().map(|entry| entry.id).collect();
+    let deck_ids: HashSet<i32> = backup.decks.iter().map(|deck| deck.id).collect();
+    let mut typed_ids = HashSet::new();
+    for id in backup.folders.iter()
+        .chain(backup.deadlines.iter().map(|deadline| &deadline.id))
+        .chain(deck_ids.iter()) {
+        if !entry_ids.contains(id) || !typed_ids.insert(*id) {
+            return Err(Error::InvalidInput(format!("backup entry {} is missing or has more than one type", id)));
+        }
+    }
+    if let Some(id) = entry_ids.iter().find(|id| !typed_ids.contains(id)) {
+        return Err(Error::InvalidInput(format!("backup entry {} is not a folder, deadline or deck", id)));
+    }
+
+    for pair in &backup.parents {
+        if !entry_ids.contains(&pair.parent_id) || !entry_ids.contains(&pair.child_id) {
+            return Err(Error::InvalidInput(format!("backup links unknown entries {} and {}", pair.parent_id, pair.child_id)));
+        }
+    }
+    if let Some(options) = backup.ankioptions.iter().find(|options| !entry_ids.contains(&options.entry_id)) {
+        return Err(Error::InvalidInput(format!("backup has SM-2 options of unknown entry {}", options.entry_id)));
+    }
+
+    let deck_refs = backup.cards.iter().map(|card| card.deck_id)
+        .chain(backup.clozes.iter().map(|cloze| cloze.deck_id))
+        .chain(backup.quotas.iter().map(|quota| quota.deck_id))
+        .chain(backup.ankiquotas.iter().map(|quota| quota.deck_id));
+    for deck_id in deck_refs {
+        if !deck_ids.contains(&deck_id) {
+            return Err(Error::InvalidInput(format!("backup refers to unknown deck {}", deck_id)));
+        }
+    }
+
+    let cloze_ids: HashSet<i32> = backup.clozes.iter().map(|cloze| cloze.id).collect();
+    if let Some(cloze_id) = backup.cards.iter().filter_map(|card| card.cloze_id).find(|id| !cloze_ids.contains(id)) {
+        return Err(Error::InvalidInput(format!("backup refers to unknown cloze {}", cloze_id)));
+    }
+
+    let card_ids: HashSet<i32> = backup.cards.iter().filter_map(|card| card.id).collect();
+    for card in &backup.cards {
+        check_format(&card.format)?;
+        if let Some(reverse_of) = card.reverse_of {
+            if card.id.is_none() || !card_ids.contains(&reverse_of) {
+                return Err(Error::InvalidInput(format!("backup refers to unknown card {}", reverse_of)));
+            }
+        }
+    }
+
+    let tag_ids: HashSet<i32> = backup.tags.iter().map(|tag| tag.id).collect();
+    for pair in &backup.card_tags {
+        if !card_ids.contains(&pair.card_id) || !tag_ids.contains(&pair.tag_id) {
+            return Err(Error::InvalidInput(format!("backup tags unknown card {} with unknown tag {}", pair.card_id, pair.tag_id)));
+        }
+    }
+
+    let children: HashSet<i32> = backup.parents.iter().map(|pair| pair.child_id).collect();
+    let root_ids: Vec<i32> = backup.folders.iter()
+        .filter(|id| !children.contains(id))
+        .copied()
+        .collect();
+    if root_ids.is_empty() {
+        return Err(Error::InvalidInput(String::from("backup has no root folder")));
+    }
+    Ok(root_ids)
+}
+
+// the folder without a parent
+fn find_root_folder(conn: &mut SqliteConnection) -> Result<Option<i32>> {
+    use crate::schema::{folders, parents};
+
+    let children = parents::table.select(parents::child_id);
+    Ok(folders::table
+        .filter(folders::id.ne_all(children))
+        .select(folders::id)
+        .order(folders::id)
+        .first::<i32>(conn)
+        .optional()?)
+}
+
+// `name`, or `name (2)`, `name (3)`, ... if a child of `parent_id` already has it
+fn free_name(conn: &mut SqliteConnection, parent_id: i32, name: &str) -> Result<String> {
+    let mut candidate = String::from(name);
+    let mut n = 2;
+    while is_duplicate_name(conn, Some(parent_id), candidate.clone())? {
+        candidate = format!("{} ({})", name, n);
+        n += 1;
+    }
+    Ok(candidate)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anki::read_anki_options;
+    use crate::cloze::create_cloze_cards;
+    use crate::edit::{create_cards, write_text_field, DeckNewContents};
+    use crate::home::write_dark_mode;
+    use crate::media::media_html;
+    use crate::tags::{add_tag, read_card_tags};
+    use crate::testing::{create_test_cards, create_test_entry, empty_connection, new_card, root_id, temp_dir, test_connection};
+    use crate::utils::read_day_hours;
+
+    const IMAGE: &[u8] = b"not really a png";
+
+    // a trunk with an exam deadline of German words and an ankibox with its
+    // own SM-2 options, returning its backup
+    fn source_backup(media_dir: &Path) -> Backup {
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        write_dark_mode(conn, false).unwrap();
+        write_text_field(conn, true).unwrap();
+        write_day_hours(conn, 4, 16).unwrap();
+
+        let deadline_id = create_test_entry(conn, "exam", root, "deadline", 10);
+        let deck_id = create_test_entry(conn, "german", deadline_id, "deck", 0);
+        let mut bidirectional = new_card("Hund", "dog");
+        bidirectional.is_bidirectional = true;
+        let image = media_html(&store_media(conn, media_dir, "cat.png", IMAGE).unwrap());
+        let cards = vec![bidirectional, new_card("Katze", &image)];
+        let card_ids = create_cards(conn, deadline_id, DeckNewContents { deck_id, deck_name: String::new(), cards }).unwrap();
+        add_tag(conn, card_ids[0], "animals").unwrap();
+        create_cloze_cards(conn, deadline_id, deck_id, "{{c1::Berlin}} is the capital of {{c2::Germany}}").unwrap();
+
+        let ankibox_id = create_test_entry(conn, "anki", root, "ankibox", 0);
+        let anki_deck_id = create_test_entry(conn, "spanish", ankibox_id, "deck", 0);
+        create_test_cards(conn, ankibox_id, anki_deck_id, &[("perro", "dog")]);
+        let options = AnkiOptions { starting_ease: 2.7, learning_steps: String::from("5m 1h"), ..AnkiOptions::default() };
+        write_anki_options(conn, ankibox_id, &options).unwrap();
+
+        read_backup(conn, media_dir).unwrap()
+    }
+
+    // a backup as `import_backup` reads it back from its file
+    fn reread(backup: &Backup) -> Backup {
+        serde_json::from_str(&serde_json::to_string(backup).unwrap()).unwrap()
+    }
+
+    fn entry_id(conn: &mut SqliteConnection, name: &str) -> i32 {
+        use crate::schema::entries;
+
+        entries::table.filter(entries::name.eq(name)).select(entries::id).get_result(conn).unwrap()
+    }
+
+    fn parent_of(conn: &mut SqliteConnection, entry_id: i32) -> i32 {
+        use crate::schema::parents;
+
+        parents::table.filter(parents::child_id.eq(entry_id)).select(parents::parent_id).get_result(conn).unwrap()
+    }
+
+    fn card_id(conn: &mut SqliteConnection, front: &str) -> i32 {
+        cards::table.filter(cards::front.eq(front)).select(cards::id).get_result(conn).unwrap()
+    }
+
+    #[test]
+    fn backup_round_trips_into_an_empty_database() {
+        use crate::schema::{clozes, decks};
+
+        let source_media = temp_dir("backup-source");
+        let backup = reread(&source_backup(&source_media));
+        assert!(backup.missing_media.is_empty());
+        assert_eq!(backup.media.len(), 1);
+
+        let conn = &mut empty_connection();
+        let media_dir = temp_dir("backup-restored");
+        let root = restore_backup(conn, &backup, &media_dir).unwrap();
+
+        // the backup's trunk is the trunk, with every entry under its parent again
+        assert_eq!(find_root_folder(conn).unwrap(), Some(root));
+        let exam_id = entry_id(conn, "exam");
+        let german_id = entry_id(conn, "german");
+        let anki_id = entry_id(conn, "anki");
+        let spanish_id = entry_id(conn, "spanish");
+        assert_eq!(parent_of(conn, exam_id), root);
+        assert_eq!(parent_of(conn, german_id), exam_id);
+        assert_eq!(parent_of(conn, anki_id), root);
+        assert_eq!(parent_of(conn, spanish_id), anki_id);
+        assert_eq!(decks::table.count().get_result::<i64>(conn).unwrap(), 2);
+
+        // the reverse card refers to its card's new id and shares its tags
+        let hund_id = card_id(conn, "Hund");
+        let reverse = cards::table
+            .filter(cards::reverse_of.eq(hund_id))
+            .select((cards::id, cards::front, cards::deck_id))
+            .get_result::<(i32, String, i32)>(conn)
+            .unwrap();
+        assert_eq!((reverse.1.as_str(), reverse.2), ("dog", german_id));
+        let tags = read_card_tags(conn, &[hund_id, reverse.0]).unwrap();
+        assert_eq!(tags[&hund_id], vec![String::from("animals")]);
+        assert_eq!(tags[&reverse.0], vec![String::from("animals")]);
+
+        // both cloze cards belong to the restored cloze
+        let (cloze_id, source) = clozes::table
+            .filter(clozes::deck_id.eq(german_id))
+            .select((clozes::id, clozes::source))
+            .get_result::<(i32, String)>(conn)
+            .unwrap();
+        assert_eq!(source, "{{c1::Berlin}} is the capital of {{c2::Germany}}");
+        let cloze_indices = cards::table
+            .filter(cards::cloze_id.eq(cloze_id))
+            .select(cards::cloze_index)
+            .order(cards::cloze_index)
+            .load::<Option<i32>>(conn)
+            .unwrap();
+        assert_eq!(cloze_indices, vec![Some(1), Some(2)]);
+
+        // SM-2 options, user config and media come along
+        let options = read_anki_options(conn, anki_id).unwrap();
+        assert_eq!((options.starting_ease, options.learning_steps.as_str()), (2.7, "5m 1h"));
+        assert_eq!(read_day_hours(conn).unwrap(), DayHours { new_day_hour: 4, deadline_cutoff_hour: 16 });
+        let config = read_backup(conn, &media_dir).unwrap().userconfig.unwrap();
+        assert_eq!((config.is_dark_mode, config.is_text_field), (false, true));
+        let file_name = &backup.media[0].file_name;
+        let katze_back = cards::table.filter(cards::front.eq("Katze")).select(cards::back).get_result::<String>(conn).unwrap();
+        assert!(katze_back.contains(file_name.as_str()));
+        assert_eq!(read_media(&media_dir, file_name).unwrap().0, IMAGE);
+    }
+
+    #[test]
+    fn backup_is_added_to_an_existing_trunk() {
+        let backup = reread(&source_backup(&temp_dir("backup-merge-source")));
+
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let exam_id = create_test_entry(conn, "exam", root, "deadline", 3);
+
+        assert_eq!(restore_backup(conn, &backup, &temp_dir("backup-merge")).unwrap(), root);
+        assert_eq!(find_root_folder(conn).unwrap(), Some(root));
+
+        // the taken top-level name gets a number, its children keep theirs
+        assert_eq!(entry_id(conn, "exam"), exam_id);
+        let restored_exam_id = entry_id(conn, "exam (2)");
+        assert_eq!(parent_of(conn, restored_exam_id), root);
+        let german_id = entry_id(conn, "german");
+        let anki_id = entry_id(conn, "anki");
+        assert_eq!(parent_of(conn, german_id), restored_exam_id);
+        assert_eq!(parent_of(conn, anki_id), root);
+
+        // the existing user config stays
+        assert_eq!(read_day_hours(conn).unwrap(), DayHours::default());
+        let config = read_backup(conn, &temp_dir("backup-merge")).unwrap().userconfig.unwrap();
+        assert_eq!((config.is_dark_mode, config.is_text_field), (true, false));
+    }
+
+    #[test]
+    fn version_1_deadlines_are_read_as_local_time() {
+        use crate::schema::deadlines;
+
+        let mut backup = reread(&source_backup(&temp_dir("backup-v1-source")));
+        backup.version = 1;
+        let local = NaiveDate::from_ymd_opt(2030, 7, 1).unwrap().and_hms_opt(9, 30, 0).unwrap();
+        backup.deadlines.iter_mut()
+            .filter(|deadline| deadline.deadline_date.is_some())
+            .for_each(|deadline| deadline.deadline_date = Some(local));
+
+        let conn = &mut empty_connection();
+        restore_backup(conn, &backup, &temp_dir("backup-v1")).unwrap();
+
+        let exam_id = entry_id(conn, "exam");
+        let deadline_date = deadlines::table
+            .filter(deadlines::id.eq(exam_id))
+            .select(deadlines::deadline_date)
+            .get_result::<Option<NaiveDateTime>>(conn)
+            .unwrap();
+        assert_eq!(deadline_date, Some(local_to_utc(local)));
+    }
+
+    #[test]
+    fn newer_backups_are_rejected() {
+        let mut backup = reread(&source_backup(&temp_dir("backup-newer-source")));
+        backup.version = BACKUP_VERSION + 1;
+
+        let conn = &mut empty_connection();
+        assert!(matches!(restore_backup(conn, &backup, &temp_dir("backup-newer")), Err(Error::InvalidInput(_))));
+        assert_eq!(find_root_folder(conn).unwrap(), None);
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use diesel::{insert_into, delete, update};
+use diesel::prelude::*;
+
+use crate::edit::{insert_deck_contents, DeckNewContents};
+use crate::models::NewCard;
+use crate::render::HTML_FORMAT;
+use crate::review_log::read_card_state;
+use crate::scheduler::{get_scheduler, get_deck_scheduler};
+use crate::tags::delete_card_tags;
+use crate::error::{Error, Result, Context};
+
+
+// piece of a cloze source
+enum Segment<'a> {
+    Text(&'a str),
+    Cloze { index: i32, answer: &'a str, hint: Option<&'a str> }
+}
+
+
+/**
+ * Whether `text` has at least one `{{cN::...}}` marker
+ */
+pub fn is_cloze(text: &str) -> bool {
+    !cloze_indices(text).is_empty()
+}
+
+
+/**
+ * Cloze indices used in `source`, including nested ones, in ascending order
+ */
+pub fn cloze_indices(source: &str) -> Vec<i32> {
+    let mut indices = Vec::new();
+    push_indices(source, &mut indices);
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+fn push_indices(source: &str, indices: &mut Vec<i32>) {
+    for segment in parse(source) {
+        if let Segment::Cloze { index, answer, .. } = segment {
+            indices.push(index);
+            push_indices(answer, indices);
+        }
+    }
+}
+
+
+/**
+ * Front and back of the card for cloze `index` of `source`: the front hides
+ * that cloze as `[...]` (or `[hint]` for `{{c1::answer::hint}}`), the back
+ * shows it highlighted, and every other cloze shows as plain text on both.
+ * A cloze nested in another is hidden on its own card only
+ */
+pub fn render_cloze(source: &str, index: i32) -> NewCard {
+    let mut front = String::new();
+    let mut back = String::new();
+    render_segments(source, Some(index), &mut front, &mut back);
+    NewCard { front, back, is_bidirectional: false, format: String::from(HTML_FORMAT) }
+}
+
+// renders `source` hiding the clozes of `index`; with None every cloze shows as text
+fn render_segments(source: &str, index: Option<i32>, front: &mut String, back: &mut String) {
+    for segment in parse(source) {
+        match segment {
+            Segment::Text(text) => {
+                front.push_str(text);
+                back.push_str(text);
+            },
+            Segment::Cloze { index: i, answer, hint } if Some(i) == index => {
+                front.push_str(&format!("<span class=\"cloze\">[{}]</span>", hint.unwrap_or("...")));
+                let mut hidden = String::new();
+                render_segments(answer, None, &mut String::new(), &mut hidden);
+                back.push_str(&format!("<span class=\"cloze\">{}</span>", hidden));
+            },
+            Segment::Cloze { answer, .. } => render_segments(answer, index, front, back)
+        }
+    }
+}
+
+
+/**
+ * Creates a cloze from `source` in a deck of a deadline or ankibox, with one
+ * card per cloze index, returning the ids of the cards
+ */
+pub fn create_cloze_cards(conn: &mut SqliteConnection, deadline_id: i32, deck_id: i32, source: &str) -> Result<Vec<i32>> {
+    use crate::schema::{cards, clozes};
+
+    let indices = cloze_indices(source);
+    if indices.is_empty() {
+        return Err(Error::InvalidInput(String::from("cloze text has no `{{c1::...}}` markers")));
+    }
+    let scheduler = get_scheduler(conn, deadline_id)?;
+
+    // the cloze and its cards are written together or not at all
+    conn.transaction(|conn| {
+        insert_into(clozes::table)
+            .values((clozes::deck_id.eq(deck_id), clozes::source.eq(source)))
+            .execute(conn)?;
+        let cloze_id = clozes::table
+            .select(diesel::dsl::max(clozes::id))
+            .get_result::<Option<i32>>(conn)?
+            .ok_or_else(|| Error::IntegrityViolation(String::from("cloze was not inserted")))?;
+
+        let cards: Vec<NewCard> = indices.iter().map(|&index| render_cloze(source, index)).collect();
+        let deck_new_contents = DeckNewContents { deck_id, deck_name: String::new(), cards };
+        let state = scheduler.new_card_state(conn, deck_id)?;
+        let card_ids = insert_deck_contents(conn, deck_new_contents, &state)?;
+
+        for (&card_id, &index) in card_ids.iter().zip(&indices) {
+            update(cards::table.filter(cards::id.eq(card_id)))
+                .set((cards::cloze_id.eq(cloze_id), cards::cloze_index.eq(index)))
+                .execute(conn)?;
+        }
+
+        scheduler.cards_created(conn, deck_id, card_ids.len() as i32)?;
+        Ok(card_ids)
+    })
+}
+
+
+/**
+ * Replaces the source of a cloze and re-renders its cards. Cards of indices
+ * still in `source` keep their scheduling state, new indices get new cards
+ * and cards of removed indices are deleted
+ */
+pub fn update_cloze(conn: &mut SqliteConnection, cloze_id: i32, source: &str) -> Result<()> {
+    use crate::schema::{cards, clozes};
+
+    let indices = cloze_indices(source);
+    if indices.is_empty() {
+        return Err(Error::InvalidInput(String::from("cloze text has no `{{c1::...}}` markers")));
+    }
+
+    let deck_id = clozes::table
+        .find(cloze_id)
+        .select(clozes::deck_id)
+        .get_result::<i32>(conn)
+        .context(&format!("cloze {}", cloze_id))?;
+    let scheduler = get_deck_scheduler(conn, deck_id)?;
+
+    let siblings: HashMap<i32, i32> = cards::table
+        .filter(cards::cloze_id.eq(cloze_id))
+        .select((cards::cloze_index, cards::id))
+        .get_results::<(Option<i32>, i32)>(conn)?
+        .into_iter()
+        .filter_map(|(index, card_id)| index.map(|index| (index, card_id)))
+        .collect();
+
+    conn.transaction(|conn| {
+        update(clozes::table.find(cloze_id))
+            .set(clozes::source.eq(source))
+            .execute(conn)?;
+
+        let mut new_indices = Vec::new();
+        for &index in &indices {
+            let card = render_cloze(source, index);
+            match siblings.get(&index) {
+                Some(&card_id) => {
+                    update(cards::table.filter(cards::id.eq(card_id)))
+                        .set((cards::front.eq(card.front), cards::back.eq(card.back)))
+                        .execute(conn)?;
+                },
+                None => new_indices.push(index)
+            }
+        }
+
+        if !new_indices.is_empty() {
+            let cards: Vec<NewCard> = new_indices.iter().map(|&index| render_cloze(source, index)).collect();
+            let deck_new_contents = DeckNewContents { deck_id, deck_name: String::new(), cards };
+            let state = scheduler.new_card_state(conn, deck_id)?;
+            let card_ids = insert_deck_contents(conn, deck_new_contents, &state)?;
+            for (&card_id, &index) in card_ids.iter().zip(&new_indices) {
+                update(cards::table.filter(cards::id.eq(card_id)))
+                    .set((cards::cloze_id.eq(cloze_id), cards::cloze_index.eq(index)))
+                    .execute(conn)?;
+            }
+            scheduler.cards_created(conn, deck_id, card_ids.len() as i32)?;
+        }
+
+        for (index, &card_id) in &siblings {
+            if !indices.contains(index) {
+                let state = read_card_state(conn, card_id)?;
+                delete_card_tags(conn, &[card_id])?;
+                delete(cards::table.filter(cards::id.eq(card_id)))
+                    .execute(conn)?;
+                scheduler.card_deleted(conn, deck_id, &state)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+
+/**
+ * Deletes a cloze and all of its cards
+ */
+pub fn delete_cloze(conn: &mut SqliteConnection, cloze_id: i32) -> Result<()> {
+    use crate::schema::{cards, clozes};
+
+    let deck_id = clozes::table
+        .find(cloze_id)
+        .select(clozes::deck_id)
+        .get_result::<i32>(conn)
+        .context(&format!("cloze {}", cloze_id))?;
+    let scheduler = get_deck_scheduler(conn, deck_id)?;
+
+    let card_ids = cards::table
+        .filter(cards::cloze_id.eq(cloze_id))
+        .select(cards::id)
+        .get_results::<i32>(conn)?;
+
+    conn.transaction(|conn| {
+        for card_id in card_ids {
+            let state = read_card_state(conn, card_id)?;
+            delete_card_tags(conn, &[card_id])?;
+            delete(cards::table.filter(cards::id.eq(card_id)))
+                .execute(conn)?;
+            scheduler.card_deleted(conn, deck_id, &state)?;
+        }
+        delete(clozes::table.find(cloze_id))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+
+/**
+ * Id of the cloze a card was rendered from, if any
+ */
+pub fn get_cloze_id(conn: &mut SqliteConnection, card_id: i32) -> Result<Option<i32>> {
+    use crate::schema::cards;
+
+    cards::table
+        .filter(cards::id.eq(card_id))
+        .select(cards::cloze_id)
+        .get_result::<Option<i32>>(conn)
+        .context(&format!("card {}", card_id))
+}
+
+
+// splits `source` at `{{cN::answer}}` and `{{cN::answer::hint}}` markers;
+// anything that does not parse as a marker stays text
+fn parse(source: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{c") {
+        let Some((index, answer, hint, len)) = parse_marker(&rest[start..]) else {
+            // keep the `{{c` as text and look for the next marker after it
+            segments.push(Segment::Text(&rest[..start + 3]));
+            rest = &rest[start + 3..];
+            continue;
+        };
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+        segments.push(Segment::Cloze { index, answer, hint });
+        rest = &rest[start + len..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    segments
+}
+
+// (index, answer, hint, length of the marker) of a marker at the start of
+// `text`; the answer may hold nested markers, whose `}}` and `::` it skips
+fn parse_marker(text: &str) -> Option<(i32, &str, Option<&str>, usize)> {
+    let body = text.strip_prefix("{{c")?;
+    let digits = body.find(|c: char| !c.is_ascii_digit())?;
+    let index = body[..digits].parse::<i32>().ok().filter(|&index| index > 0)?;
+    let content = body[digits..].strip_prefix("::")?;
+
+    let mut depth = 0;
+    let mut hint_start = None;
+    let mut end = None;
+    let mut idx = 0;
+    while idx < content.len() {
+        let rest = &content[idx..];
+        if rest.starts_with("{{") {
+            depth += 1;
+            idx += 2;
+        } else if rest.starts_with("}}") {
+            if depth == 0 {
+                end = Some(idx);
+                break;
+            }
+            depth -= 1;
+            idx += 2;
+        } else if rest.starts_with("::") && depth == 0 && hint_start.is_none() {
+            hint_start = Some(idx);
+            idx += 2;
+        } else {
+            idx += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    let end = end?;
+
+    let (answer, hint) = match hint_start {
+        Some(start) => (&content[..start], Some(&content[start + 2..end])),
+        None => (&content[..end], None)
+    };
+    let len = 3 + digits + 2 + end + 2;
+    Some((index, answer, hint, len))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sides(source: &str, index: i32) -> (String, String) {
+        let card = render_cloze(source, index);
+        (card.front, card.back)
+    }
+
+    #[test]
+    fn one_card_per_index() {
+        let source = "{{c2::Paris::city}} is the capital of {{c1::France}}, like {{c2::Lyon}} is not";
+        assert_eq!(cloze_indices(source), vec![1, 2]);
+        assert_eq!(sides(source, 1), (
+            String::from("Paris is the capital of <span class=\"cloze\">[...]</span>, like Lyon is not"),
+            String::from("Paris is the capital of <span class=\"cloze\">France</span>, like Lyon is not")
+        ));
+        assert_eq!(sides(source, 2), (
+            String::from("<span class=\"cloze\">[city]</span> is the capital of France, like <span class=\"cloze\">[...]</span> is not"),
+            String::from("<span class=\"cloze\">Paris</span> is the capital of France, like <span class=\"cloze\">Lyon</span> is not")
+        ));
+    }
+
+    #[test]
+    fn nested_markers() {
+        let source = "{{c1::The {{c2::Eiffel}} tower::landmark}} is in Paris";
+        assert_eq!(cloze_indices(source), vec![1, 2]);
+        assert_eq!(sides(source, 1), (
+            String::from("<span class=\"cloze\">[landmark]</span> is in Paris"),
+            String::from("<span class=\"cloze\">The Eiffel tower</span> is in Paris")
+        ));
+        assert_eq!(sides(source, 2), (
+            String::from("The <span class=\"cloze\">[...]</span> tower is in Paris"),
+            String::from("The <span class=\"cloze\">Eiffel</span> tower is in Paris")
+        ));
+    }
+
+    #[test]
+    fn malformed_markers_stay_text() {
+        for source in ["{{c1::unclosed", "{{c0::zero}}", "{{c::no index}}", "{{c1:one colon}}", "{{x1::other}}"] {
+            assert!(!is_cloze(source), "{}", source);
+            assert_eq!(sides(source, 1), (String::from(source), String::from(source)));
+        }
+    }
+
+    #[test]
+    fn malformed_markers_around_valid_ones() {
+        let source = "{{c{{c1::a}} and {{c2::b";
+        assert_eq!(cloze_indices(source), vec![1]);
+        assert_eq!(sides(source, 1), (
+            String::from("{{c<span class=\"cloze\">[...]</span> and {{c2::b"),
+            String::from("{{c<span class=\"cloze\">a</span> and {{c2::b")
+        ));
+
+        // an outer marker that is never closed leaves the inner one
+        assert_eq!(cloze_indices("{{c1::a {{c2::b}} c"), vec![2]);
+    }
+}
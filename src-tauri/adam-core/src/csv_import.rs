@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use diesel::prelude::*;
+
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::edit::{create_cards, DeckNewContents};
+use crate::home::{create_deck, find_deck};
+use crate::models::NewCard;
+use crate::render::HTML_FORMAT;
+use crate::tags::add_imported_tags;
+use crate::textfield::plain_to_html;
+use crate::error::{Error, Result};
+
+
+/**
+ * Which columns of a delimited file hold what. Columns count from 0
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColumnMapping {
+    // guessed from the file extension when None: tab for `.tsv`/`.tab`, comma otherwise
+    pub delimiter: Option<char>,
+    // whether the first row names the columns instead of holding a card
+    pub has_headers: bool,
+    pub front: usize,
+    pub back: usize,
+    // tags separated by spaces or commas
+    pub tags: Option<usize>,
+    pub deck: Option<usize>,
+    // deck for rows without a deck column or with an empty one; defaults to the file name
+    pub default_deck: Option<String>
+}
+
+/**
+ * A row of a delimited file as it will be imported
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub deck_name: String,
+    pub front: String,
+    pub back: String,
+    pub tags: Vec<String>
+}
+
+
+/**
+ * Parses at most `limit` rows of a delimited file with `mapping`, without
+ * writing anything, so the caller can check the mapping before importing
+ */
+pub fn preview_delimited(path: &Path, mapping: &ColumnMapping, limit: usize) -> Result<Vec<ImportRow>> {
+    let mut rows = read_delimited(path, mapping)?;
+    rows.truncate(limit);
+    Ok(rows)
+}
+
+
+/**
+ * Imports a delimited file into a deadline or ankibox, returning the ids of
+ * the created cards. Rows go to the deck named in their deck column, which is
+ * created when the deadline has no deck of that name
+ */
+pub fn import_delimited(conn: &mut SqliteConnection, path: &Path, deadline_id: i32, mapping: &ColumnMapping) -> Result<Vec<i32>> {
+    let rows = read_delimited(path, mapping)?;
+
+    // group rows by deck, keeping their order within each deck along with their
+    // tags; cells are plain text, escaped and with their line breaks kept
+    let mut decks: BTreeMap<String, (Vec<NewCard>, Vec<Vec<String>>)> = BTreeMap::new();
+    for row in rows {
+        let (cards, tags) = decks.entry(row.deck_name).or_default();
+        cards.push(NewCard { front: plain_to_html(&row.front), back: plain_to_html(&row.back), is_bidirectional: false, format: String::from(HTML_FORMAT) });
+        tags.push(row.tags);
+    }
+
+    // a failed import leaves no half-filled decks behind
+    conn.transaction(|conn| {
+        let mut card_ids = Vec::new();
+        for (deck_name, (cards, tags)) in decks {
+            let deck_id = match find_deck(conn, deadline_id, &deck_name)? {
+                Some(deck_id) => deck_id,
+                None => create_deck(conn, deadline_id, &deck_name)?
+            };
+
+            let deck_new_contents = DeckNewContents { deck_id, deck_name, cards };
+            let deck_card_ids = create_cards(conn, deadline_id, deck_new_contents)?;
+            for (card_id, names) in deck_card_ids.iter().zip(&tags) {
+                add_imported_tags(conn, *card_id, names)?;
+            }
+            card_ids.extend(deck_card_ids);
+        }
+        Ok(card_ids)
+    })
+}
+
+
+fn read_delimited(path: &Path, mapping: &ColumnMapping) -> Result<Vec<ImportRow>> {
+    let delimiter = match mapping.delimiter {
+        Some(delimiter) if delimiter.is_ascii() => delimiter as u8,
+        Some(delimiter) => return Err(Error::InvalidInput(format!("delimiter {:?} is not an ASCII character", delimiter))),
+        None => match path.extension().and_then(|ext| ext.to_str()) {
+            Some("tsv") | Some("tab") => b'\t',
+            _ => b','
+        }
+    };
+
+    let default_deck = mapping.default_deck.clone()
+        .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .unwrap_or_else(|| String::from("Imported"));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(mapping.has_headers)
+        // rows may leave out trailing columns, e.g. an empty tags column
+        .flexible(true)
+        .from_path(path)
+        .map_err(csv_error)?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let column = |idx: usize| record.get(idx).unwrap_or("").trim().to_string();
+
+        let front = column(mapping.front);
+        // blank lines and rows without a front are not cards
+        if front.is_empty() {
+            continue;
+        }
+        if record.len() <= mapping.back {
+            let line = record.position().map_or(0, |pos| pos.line());
+            return Err(Error::InvalidInput(format!("row {} has no column {} for the back", line, mapping.back)));
+        }
+
+        let deck_name = mapping.deck
+            .map(column)
+            .filter(|deck| !deck.is_empty())
+            .unwrap_or_else(|| default_deck.clone());
+        let tags = mapping.tags
+            .map(|idx| column(idx)
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect())
+            .unwrap_or_default();
+
+        rows.push(ImportRow { deck_name, front, back: column(mapping.back), tags });
+    }
+
+    Ok(rows)
+}
+
+fn csv_error(err: csv::Error) -> Error {
+    let message = err.to_string();
+    match err.into_kind() {
+        csv::ErrorKind::Io(err) => Error::from(err),
+        _ => Error::InvalidInput(format!("cannot parse delimited file: {}", message))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::read_deadline_contents;
+    use crate::tags::read_card_tags;
+    use crate::testing::{create_test_entry, root_id, temp_dir, test_connection};
+
+    fn mapping() -> ColumnMapping {
+        ColumnMapping { delimiter: None, has_headers: true, front: 0, back: 1, tags: Some(2), deck: None, default_deck: None }
+    }
+
+    fn row(deck_name: &str, front: &str, back: &str, tags: &[&str]) -> ImportRow {
+        ImportRow {
+            deck_name: deck_name.to_string(),
+            front: front.to_string(),
+            back: back.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect()
+        }
+    }
+
+    #[test]
+    fn preview_reads_quoted_cells_and_tags() {
+        let path = temp_dir("csv-preview").join("words.csv");
+        std::fs::write(&path, "front,back,tags\n\"two\nlines\",\"a, b\",\"verb, noun\"\n,skipped,\nlast , back\n").unwrap();
+
+        let rows = preview_delimited(&path, &mapping(), 10).unwrap();
+        assert_eq!(rows, vec![
+            row("words", "two\nlines", "a, b", &["verb", "noun"]),
+            row("words", "last", "back", &[])
+        ]);
+        assert_eq!(preview_delimited(&path, &mapping(), 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tabs_are_guessed_from_the_extension() {
+        let dir = temp_dir("csv-tsv");
+        let path = dir.join("verbs.tsv");
+        std::fs::write(&path, "gehen\tto go, walk\tgerman verb\tGerman\nsehen\tto see\t\t\n").unwrap();
+        let mapping = ColumnMapping { has_headers: false, deck: Some(3), default_deck: Some(String::from("Misc")), ..mapping() };
+
+        assert_eq!(preview_delimited(&path, &mapping, 10).unwrap(), vec![
+            row("German", "gehen", "to go, walk", &["german", "verb"]),
+            row("Misc", "sehen", "to see", &[])
+        ]);
+
+        // the same file under another name is read with commas, which leaves
+        // the second row in a single column
+        let renamed = dir.join("verbs.txt");
+        std::fs::copy(&path, &renamed).unwrap();
+        assert!(matches!(preview_delimited(&renamed, &mapping, 10), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn rows_without_a_back_are_rejected() {
+        let path = temp_dir("csv-short").join("short.csv");
+        std::fs::write(&path, "only a front\n").unwrap();
+        let mapping = ColumnMapping { has_headers: false, ..mapping() };
+        assert!(matches!(preview_delimited(&path, &mapping, 10), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn imports_escaped_cards_into_their_decks() {
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let deadline_id = create_test_entry(conn, "exam", root, "deadline", 10);
+        let existing_deck = create_test_entry(conn, "maths", deadline_id, "deck", 0);
+
+        let path = temp_dir("csv-import").join("cards.csv");
+        std::fs::write(&path, "front,back,tags,deck\n\"a < b & c\",\"first\nsecond\",\"algebra proof\",maths\nplain,\"<i>not italic</i>\",,\n").unwrap();
+        let mapping = ColumnMapping { deck: Some(3), ..mapping() };
+        let card_ids = import_delimited(conn, &path, deadline_id, &mapping).unwrap();
+        assert_eq!(card_ids.len(), 2);
+
+        let decks = read_deadline_contents(conn, deadline_id).unwrap();
+        assert_eq!(decks.len(), 2);
+        let maths = decks.iter().find(|deck| deck.deck_name == "maths").unwrap();
+        assert_eq!(maths.deck_id, existing_deck);
+        assert_eq!(maths.cards[0].front, "<div>a &lt; b &amp; c</div>");
+        assert_eq!(maths.cards[0].back, "<div>first<br>second</div>");
+
+        // rows without a deck go to a new deck named after the file
+        let cards = decks.iter().find(|deck| deck.deck_name == "cards").unwrap();
+        assert_eq!(cards.cards[0].back, "<div>&lt;i&gt;not italic&lt;/i&gt;</div>");
+
+        let tags = read_card_tags(conn, &card_ids).unwrap();
+        assert_eq!(tags.get(&maths.cards[0].id), Some(&vec![String::from("algebra"), String::from("proof")]));
+        assert_eq!(tags.get(&cards.cards[0].id), None);
+    }
+
+    #[test]
+    fn cells_become_editor_html() {
+        assert_eq!(plain_to_html("x > \"y\"\nz"), "<div>x &gt; &quot;y&quot;<br>z</div>");
+    }
+}
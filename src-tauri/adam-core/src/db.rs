@@ -0,0 +1,101 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// bundle identifier from `tauri.conf.json`; tauri stores app data under this name
+pub const APP_IDENTIFIER: &str = "adam.dev";
+pub const DATABASE_FILE: &str = "adam.db";
+pub const MEDIA_DIR: &str = "media";
+
+
+// where the database is, see `resolve_database_path`
+pub struct DatabaseLocation {
+    pub path: PathBuf,
+    // legacy database copied to `path` on this launch, for the caller to report
+    pub migrated_from: Option<PathBuf>
+}
+
+
+/**
+ * Returns the location of the database, in order of precedence:
+ *  1. `--database <path>` (or `--database=<path>`) in `args`
+ *  2. the `DATABASE_URL` environment variable (which may come from `.env`)
+ *  3. `adam.db` in the platform app-data directory
+ *
+ * In the default case the app-data directory is created if needed, and a
+ * database left in the working directory by older versions is copied there
+ * on first launch, which `migrated_from` tells.
+ */
+pub fn resolve_database_path(args: &[String]) -> io::Result<DatabaseLocation> {
+    if let Some(path) = parse_database_flag(args) {
+        return Ok(DatabaseLocation { path: PathBuf::from(path), migrated_from: None });
+    }
+
+    if let Ok(database_url) = env::var("DATABASE_URL") {
+        if !database_url.is_empty() {
+            return Ok(DatabaseLocation { path: PathBuf::from(database_url), migrated_from: None });
+        }
+    }
+
+    let data_dir = app_data_dir().ok_or_else(|| io::Error::new(
+        io::ErrorKind::NotFound, 
+        "could not determine the app data directory; set DATABASE_URL or pass --database"
+    ))?;
+    fs::create_dir_all(&data_dir)?;
+
+    let database_path = data_dir.join(DATABASE_FILE);
+    let legacy_path = Path::new(DATABASE_FILE);
+    let is_migrated = migrate_legacy_database(legacy_path, &database_path)?;
+
+    Ok(DatabaseLocation {
+        path: database_path,
+        migrated_from: is_migrated.then(|| legacy_path.to_path_buf())
+    })
+}
+
+
+/**
+ * Platform app-data directory of Adam, e.g. `~/.local/share/adam.dev` on Linux
+ * or `~/Library/Application Support/adam.dev` on macOS
+ */
+pub fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+
+/**
+ * Directory of the media store of the database at `database_path`, next to it
+ */
+pub fn media_dir(database_path: &Path) -> PathBuf {
+    database_path.parent().unwrap_or(Path::new("")).join(MEDIA_DIR)
+}
+
+
+/**
+ * Copies the database at `legacy_path` to `database_path` if the latter does not
+ * exist yet. Returns whether a database was migrated. The legacy file is kept as 
+ * a backup.
+ */
+pub fn migrate_legacy_database(legacy_path: &Path, database_path: &Path) -> io::Result<bool> {
+    if database_path.exists() || !legacy_path.is_file() {
+        return Ok(false);
+    }
+
+    fs::copy(legacy_path, database_path)?;
+    Ok(true)
+}
+
+
+fn parse_database_flag(args: &[String]) -> Option<String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--database" {
+            return args.next().cloned();
+        }
+        if let Some(path) = arg.strip_prefix("--database=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
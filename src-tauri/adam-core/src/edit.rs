@@ -0,0 +1,701 @@
+// #![allow(unused_imports)]
+// #![allow(unused_variables)]
+// #![allow(dead_code)]
+
+use diesel::{insert_into, delete, update};
+use diesel::prelude::*;
+
+use chrono::prelude::*;
+
+use serde::{
+    Serialize, 
+    Deserialize
+};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::models::{Card, NewCard};
+use crate::render::{render_card, check_format};
+use crate::tags::{add_tag, read_card_tags, delete_card_tags};
+use crate::media::{card_media, remove_unreferenced_media};
+use crate::error::{Error, Result, Context};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeckContents {
+    pub deck_id: i32,
+    pub deck_name: String,
+    pub cards: Vec<Card>,
+    // tags of each card by card id; cards without tags are left out
+    pub tags: BTreeMap<i32, Vec<String>>,
+    // ids of the cards that have a reverse card, see `set_bidirectional`
+    pub bidirectional: BTreeSet<i32>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeckNewContents {
+    pub deck_id: i32,
+    pub deck_name: String,
+    pub cards: Vec<NewCard>
+}
+
+use crate::home::compute_num_boxes_from_id;
+use crate::review_log::{read_card_state, CardState};
+use crate::scheduler::{get_scheduler, get_deck_scheduler};
+use crate::cloze::{get_cloze_id, update_cloze, delete_cloze};
+
+
+pub fn read_deadline_contents(conn: &mut SqliteConnection, deadline_id: i32) -> Result<Vec<DeckContents>> {
+    use crate::schema::{parents, cards, clozes, entries};
+
+    // get deck ids and names of deadline
+    let deck_ids = parents::table
+        .filter(parents::parent_id.eq(deadline_id))
+        .select(parents::child_id)
+        .load::<i32>(conn)?;
+
+
+    let mut deadline_contents: Vec<DeckContents> = Vec::new();
+    // get card ids for each deck
+    for deck_id in deck_ids {
+        // reverse cards are edited through their card
+        let item_ids = cards::table
+            .filter(cards::deck_id.eq(deck_id).and(cards::reverse_of.is_null()))
+            .select(cards::id)
+            .load::<i32>(conn)?;
+
+        let card_data = cards::table
+            .filter(cards::id.eq_any(item_ids))
+            .select((cards::id, cards::front, cards::back, cards::format, cards::cloze_id))
+            .get_results::<(i32, String, String, String, Option<i32>)>(conn)?;
+
+        // a cloze shows once, as its first card with the source as front
+        let mut cloze_sources: HashMap<i32, String> = clozes::table
+            .filter(clozes::deck_id.eq(deck_id))
+            .select((clozes::id, clozes::source))
+            .get_results::<(i32, String)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut cards: Vec<Card> = Vec::new();
+        for card in card_data {
+            match card {
+                (id, front, back, format, None) => cards.push( render_card(id, front, back, format) ),
+                (id, _, _, format, Some(cloze_id)) => {
+                    if let Some(source) = cloze_sources.remove(&cloze_id) {
+                        cards.push( render_card(id, source, String::new(), format) );
+                    }
+                }
+            }
+        }
+
+
+        let deck_name = entries::table
+            .filter(entries::id.eq(deck_id))
+            .select(entries::name)
+            .get_result::<String>(conn)?;
+
+        let card_ids: Vec<i32> = cards.iter().map(|card| card.id).collect();
+        let tags = read_card_tags(conn, &card_ids)?;
+        let bidirectional = cards::table
+            .filter(cards::reverse_of.eq_any(&card_ids))
+            .select(cards::reverse_of.assume_not_null())
+            .load::<i32>(conn)?
+            .into_iter()
+            .collect();
+
+        deadline_contents.push( DeckContents { deck_id, deck_name, cards, tags, bidirectional } );
+    }
+
+
+    // get card contents for each card id
+    Ok(deadline_contents)
+}
+
+
+/**
+ * Deletes a card with its reverse card, or the whole cloze it belongs to.
+ * Media in `media_dir` only these cards referred to are removed with them
+ */
+pub fn delete_card(conn: &mut SqliteConnection, card_id: i32, media_dir: &Path) -> Result<()> {
+    use crate::schema::cards;
+
+    // the cards, their quotas and their media are removed together or not at all
+    conn.transaction(|conn| {
+        // the edit page shows a cloze as one card, so deleting it deletes the cloze
+        let media_files = match get_cloze_id(conn, card_id)? {
+            Some(cloze_id) => {
+                let cloze_card_ids = cards::table
+                    .filter(cards::cloze_id.eq(cloze_id))
+                    .select(cards::id)
+                    .get_results::<i32>(conn)?;
+                let media_files = card_media(conn, &cloze_card_ids)?;
+                delete_cloze(conn, cloze_id)?;
+                media_files
+            },
+            None => delete_card_and_reverse(conn, card_id)?
+        };
+        remove_unreferenced_media(conn, media_dir, &media_files)?;
+        Ok(())
+    })
+}
+
+// deletes a card that is not part of a cloze, and its reverse card, returning
+// the media they referred to
+fn delete_card_and_reverse(conn: &mut SqliteConnection, card_id: i32) -> Result<Vec<String>> {
+    use crate::schema::cards;
+
+    let deck_id = cards::table
+        .filter(cards::id.eq(card_id))
+        .select(cards::deck_id)
+        .get_result::<i32>(conn)
+        .context(&format!("card {}", card_id))?;
+
+    let scheduler = get_deck_scheduler(conn, deck_id)?;
+
+    // a bidirectional card goes with its reverse card
+    let mut card_ids = vec![card_id];
+    card_ids.extend(cards::table
+        .filter(cards::reverse_of.eq(card_id))
+        .select(cards::id)
+        .get_results::<i32>(conn)?);
+    let media_files = card_media(conn, &card_ids)?;
+
+    for card_id in card_ids {
+        let state = read_card_state(conn, card_id)?;
+        delete_card_tags(conn, &[card_id])?;
+        delete(cards::table.filter(cards::id.eq(card_id)))
+            .execute(conn)?;
+
+        scheduler.card_deleted(conn, deck_id, &state)?;
+    }
+    Ok(media_files)
+}
+
+
+/**
+ * Gives a card a reverse card, showing its back first, or deletes the one it
+ * has. A new reverse card starts as a new card with the card's tags and counts
+ * towards the deck's quotas. Cloze cards and reverse cards cannot be made
+ * bidirectional
+ */
+pub fn set_bidirectional(conn: &mut SqliteConnection, card_id: i32, is_bidirectional: bool) -> Result<()> {
+    use crate::schema::cards;
+
+    let (deck_id, front, back, format, cloze_id, reverse_of) = cards::table
+        .filter(cards::id.eq(card_id))
+        .select((cards::deck_id, cards::front, cards::back, cards::format, cards::cloze_id, cards::reverse_of))
+        .get_result::<(i32, String, String, String, Option<i32>, Option<i32>)>(conn)
+        .context(&format!("card {}", card_id))?;
+    if cloze_id.is_some() {
+        return Err(Error::InvalidInput(format!("card {} is part of a cloze", card_id)));
+    }
+    if reverse_of.is_some() {
+        return Err(Error::InvalidInput(format!("card {} is a reverse card", card_id)));
+    }
+
+    let reverse_id = cards::table
+        .filter(cards::reverse_of.eq(card_id))
+        .select(cards::id)
+        .first::<i32>(conn)
+        .optional()?;
+    let scheduler = get_deck_scheduler(conn, deck_id)?;
+
+    conn.transaction(|conn| {
+        match (is_bidirectional, reverse_id) {
+            (true, None) => {
+                let state = scheduler.new_card_state(conn, deck_id)?;
+                insert_card(conn, deck_id, &back, &front, &format, &state, Some(card_id))?;
+                // tagging the card again tags its new reverse card too
+                for name in read_card_tags(conn, &[card_id])?.remove(&card_id).unwrap_or_default() {
+                    add_tag(conn, card_id, &name)?;
+                }
+                scheduler.cards_created(conn, deck_id, 1)?;
+            },
+            (false, Some(reverse_id)) => {
+                // the reverse card refers to the same media as the card, so none is removed
+                let state = read_card_state(conn, reverse_id)?;
+                delete_card_tags(conn, &[reverse_id])?;
+                delete(cards::table.filter(cards::id.eq(reverse_id)))
+                    .execute(conn)?;
+                scheduler.card_deleted(conn, deck_id, &state)?;
+            },
+            _ => {}
+        }
+        Ok(())
+    })
+}
+
+
+// create cards, returning ids of returned cards
+/**
+ * Creates cards in deck_contents into the `cards` table associated with the proper deck.
+ * Bidirectional cards also get a reverse card, which counts towards quotas but
+ * whose id is not returned
+ */
+pub fn create_cards(conn: &mut SqliteConnection, deadline_id: i32, deck_new_contents: DeckNewContents) -> Result<Vec<i32>> {
+    
+    let deck_id = deck_new_contents.deck_id;
+    let num_reverse = deck_new_contents.cards.iter().filter(|card| card.is_bidirectional).count();
+
+    let scheduler = get_scheduler(conn, deadline_id)?;
+
+    // cards and their quotas are written together or not at all
+    conn.transaction(|conn| {
+        // add new cards to `cards` database
+        let state = scheduler.new_card_state(conn, deck_id)?;
+        let card_ids = insert_deck_contents(conn, deck_new_contents, &state)?;
+
+        // account for quotas
+        scheduler.cards_created(conn, deck_id, (card_ids.len() + num_reverse) as i32)?;
+
+        // return ids of new cards
+        Ok(card_ids)
+    })
+
+}
+
+// `state` is the scheduling state new cards start in, see `Scheduler::new_card_state`.
+// Returns one id per new card; reverse cards of bidirectional ones are inserted after them
+pub fn insert_deck_contents(conn: &mut SqliteConnection, deck_new_contents: DeckNewContents, state: &CardState) -> Result<Vec<i32>> {
+    let deck_id = deck_new_contents.deck_id;
+
+    let mut card_ids = Vec::new();
+    for new_card in deck_new_contents.cards {
+        check_format(&new_card.format)?;
+        let card_id = insert_card(conn, deck_id, &new_card.front, &new_card.back, &new_card.format, state, None)?;
+        if new_card.is_bidirectional {
+            insert_card(conn, deck_id, &new_card.back, &new_card.front, &new_card.format, state, Some(card_id))?;
+        }
+        card_ids.push(card_id);
+    }
+    Ok(card_ids)
+}
+
+// returns the id of the inserted card
+fn insert_card(conn: &mut SqliteConnection, deck_id: i32, front: &str, back: &str, format: &str, state: &CardState, reverse_of: Option<i32>) -> Result<i32> {
+    use crate::schema::cards;
+
+    insert_into(cards::table)
+        .values((
+            cards::front.eq(front), 
+            cards::deck_id.eq(deck_id), 
+            cards::back.eq(back), 
+            cards::format.eq(format),
+            cards::box_position.eq(state.box_position), 
+            cards::next_practice.eq(state.next_practice),
+            cards::repetitions.eq(state.repetitions), 
+            cards::interval.eq(state.interval), 
+            cards::easiness.eq(state.easiness),
+            cards::stability.eq(state.stability),
+            cards::difficulty.eq(state.difficulty),
+            cards::last_review.eq(state.last_review),
+            cards::learning_step.eq(state.learning_step),
+            cards::due_at.eq(state.due_at),
+            cards::lapses.eq(state.lapses),
+            cards::reverse_of.eq(reverse_of)
+        ))
+        .execute(conn)?;
+
+    let card_id = cards::table
+        .filter(cards::deck_id.eq(deck_id))
+        .select(cards::id)
+        .order(cards::id.desc())
+        .first::<i32>(conn)?;
+
+    Ok(card_id)
+}
+
+
+/**
+ * Updates an existing card in the file system, called on the `onChange` event. 
+ * Allows a user to change the contents of a card with the changes saving.
+ * A cloze card takes its new front as the new source of its cloze, updating
+ * all its siblings, and fails when the front has no cloze markers left; delete
+ * the cloze to make plain cards of it. A bidirectional card and its
+ * reverse card are kept mirrored
+ */
+pub fn update_card(conn: &mut SqliteConnection, card: Card) -> Result<()> {
+    use crate::schema::cards;
+
+    check_format(&card.format)?;
+
+    // the front of a cloze card is the source of its cloze, which
+    // `update_cloze` rejects when it has no markers left
+    if let Some(cloze_id) = get_cloze_id(conn, card.id)? {
+        return update_cloze(conn, cloze_id, &card.front);
+    }
+
+    let reverse_of = cards::table
+        .filter(cards::id.eq(card.id))
+        .select(cards::reverse_of)
+        .get_result::<Option<i32>>(conn)
+        .context(&format!("card {}", card.id))?;
+
+    conn.transaction(|conn| {
+        update(cards::table)
+            .filter(cards::id.eq(card.id))
+            .set((cards::front.eq(&card.front), cards::back.eq(&card.back), cards::format.eq(&card.format)))
+            .execute(conn)?;
+
+        // the linked card shows the same sides the other way round
+        let linked = match reverse_of {
+            Some(card_id) => cards::table.filter(cards::id.eq(card_id)).into_boxed(),
+            None => cards::table.filter(cards::reverse_of.eq(card.id)).into_boxed()
+        };
+        update(cards::table)
+            .filter(cards::id.eq_any(linked.select(cards::id)))
+            .set((cards::front.eq(&card.back), cards::back.eq(&card.front), cards::format.eq(&card.format)))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+
+
+////////////////// HELPERS FOR QUOTAS //////////////////
+
+
+// quotas helper
+use crate::models::QuotaRecord;
+use crate::utils::{days_until_deadline, read_day_hours};
+
+
+pub fn get_days_to_go(conn: &mut SqliteConnection, deadline_id: i32) -> Result<i32> {
+    use crate::schema::deadlines;
+
+    let (deadline_date, deadline_tz) = deadlines::table
+        .filter(deadlines::id.eq(deadline_id))
+        .select((deadlines::deadline_date, deadlines::deadline_tz))
+        .get_result::<(Option<NaiveDateTime>, Option<String>)>(conn)
+        .context(&format!("deadline {}", deadline_id))?;
+    let deadline_date = deadline_date
+        .ok_or_else(|| Error::InvalidInput(format!("entry {} has no deadline date", deadline_id)))?;
+
+    let hours = read_day_hours(conn)?;
+
+    Ok(days_until_deadline(
+        deadline_date,
+        deadline_tz.as_deref(),
+        hours.new_day_hour,
+        hours.deadline_cutoff_hour
+    )? as i32)
+}
+
+pub fn write_quotas(conn: &mut SqliteConnection, deadline_id: i32, deck_id: i32, num_cards: i32) -> Result<()> {
+
+    use crate::schema::quotas;
+    // write quotas for `num_new` new cards
+    let days_to_go = get_days_to_go(conn, deadline_id)?;
+    // quotas of a passed deadline are rebuilt by `reset_deadline`
+    if num_cards > 0 && days_to_go >= 0 {
+        let num_boxes = compute_num_boxes_from_id(conn, deadline_id)?;
+        let mut quota_records = compute_quotas(num_cards, days_to_go, num_boxes)?;
+        discount_past_progressions(conn, &mut quota_records, deck_id)?;
+
+        for new_quota_record in quota_records {
+            let existing_quota = quotas::table
+                .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(new_quota_record.days_to_go)))
+                .select((quotas::days_to_go, quotas::new_assigned, quotas::review_assigned, quotas::new_quota_initial, quotas::review_quota_initial, quotas::new_practiced, quotas::review_practiced))
+                .get_result::<(i32, i32, i32, i32, i32, i32, i32)>(conn)
+                .optional()?;
+
+            match existing_quota {
+                Some(q) => {
+                    let combined_quota_record = add_quota_records(
+                        new_quota_record,
+                        QuotaRecord { days_to_go: q.0, new_assigned: q.1, review_assigned: q.2, new_quota_initial: q.3, review_quota_initial: q.4, new_practiced: q.5, review_practiced: q.6 }
+                    );
+    
+                    update(quotas::table)
+                        .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(combined_quota_record.days_to_go)))
+                        .set(combined_quota_record)
+                        .execute(conn)?;
+                },
+
+                None => {
+                    insert_into(quotas::table)
+                        .values((new_quota_record, quotas::id.eq(deck_id)))
+                        .execute(conn)?;
+                }
+                
+            }
+
+        }
+
+    }
+    Ok(())
+}
+
+
+
+// computes quotas for `num_cards` given `days_to_go` and `num_boxes`
+pub fn compute_quotas(num_cards: i32, days_to_go: i32, num_boxes: i32)  -> Result<Vec<QuotaRecord>> {
+    if num_cards <= 0 {
+        return Err(Error::SchedulingError(format!("cannot compute quotas for {} cards", num_cards)));
+    }
+
+    let n = num_cards;          // number of cards                                                           
+    let t = days_to_go;         // days until deadline                                          
+    let b = num_boxes;          // number of boxes
+                                                                                
+    let mut sum: i32 = (0..t).sum();
+    // avoid division by zero error for when t = 0, 1
+    if sum == 0 {
+      sum += 1;
+    }
+ 
+    // compute new card quota vector
+    let mut nq: Vec<i32> = (0..t).rev().map(|x| x * n / sum).collect();
+    let nq_sum = nq.iter().sum::<i32>();
+
+    // enforce sum of NQ equals number of cards by adding remainder
+    if let Some(first) = nq.get_mut(0) {
+        *first += n - nq_sum;
+        // no new cards on last day
+        nq.push(0);
+    }
+                                                                                
+ 
+    // compute review card quota vector
+    let mut rq: Vec<i32> = (0..t).map(|x| x * n * (b - 2) / sum).collect();                             
+    let rq_sum = rq.iter().sum::<i32>();
+
+    // enforce sum of RQ equals number of cards times number of bins minus 2
+    if let Some(last) = rq.last_mut() {
+        *last += (n * (b - 2)) - rq_sum;
+        // user reviews all cards the day of exam
+        rq.push(n);
+    }
+    
+    // review cards if days_to_go == 0
+    if days_to_go == 0 {
+      nq.push(n);
+      rq.push(n * (b - 1));
+    }
+
+    let mut quotas = Vec::new();
+    for i in 0..nq.len() {
+        let dtg = nq.len() - 1 - i; // days to go
+        quotas.push(
+            QuotaRecord {
+                days_to_go: dtg as i32,
+                new_assigned: nq[i],
+                review_assigned: rq[i],
+                new_quota_initial: nq[i],
+                review_quota_initial: rq[i],
+                new_practiced: 0,
+                review_practiced: 0
+            }
+        );
+    }
+
+    Ok(quotas)
+}
+
+// fn subtract_quota_records(q1: QuotaRecord, q2: QuotaRecord) -> QuotaRecord{
+//     QuotaRecord {
+//         days_to_go: q1.days_to_go,
+//         new_assigned: q1.new_assigned - q2.new_assigned,
+//         review_assigned: q1.review_assigned - q2.review_assigned,
+//         new_quota_initial: q1.new_quota_initial - q2.new_quota_initial,
+//         review_quota_initial: q1.review_quota_initial - q2.review_quota_initial,
+//         new_practiced: q1.new_practiced - q2.new_practiced,
+//         review_practiced: q1.review_practiced - q2.review_practiced
+//     }
+// }
+
+fn add_quota_records(q1: QuotaRecord, q2: QuotaRecord) -> QuotaRecord{
+    QuotaRecord {
+        days_to_go: q1.days_to_go,
+        new_assigned: q1.new_assigned + q2.new_assigned,
+        review_assigned: q1.review_assigned + q2.review_assigned,
+        new_quota_initial: q1.new_quota_initial + q2.new_quota_initial,
+        review_quota_initial: q1.review_quota_initial + q2.review_quota_initial,
+        new_practiced: q1.new_practiced + q2.new_practiced,
+        review_practiced: q1.review_practiced + q2.review_practiced
+    }
+}
+
+
+
+pub fn write_text_field(conn: &mut SqliteConnection, is_text_field: bool) -> Result<()> { 
+    use crate::schema::userconfig;
+    
+    update(userconfig::table)
+        .set(userconfig::is_text_field.eq(is_text_field))
+        .execute(conn)?;
+    Ok(())
+}
+
+/**
+ * Decreases values in `new_quotas` to account for past reviews, encoded in 
+ * box positions of cards in `cards`
+ * 
+ * This function arises from the scheme where quotas are computed only based on
+ * number of cards and days until deadline
+ */
+pub fn discount_past_progressions(conn: &mut SqliteConnection, new_quotas: &mut [QuotaRecord], deck_id: i32) -> Result<()> {
+     use crate::schema::cards;
+
+    if new_quotas.len() <= 1 {
+        return Ok(());
+    }
+
+     // get array of card box positions in deck
+     let box_positions = cards::table
+        .filter(cards::deck_id.eq(deck_id))
+        .select(cards::box_position)
+        .get_results::<Option<i32>>(conn)?;
+
+    let box_positions = box_positions.into_iter().flatten().collect::<Vec<i32>>();
+
+    // return if all new cards
+    if box_positions.iter().sum::<i32>() == 0 {
+        return Ok(());
+    }
+
+    // get number of cards which are advanced from the initial box
+    let tot_new_advanced: i32 = box_positions.iter()
+        .map(|x| (*x > 0) as i32).sum();
+
+    // get number of times cards are advanced, not counting initial advance
+    let tot_review_advanced = box_positions.iter()
+        .map(|x| (x - 1) * ((*x > 0) as i32))
+        .sum::<i32>();
+
+
+    let days = new_quotas.len() as i32 - 1;
+    let new_per_day = tot_new_advanced / days;
+    let mut remainder = tot_new_advanced - days * new_per_day;
+    for dtg in 1..new_quotas.len() {
+        new_quotas[dtg].new_assigned -= new_per_day;
+        new_quotas[dtg].new_quota_initial -= new_per_day;
+
+        // subtract remainder to day furthest from deadline
+        if remainder > 0 {
+            let sub_value = std::cmp::min(new_quotas[dtg].new_assigned, remainder);
+            new_quotas[dtg].new_assigned -= sub_value;
+            new_quotas[dtg].new_quota_initial -= sub_value;
+            remainder -= sub_value;
+        }
+    }
+    if remainder != 0 {
+        return Err(Error::SchedulingError(format!("{} advanced new cards left undiscounted in deck {}", remainder, deck_id)));
+    }
+
+    let review_per_day = tot_review_advanced / days;
+
+    let mut remainder = tot_review_advanced - days * review_per_day;
+    for dtg in (1..new_quotas.len()).rev() {
+            new_quotas[dtg].review_assigned -= review_per_day;
+            new_quotas[dtg].review_quota_initial -= review_per_day;
+
+            // subtract remainder to before deadline day
+            if remainder > 0 {
+            let sub_value = std::cmp::min(new_quotas[dtg].review_assigned, remainder);
+            new_quotas[dtg].review_assigned -= sub_value;
+            new_quotas[dtg].review_quota_initial -= sub_value;
+            remainder -= sub_value;
+        }
+    }
+
+    if remainder != 0 {
+        return Err(Error::SchedulingError(format!("{} review advances left undiscounted in deck {}", remainder, deck_id)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::home::get_deck_quota;
+    use crate::testing::{create_test_cards, create_test_entry, new_card, root_id, test_connection};
+
+    // sum of the new cards assigned over a deck's quota days
+    fn new_assigned(conn: &mut SqliteConnection, deck_id: i32) -> i32 {
+        use crate::schema::quotas;
+
+        quotas::table
+            .filter(quotas::id.eq(deck_id))
+            .select(quotas::new_assigned)
+            .load::<i32>(conn)
+            .unwrap()
+            .into_iter()
+            .sum()
+    }
+
+    #[test]
+    fn reverse_cards_count_towards_quotas() {
+        use crate::schema::cards;
+
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let deadline_id = create_test_entry(conn, "deadline", root, "deadline", 10);
+        let deck_id = create_test_entry(conn, "deck", deadline_id, "deck", 0);
+
+        let mut bidirectional = new_card("Hund", "dog");
+        bidirectional.is_bidirectional = true;
+        let cards = vec![bidirectional, new_card("Katze", "cat")];
+        let card_ids = create_cards(conn, deadline_id, DeckNewContents { deck_id, deck_name: String::new(), cards }).unwrap();
+        assert_eq!(card_ids.len(), 2);
+
+        let reverse = cards::table
+            .filter(cards::reverse_of.eq(card_ids[0]))
+            .select((cards::front, cards::back))
+            .get_result::<(String, String)>(conn)
+            .unwrap();
+        assert_eq!(reverse, (String::from("dog"), String::from("Hund")));
+
+        // quotas are those of three cards
+        let days_to_go = get_days_to_go(conn, deadline_id).unwrap();
+        let num_boxes = compute_num_boxes_from_id(conn, deadline_id).unwrap();
+        let today = compute_quotas(3, days_to_go, num_boxes).unwrap()
+            .into_iter()
+            .find(|record| record.days_to_go == days_to_go)
+            .unwrap();
+        assert_eq!(new_assigned(conn, deck_id), 3);
+        assert_eq!(get_deck_quota(conn, deck_id).unwrap().unwrap().new_left, today.new_assigned);
+
+        let contents = read_deadline_contents(conn, deadline_id).unwrap();
+        assert_eq!(contents[0].cards.len(), 2);
+        assert_eq!(contents[0].bidirectional, BTreeSet::from([card_ids[0]]));
+    }
+
+    #[test]
+    fn set_bidirectional_adds_and_removes_the_reverse_card() {
+        use crate::schema::cards;
+
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let deadline_id = create_test_entry(conn, "deadline", root, "deadline", 10);
+        let deck_id = create_test_entry(conn, "deck", deadline_id, "deck", 0);
+        let card_id = create_test_cards(conn, deadline_id, deck_id, &[("Hund", "dog")])[0];
+        add_tag(conn, card_id, "german").unwrap();
+        assert_eq!(new_assigned(conn, deck_id), 1);
+
+        set_bidirectional(conn, card_id, true).unwrap();
+        set_bidirectional(conn, card_id, true).unwrap();
+        let reverse_ids = cards::table
+            .filter(cards::reverse_of.eq(card_id))
+            .select(cards::id)
+            .load::<i32>(conn)
+            .unwrap();
+        assert_eq!(reverse_ids.len(), 1);
+        assert_eq!(read_card_tags(conn, &reverse_ids).unwrap()[&reverse_ids[0]], vec![String::from("german")]);
+        assert_eq!(new_assigned(conn, deck_id), 2);
+        assert_eq!(read_deadline_contents(conn, deadline_id).unwrap()[0].bidirectional, BTreeSet::from([card_id]));
+        assert!(matches!(set_bidirectional(conn, reverse_ids[0], false), Err(Error::InvalidInput(_))));
+
+        set_bidirectional(conn, card_id, false).unwrap();
+        let num_reverse = cards::table
+            .filter(cards::reverse_of.eq(card_id))
+            .count()
+            .get_result::<i64>(conn)
+            .unwrap();
+        assert_eq!(num_reverse, 0);
+        assert_eq!(new_assigned(conn, deck_id), 1);
+        assert!(read_deadline_contents(conn, deadline_id).unwrap()[0].bidirectional.is_empty());
+    }
+}
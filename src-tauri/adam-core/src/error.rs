@@ -0,0 +1,81 @@
+use serde::Serialize;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+
+/**
+ * Errors surfaced by the engine. Tauri commands return these to the frontend,
+ * where they serialize as `{ kind: "not_found", message: "..." }`
+ */
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum Error {
+    // a row the caller referred to does not exist
+    NotFound(String),
+    // the database contradicts itself, e.g. a deck without a parent deadline
+    IntegrityViolation(String),
+    // arguments from the caller cannot be used, e.g. an unparseable date
+    InvalidInput(String),
+    // the scheduler cannot produce a plan, e.g. a deadline too far away
+    SchedulingError(String),
+    // any other failure of the database itself
+    DatabaseError(String),
+    // a file could not be read or written, e.g. during an import
+    IoError(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(msg) => write!(f, "not found: {}", msg),
+            Error::IntegrityViolation(msg) => write!(f, "integrity violation: {}", msg),
+            Error::InvalidInput(msg) => write!(f, "invalid input: {}", msg),
+            Error::SchedulingError(msg) => write!(f, "scheduling error: {}", msg),
+            Error::DatabaseError(msg) => write!(f, "database error: {}", msg),
+            Error::IoError(msg) => write!(f, "io error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<DieselError> for Error {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => Error::NotFound(String::from("record not found")),
+            DieselError::DatabaseError(kind, info) => match kind {
+                DatabaseErrorKind::UniqueViolation 
+                | DatabaseErrorKind::ForeignKeyViolation 
+                | DatabaseErrorKind::NotNullViolation 
+                | DatabaseErrorKind::CheckViolation => Error::IntegrityViolation(info.message().to_string()),
+                _ => Error::DatabaseError(info.message().to_string()),
+            },
+            err => Error::DatabaseError(err.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::IoError(err.to_string())
+    }
+}
+
+
+/**
+ * Attaches what was being looked up to a `NotFound`, e.g. 
+ * `.get_result(conn).context("card 3")` reports "not found: card 3"
+ */
+pub trait Context<T> {
+    fn context(self, what: &str) -> Result<T>;
+}
+
+impl<T> Context<T> for std::result::Result<T, DieselError> {
+    fn context(self, what: &str) -> Result<T> {
+        self.map_err(|err| match err {
+            DieselError::NotFound => Error::NotFound(what.to_string()),
+            err => Error::from(err),
+        })
+    }
+}
@@ -0,0 +1,311 @@
+use crate::review::{ReviewCard, get_queue_score};
+use crate::anki::{add_ankiquota_practice, balance_interval, new_sm2_state, AnkiOptions, Sm2Scheduler};
+use crate::home::Quota;
+use crate::models::NewReviewLog;
+use crate::review_log::CardState;
+use crate::scheduler::Scheduler;
+use crate::utils::study_today;
+use crate::error::{Error, Result, Context};
+
+
+use diesel::update;
+use diesel::prelude::*;
+
+
+// default FSRS-4.5 weights
+const W: [f32; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 
+    0.1367, 1.0461, 2.1072, 0.0793, 0.3246, 1.587, 0.2272, 2.8755
+];
+const DECAY: f32 = -0.5;
+// makes retrievability 90% once `stability` days have elapsed
+const FACTOR: f32 = 19.0 / 81.0;
+const MAX_INTERVAL: f32 = 36500.0;
+
+pub const DEFAULT_TARGET_RETENTION: f32 = 0.9;
+// below, cards come back too late to be worth the reviews; above, too often
+pub const MIN_TARGET_RETENTION: f32 = 0.7;
+pub const MAX_TARGET_RETENTION: f32 = 0.99;
+
+
+pub struct FsrsResponse {
+    pub interval: i32,   // days until the next review; 0 repeats the card today
+    pub stability: f32,  // days until retrievability falls to 90%
+    pub difficulty: f32, // between 1 and 10
+}
+
+/**
+ * Computes the memory state of a card after an answer with FSRS.
+ * 
+ * Args:
+ *  rating: 1-4 for Again, Hard, Good, Easy
+ *  memory: (stability, difficulty) of the card, None if never answered
+ *  elapsed_days: days since the card was last answered
+ *  target_retention: probability of recall at which the card is due
+ */
+pub fn calculate_fsrs(
+    rating: i32,
+    memory: Option<(f32, f32)>,
+    elapsed_days: i32,
+    target_retention: f32,
+) -> FsrsResponse {
+    let g = rating.clamp(1, 4);
+
+    let (stability, difficulty) = match memory {
+        None => (W[(g - 1) as usize], init_difficulty(g)),
+        Some((s, d)) => {
+            let r = retrievability(elapsed_days.max(0) as f32, s);
+            let stability = if g == 1 {
+                // forgetting never makes a memory more stable
+                (W[11] * d.powf(-W[12]) * ((s + 1.0).powf(W[13]) - 1.0) * (W[14] * (1.0 - r)).exp()).min(s)
+            } else {
+                let hard_penalty = if g == 2 { W[15] } else { 1.0 };
+                let easy_bonus = if g == 4 { W[16] } else { 1.0 };
+                s * (W[8].exp() * (11.0 - d) * s.powf(-W[9]) * ((W[10] * (1.0 - r)).exp() - 1.0) 
+                    * hard_penalty * easy_bonus + 1.0)
+            };
+
+            // difficulty moves with the rating and reverts towards that of a first "Good"
+            let difficulty = d - W[6] * (g - 3) as f32;
+            let difficulty = W[7] * init_difficulty(3) + (1.0 - W[7]) * difficulty;
+            (stability, difficulty.clamp(1.0, 10.0))
+        }
+    };
+    let stability = stability.max(0.01);
+
+    let interval = if g == 1 {
+        0
+    } else {
+        let days = stability / FACTOR * (target_retention.powf(1.0 / DECAY) - 1.0);
+        days.round().clamp(1.0, MAX_INTERVAL) as i32
+    };
+
+    FsrsResponse {
+        interval,
+        stability,
+        difficulty,
+    }
+}
+
+fn init_difficulty(rating: i32) -> f32 {
+    (W[4] - (rating - 3) as f32 * W[5]).clamp(1.0, 10.0)
+}
+
+// probability of recalling a card `elapsed_days` after its last review
+fn retrievability(elapsed_days: f32, stability: f32) -> f32 {
+    (1.0 + FACTOR * elapsed_days / stability).powf(DECAY)
+}
+
+
+/**
+ * Checks that `retention` is a target retention FSRS boxes accept, between
+ * `MIN_TARGET_RETENTION` and `MAX_TARGET_RETENTION`
+ */
+pub fn check_target_retention(retention: f32) -> Result<f32> {
+    if !(MIN_TARGET_RETENTION..=MAX_TARGET_RETENTION).contains(&retention) {
+        return Err(Error::InvalidInput(format!("target retention {} is not between {} and {}",
+            retention, MIN_TARGET_RETENTION, MAX_TARGET_RETENTION)));
+    }
+    Ok(retention)
+}
+
+
+/**
+ * Target retention of an FSRS box
+ */
+pub fn read_target_retention(conn: &mut SqliteConnection, deadline_id: i32) -> Result<f32> {
+    use crate::schema::deadlines;
+
+    let (is_fsrs, target_retention) = deadlines::table
+        .find(deadline_id)
+        .select((deadlines::is_fsrs, deadlines::target_retention))
+        .get_result::<(bool, Option<f32>)>(conn)
+        .context(&format!("deadline {}", deadline_id))?;
+    if !is_fsrs {
+        return Err(Error::InvalidInput(format!("entry {} is not an FSRS box", deadline_id)));
+    }
+    Ok(target_retention.unwrap_or(DEFAULT_TARGET_RETENTION))
+}
+
+
+/**
+ * Sets the probability of recall at which the cards of an FSRS box are due.
+ * Cards already scheduled keep their due date; the new retention applies
+ * from their next answer
+ */
+pub fn write_target_retention(conn: &mut SqliteConnection, deadline_id: i32, retention: f32) -> Result<()> {
+    use crate::schema::deadlines;
+
+    let retention = check_target_retention(retention)?;
+    read_target_retention(conn, deadline_id)?;
+
+    update(deadlines::table.find(deadline_id))
+        .set(deadlines::target_retention.eq(retention))
+        .execute(conn)?;
+    Ok(())
+}
+
+
+// maps the 1-5 ankibox buttons (again hard okay good easy) onto the four FSRS ratings
+fn score_to_rating(score: i32) -> Result<i32> {
+    match score {
+        1 => Ok(1),
+        2 => Ok(2),
+        3 | 4 => Ok(3),
+        5 => Ok(4),
+        _ => Err(Error::InvalidInput(format!("score {} is not between 1 and 5", score)))
+    }
+}
+
+
+pub fn update_card_fsrs(conn: &mut SqliteConnection, card: &ReviewCard, score: i32) -> Result<String> {
+    use crate::schema::{cards, parents, deadlines};
+
+    let rating = score_to_rating(score)?;
+
+    // get card's current memory state
+    let (deck_id, stability, difficulty, last_review, repetitions) = cards::table
+        .filter(cards::id.eq(card.card.id))
+        .select((cards::deck_id, cards::stability, cards::difficulty, cards::last_review, cards::repetitions))
+        .get_result::<(i32, Option<f32>, Option<f32>, Option<chrono::NaiveDate>, Option<i32>)>(conn)
+        .context(&format!("card {}", card.card.id))?;
+
+    let target_retention = parents::table
+        .inner_join(deadlines::table.on(deadlines::id.eq(parents::parent_id)))
+        .filter(parents::child_id.eq(deck_id))
+        .select(deadlines::target_retention)
+        .get_result::<Option<f32>>(conn)
+        .context(&format!("deadline of deck {}", deck_id))?
+        .unwrap_or(DEFAULT_TARGET_RETENTION);
+
+    let today = study_today(conn)?;
+    let elapsed_days = last_review.map_or(0, |date| (today - date).num_days() as i32);
+    let memory = match (stability, difficulty) {
+        (Some(s), Some(d)) => Some((s, d)),
+        _ => None
+    };
+
+    let mut new_stats = calculate_fsrs(rating, memory, elapsed_days, target_retention);
+    new_stats.interval = balance_interval(conn, deck_id, card.card.id, new_stats.interval, today, MAX_INTERVAL as i32)?;
+
+    let next_practice = today + chrono::Duration::days(i64::from(new_stats.interval));
+    // cards repeated today stay in their stack, like SM-2's "again"
+    let repetitions = repetitions.unwrap_or(0) + (new_stats.interval > 0) as i32;
+
+    update(cards::table)
+        .filter(cards::id.eq(card.card.id))
+        .set((
+            cards::front.eq(&card.card.front), 
+            cards::back.eq(&card.card.back), 
+            cards::queue_score.eq(get_queue_score()),
+            cards::next_practice.eq(next_practice),
+            cards::stability.eq(new_stats.stability),
+            cards::difficulty.eq(new_stats.difficulty),
+            cards::interval.eq(new_stats.interval),
+            cards::repetitions.eq(repetitions),
+            cards::last_review.eq(today)
+        ))
+        .execute(conn)?;
+
+    if new_stats.interval > 0 { 
+        add_ankiquota_practice(conn, deck_id, &card.stack_before)?;
+        Ok(String::from("done"))
+    } else { 
+        Ok(String::from(&card.stack_before))
+    }
+}
+
+
+/**
+ * FSRS box: queued and counted like an SM-2 ankibox, but graded by FSRS
+ */
+pub struct FsrsScheduler;
+
+impl Scheduler for FsrsScheduler {
+    // FSRS has no SM-2 options; the SM-2 fields only matter for exports
+    fn new_card_state(&self, conn: &mut SqliteConnection, _deck_id: i32) -> Result<CardState> {
+        Ok(new_sm2_state(&AnkiOptions::default(), study_today(conn)?))
+    }
+
+    fn cards_created(&self, conn: &mut SqliteConnection, deck_id: i32, num_cards: i32) -> Result<()> {
+        Sm2Scheduler.cards_created(conn, deck_id, num_cards)
+    }
+
+    fn card_deleted(&self, conn: &mut SqliteConnection, deck_id: i32, state: &CardState) -> Result<()> {
+        Sm2Scheduler.card_deleted(conn, deck_id, state)
+    }
+
+    fn deck_quota(&self, conn: &mut SqliteConnection, deck_id: i32) -> Result<Option<Quota>> {
+        Sm2Scheduler.deck_quota(conn, deck_id)
+    }
+
+    fn cap_deadline_quotas(&self, conn: &mut SqliteConnection, deadline_id: i32, deck_ids: &[i32], quotas: &mut [Quota]) -> Result<()> {
+        Sm2Scheduler.cap_deadline_quotas(conn, deadline_id, deck_ids, quotas)
+    }
+
+    fn select_new_cards(&self, conn: &mut SqliteConnection, deck_id: i32, limit: i32) -> Result<Vec<i32>> {
+        Sm2Scheduler.select_new_cards(conn, deck_id, limit)
+    }
+
+    fn pop_review_card(&self, conn: &mut SqliteConnection, deck_id: i32, only: Option<&[i32]>) -> Result<Option<ReviewCard>> {
+        Sm2Scheduler.pop_review_card(conn, deck_id, only)
+    }
+
+    // score is 1-5
+    fn grade(&self, conn: &mut SqliteConnection, card: &ReviewCard, score: i32) -> Result<String> {
+        update_card_fsrs(conn, card, score)
+    }
+
+    fn shift_quota(&self, conn: &mut SqliteConnection, log: &NewReviewLog, sign: i32) -> Result<()> {
+        Sm2Scheduler.shift_quota(conn, log, sign)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Card;
+    use crate::testing::{test_connection, root_id, create_test_entry, create_test_cards};
+
+    fn answer_good(conn: &mut SqliteConnection, card_id: i32) -> i32 {
+        use crate::schema::cards;
+
+        let card = Card { id: card_id, front: String::from("front"), back: String::from("back"), format: String::from("html"),
+            front_html: String::new(), back_html: String::new() };
+        update_card_fsrs(conn, &ReviewCard { stack_before: String::from("new"), deck_name: String::new(), card }, 4).unwrap();
+        cards::table.find(card_id).select(cards::interval).get_result::<Option<i32>>(conn).unwrap().unwrap()
+    }
+
+    #[test]
+    fn target_retention_sets_the_interval() {
+        let conn = &mut test_connection();
+        let root_id = root_id(conn);
+        let fsrsbox_id = create_test_entry(conn, "fsrs", root_id, "fsrsbox", 0);
+        let deck_id = create_test_entry(conn, "deck", fsrsbox_id, "deck", 0);
+        let card_ids = create_test_cards(conn, fsrsbox_id, deck_id, &[("a", "1"), ("b", "2")]);
+        assert_eq!(read_target_retention(conn, fsrsbox_id).unwrap(), DEFAULT_TARGET_RETENTION);
+
+        // a first "good" is recalled with 99% after a day, and with 70% after two weeks
+        write_target_retention(conn, fsrsbox_id, 0.99).unwrap();
+        assert_eq!(read_target_retention(conn, fsrsbox_id).unwrap(), 0.99);
+        assert_eq!(answer_good(conn, card_ids[0]), 1);
+        write_target_retention(conn, fsrsbox_id, 0.7).unwrap();
+        assert!((14..=18).contains(&answer_good(conn, card_ids[1])));
+    }
+
+    #[test]
+    fn target_retention_is_checked() {
+        let conn = &mut test_connection();
+        let root_id = root_id(conn);
+        let fsrsbox_id = create_test_entry(conn, "fsrs", root_id, "fsrsbox", 0);
+        let ankibox_id = create_test_entry(conn, "anki", root_id, "ankibox", 0);
+
+        for retention in [0.5, 0.995, 1.0, f32::NAN] {
+            assert!(matches!(write_target_retention(conn, fsrsbox_id, retention), Err(Error::InvalidInput(_))));
+        }
+        assert!(matches!(write_target_retention(conn, ankibox_id, 0.9), Err(Error::InvalidInput(_))));
+        assert!(matches!(write_target_retention(conn, 1000, 0.9), Err(Error::NotFound(_))));
+        assert_eq!(read_target_retention(conn, fsrsbox_id).unwrap(), DEFAULT_TARGET_RETENTION);
+    }
+}
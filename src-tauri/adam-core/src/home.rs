@@ -0,0 +1,897 @@
+// #![allow(unused_imports)]
+// #![allow(unused_variables)]
+// #![allow(dead_code)]
+
+use std::path::Path;
+
+use chrono::{prelude::*, Local, DateTime, Duration}; //, Utc};
+
+use serde::{
+    Serialize, 
+    Deserialize
+};
+
+use diesel::{insert_into, delete, update};
+use diesel::prelude::*;
+
+use crate::error::{Error, Result, Context};
+use crate::utils::{get_num_boxes, local_to_utc, utc_to_local, in_deadline_zone, current_time_zone};
+use crate::edit::{get_days_to_go, write_quotas, insert_deck_contents, DeckNewContents};
+use crate::scheduler::{get_scheduler, get_deck_scheduler};
+use crate::media::{card_media, remove_unreferenced_media};
+use crate::models::NewCard;
+use crate::render::HTML_FORMAT;
+use crate::fsrs::{check_target_retention, DEFAULT_TARGET_RETENTION};
+
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EntryMetadata {
+    pub entry_type: String,
+    pub deadline_date: Option<String>,
+    pub study_intensity: Option<i32>,
+    // FSRS boxes only; defaults to `fsrs::DEFAULT_TARGET_RETENTION`
+    #[serde(default)]
+    pub target_retention: Option<f32>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FolderSystem {
+    pub pairs: Vec<EntryPair>,
+    pub data: Vec<EntryData>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EntryPair {
+    pub parent_id: i32,
+    pub child_id: i32
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EntryData {
+    pub entry_id: i32,
+    pub entry_name: String,
+    pub is_expanded: Option<bool>,
+    pub entry_type: String,
+    pub entry_quota: Option<Quota>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Quota {
+    pub new_left: i32,
+    pub review_left: i32,
+    pub num_progressed: i32
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppConfig {
+    is_dark_mode: bool,
+    is_textfield: bool,
+    new_day_hour: i32,
+    deadline_cutoff_hour: i32
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProgressData {
+    curr_day: i32,
+    tot_days: i32
+}
+
+pub fn folder_system_is_empty(conn: &mut SqliteConnection) -> Result<bool> {
+    use crate::schema::folders;
+    let all_folders = folders::table
+        .select(folders::id)
+        .load::<i32>(conn)?;
+
+    Ok(all_folders.is_empty())
+
+}
+
+pub fn read_user_config(conn: &mut SqliteConnection) -> Result<AppConfig> { 
+    use crate::schema::userconfig;
+
+    let config = userconfig::table
+        .select((userconfig::is_dark_mode, userconfig::is_text_field, userconfig::new_day_hour, userconfig::deadline_cutoff_hour))
+        .load::<(bool, bool, i32, i32)>(conn)?;
+
+    if config.len() > 1 {
+        eprintln!("multiple rows in user config")
+    }
+
+    let (is_dark_mode, is_textfield, new_day_hour, deadline_cutoff_hour) = *config.first()
+        .ok_or_else(|| Error::IntegrityViolation(String::from("no rows in user config")))?;
+
+    Ok(AppConfig { 
+        is_dark_mode,
+        is_textfield,
+        new_day_hour,
+        deadline_cutoff_hour
+    })
+}
+
+pub fn write_dark_mode(conn: &mut SqliteConnection, is_dark_mode: bool) -> Result<()> { 
+    use crate::schema::userconfig;
+    
+    update(userconfig::table)
+        .set(userconfig::is_dark_mode.eq(is_dark_mode))
+        .execute(conn)?;
+    Ok(())
+}
+
+/**
+ * Sets the hour a study day starts at and the hour from which a deadline's
+ * own day is studied on; both are local hours 0-23. A cutoff before the
+ * start of the day falls on the next morning, see `days_until_deadline`.
+ * Days to go of AM-1 deadlines and ankibox due dates follow them from then on
+ */
+pub fn write_day_hours(conn: &mut SqliteConnection, new_day_hour: i32, deadline_cutoff_hour: i32) -> Result<()> { 
+    use crate::schema::userconfig;
+
+    if !(0..24).contains(&new_day_hour) || !(0..24).contains(&deadline_cutoff_hour) {
+        return Err(Error::InvalidInput(String::from("hours must be between 0 and 23")));
+    }
+
+    update(userconfig::table)
+        .set((userconfig::new_day_hour.eq(new_day_hour), userconfig::deadline_cutoff_hour.eq(deadline_cutoff_hour)))
+        .execute(conn)?;
+    Ok(())
+}
+
+
+pub fn read_folder_system(conn: &mut SqliteConnection) -> Result<FolderSystem> {
+    use crate::schema::{entries, parents};
+
+    // read parents
+    let all_parents = parents::table
+        .select((parents::parent_id, parents::child_id))
+        .load::<(i32, i32)>(conn)?;
+
+    let mut pairs = Vec::new();
+    for parent in all_parents {
+        pairs.push(EntryPair {parent_id: parent.0, child_id: parent.1});
+    }
+
+
+    let all_entries = entries::table
+        .select((entries::id, entries::name, entries::is_expanded))
+        .load::<(i32, String, Option<bool>)>(conn)?;
+
+    let mut data: Vec<EntryData> = Vec::new();
+    for entry in all_entries {
+        let entry_id = entry.0;
+        let entry_type = match get_entry_type(conn, entry_id) {
+            Err(Error::NotFound(_)) => {
+                return Err(Error::IntegrityViolation(format!("failed to find entry id {} \
+                in decks, deadlines, or folders", entry_id)));
+            },
+            type_result => type_result?
+        };
+
+        data.push(
+            EntryData {
+                entry_id,
+                entry_name: entry.1,
+                is_expanded: entry.2,
+                entry_type,
+                entry_quota: None // Some(Quota{new_left: 0, review_left:0, num_progressed: 0})
+            }
+        );
+    }
+
+    // write quotas into data in-place
+    let mut folder_system = FolderSystem { pairs, data };
+    folder_system = compute_quotas_(conn, folder_system)?;
+
+    Ok(folder_system)
+}
+
+
+fn compute_quotas_(conn: &mut SqliteConnection, mut folder_system: FolderSystem) -> Result<FolderSystem> {
+    use crate::schema::parents;
+
+    // first write the quotas of all of the decks
+    let mut deck_ids = Vec::new();
+    for entry in &mut folder_system.data {
+        let entry_type = get_entry_type(conn, entry.entry_id)?;
+        if entry_type == "deck" {
+
+            entry.entry_quota = get_deck_quota(conn, entry.entry_id)?;
+
+            deck_ids.push(entry.entry_id);
+
+        }
+    }
+
+    // now get quotas of deadlines as as sum of quotas of children 
+    
+    let deadline_ids = parents::table
+        .filter(parents::child_id.eq_any(deck_ids))
+        .select(parents::parent_id)
+        .load::<i32>(conn)?;
+
+    for deadline_id in deadline_ids {
+        let child_decks = parents::table
+            .filter(parents::parent_id.eq(deadline_id))
+            .select(parents::child_id)
+            .load::<i32>(conn)?;
+
+        let mut child_ids = Vec::new();
+        let mut child_quotas = Vec::new();
+        for entry in folder_system.data.iter().filter(|x| child_decks.contains(&x.entry_id)) {
+            if let Some(quota) = &entry.entry_quota {
+                child_ids.push(entry.entry_id);
+                child_quotas.push(quota.clone());
+            }
+        }
+
+        // limits of the deadline itself are shared by its decks
+        get_scheduler(conn, deadline_id)?.cap_deadline_quotas(conn, deadline_id, &child_ids, &mut child_quotas)?;
+        for entry in &mut folder_system.data {
+            if let Some(i) = child_ids.iter().position(|id| *id == entry.entry_id) {
+                entry.entry_quota = Some(child_quotas[i].clone());
+            }
+        }
+
+        // aggregate quotas
+        let mut new_left = 0; 
+        let mut review_left = 0; 
+        let mut num_progressed = 0;
+        for quota in &child_quotas {
+            new_left += quota.new_left;
+            review_left += quota.review_left;
+            num_progressed += quota.num_progressed;
+        }
+
+        for entry in &mut folder_system.data {
+            if entry.entry_id == deadline_id {
+                entry.entry_quota = Some(Quota { new_left, review_left, num_progressed });
+            }
+        }
+    }
+
+    Ok(folder_system)
+}
+
+
+
+/**
+ * Returns today's quota of a deck, as computed by its deadline's scheduler
+ */
+pub fn get_deck_quota(conn: &mut SqliteConnection, deck_id: i32) -> Result<Option<Quota>> {
+    get_deck_scheduler(conn, deck_id)?.deck_quota(conn, deck_id)
+}
+
+/**
+ * Given an id of an Entry, returns whether this id corresponds to a 
+ * folder, deadline, or deck.
+ * 
+ * Args:
+ *  conn: connection to diesel psql database
+ *  entry_id: id of entry to give a type to
+ *  
+ */
+pub fn get_entry_type(conn: &mut SqliteConnection, entry_id: i32) -> Result<String> {
+    use crate::schema::{folders, decks, deadlines};
+
+    let folder_id = folders::table
+        .select(folders::id)
+        .filter(folders::id.eq(entry_id))
+        .first::<i32>(conn);
+    if folder_id.is_ok() {
+        return Ok("folder".to_string());
+    }
+
+    let deadline_id = deadlines::table
+        .filter(deadlines::id.eq(entry_id))
+        .select(deadlines::id)
+        .first::<i32>(conn);
+    if let Ok(id) = deadline_id {
+        let (is_anki, is_fsrs) = deadlines::table
+        .filter(deadlines::id.eq(id))
+        .select((deadlines::is_anki, deadlines::is_fsrs))
+        .get_result::<(bool, bool)>(conn)?;
+        if is_fsrs {
+            return Ok("fsrsbox".to_string())
+        } else if is_anki {
+            return Ok("ankibox".to_string())
+        } else {
+            return Ok("deadline".to_string());
+        }
+    }
+
+    let deck_id = decks::table
+        .select(decks::id)
+        .filter(decks::id.eq(entry_id))
+        .first::<i32>(conn);
+    if deck_id.is_ok() {
+        return Ok("deck".to_string());
+    }
+
+    Err(Error::NotFound(format!("entry {}", entry_id)))
+}
+
+
+
+/** 
+ * Creates entry in database.
+ * 
+ * Args:
+ *  conn: connection to diesel psql database
+ *  entry_name: name of entry to be created
+ *  parent_id: id of parent of this entry if not root folder
+ *  entry_metadata: {entry_type: String, deadline_date: Option<String>, study_intensity: Option<String>, target_retention: Option<f32>}
+ *  
+ */
+pub fn create_entry(conn: &mut SqliteConnection, entry_name: &str, parent_id: Option<i32>, md: EntryMetadata) -> Result<()> {
+    use crate::schema::folders;
+
+    let entry_type = md.entry_type.as_str();
+    if !["folder", "deadline", "ankibox", "fsrsbox", "deck"].contains(&entry_type) {
+        return Err(Error::InvalidInput(format!("unknown entry type {}", entry_type)));
+    }
+
+    // a half-created entry would break `read_folder_system`
+    conn.transaction(|conn| {
+        let entry_id = insert_entry(conn, parent_id, entry_name, entry_type)?;
+
+        // insert into specialized relation `Folder`/`Deadline`/`Deck` using id
+        match entry_type {
+            "folder" => { insert_into(folders::table).values(folders::id.eq(entry_id)).execute(conn)?; },
+            "deadline" | "ankibox" | "fsrsbox" => insert_deadline(conn, entry_id, entry_type, md.deadline_date, md.study_intensity, md.target_retention)?,
+            _ => {
+                let parent_id = parent_id.ok_or_else(|| Error::InvalidInput(String::from("no parent to deck")))?;
+                insert_deck(conn, entry_id, parent_id)?
+            }
+        }
+        Ok(())
+    })
+
+}
+
+// returns entry_id
+fn insert_entry(conn: &mut SqliteConnection, parent_id: Option<i32>, entry_name: &str, entry_type: &str) -> Result<i32> {
+    use crate::schema::{entries, parents};
+
+    let is_expanded = if entry_type == "deck" { None } else { Some(true) };
+
+    insert_into(entries::table)
+        .values((entries::name.eq(entry_name), entries::is_expanded.eq(is_expanded)))
+        .execute(conn)?;
+
+    let entry_id = entries::table
+        .filter(entries::name.eq(entry_name))
+        .order(entries::id.desc())
+        .select(entries::id)
+        .first::<i32>(conn)?;
+
+    if let Some(pid) = parent_id {
+        insert_into(parents::table)
+            .values((parents::child_id.eq(entry_id), parents::parent_id.eq(pid)))
+            .execute(conn)?;
+    }
+
+    Ok(entry_id)
+}
+
+
+fn insert_deadline(
+    conn: &mut SqliteConnection, 
+    entry_id: i32, 
+    entry_type: &str, 
+    deadline_date: Option<String>, 
+    study_intensity: Option<i32>, 
+    target_retention: Option<f32>
+) -> Result<()> {
+    use crate::schema::deadlines;
+
+    // FSRS boxes are ankiboxes with a different scheduler
+    let is_fsrs = entry_type == "fsrsbox";
+    let is_anki = entry_type == "ankibox" || is_fsrs;
+
+    let target_retention = if is_fsrs {
+        Some(check_target_retention(target_retention.unwrap_or(DEFAULT_TARGET_RETENTION))?)
+    } else {
+        None
+    };
+
+    let (deadline_date, study_intensity, num_reset) = if !is_anki {
+        let deadline_date = deadline_date.ok_or_else(|| Error::InvalidInput(String::from("deadline has no date")))?;
+        let study_intensity = study_intensity.ok_or_else(|| Error::InvalidInput(String::from("deadline has no study intensity")))?;
+        (Some(string_to_chrono(&deadline_date)?.naive_utc()), Some(study_intensity), Some(0))
+    } else {
+        (None, None, None)
+    };
+    let deadline_tz = deadline_date.and(current_time_zone());
+    
+    insert_into(deadlines::table)
+        .values((
+            deadlines::id.eq(entry_id), 
+            deadlines::deadline_date.eq(deadline_date),
+            deadlines::deadline_tz.eq(deadline_tz),
+            deadlines::study_intensity.eq(study_intensity),
+            deadlines::num_reset.eq(num_reset),
+            deadlines::is_anki.eq(is_anki),
+            deadlines::is_fsrs.eq(is_fsrs),
+            deadlines::target_retention.eq(target_retention)
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/**
+ * Creates an empty deck named `deck_name` in a deadline or ankibox, returning its id
+ */
+pub fn create_deck(conn: &mut SqliteConnection, deadline_id: i32, deck_name: &str) -> Result<i32> {
+    let deck_id = insert_entry(conn, Some(deadline_id), deck_name, "deck")?;
+    insert_deck(conn, deck_id, deadline_id)?;
+    Ok(deck_id)
+}
+
+/**
+ * Returns the id of the deck named `deck_name` in a deadline or ankibox, if any
+ */
+pub fn find_deck(conn: &mut SqliteConnection, deadline_id: i32, deck_name: &str) -> Result<Option<i32>> {
+    use crate::schema::{entries, parents};
+
+    Ok(parents::table
+        .inner_join(entries::table.on(entries::id.eq(parents::child_id)))
+        .filter(parents::parent_id.eq(deadline_id).and(entries::name.eq(deck_name)))
+        .select(entries::id)
+        .first::<i32>(conn)
+        .optional()?)
+}
+
+fn insert_deck(conn: &mut SqliteConnection, deck_id: i32, deadline_id: i32) -> Result<()> {
+    use crate::schema::{decks, deadlines};
+
+    let is_anki = deadlines::table
+        .filter(deadlines::id.eq(deadline_id))
+        .select(deadlines::is_anki)
+        .get_result::<bool>(conn)
+        .context(&format!("deadline {}", deadline_id))?;
+
+    let (num_boxes, new_per_day) = if !is_anki { 
+        (Some(compute_num_boxes_from_id(conn, deadline_id)?), None)
+    } else {
+        (None, Some(5)) // new_per_day is 5 by default 
+    };
+
+    insert_into(decks::table)
+        .values((
+            decks::id.eq(deck_id),
+            decks::num_boxes.eq(num_boxes),
+            decks::new_per_day.eq(new_per_day)
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+// fn get_current_time() -> NaiveDateTime {
+//     let current_time = Local::now().naive_local();
+//     current_time
+// }
+
+/** 
+ * Deletes from the database a file system entry, its children, and all their contents
+ * 
+ * Args:
+ *  conn: connection to diesel psql database
+ *  entry_id: id of entry to be deleted
+ *  entry_type: type of entry to be deleted. is in "deadline", "folder", or "deck"
+ */
+pub fn delete_entry(conn: &mut SqliteConnection, entry_id: i32, media_dir: &Path) -> Result<()> {
+    use crate::schema::{cards, entries, parents};
+    // TODO: decrement quota
+
+    conn.transaction(|conn| {
+        // iteratively delete ids that descend from this id, keeping the media
+        // their cards referred to
+        let mut media_files = Vec::new();
+        let mut parents = vec![entry_id];
+        loop {
+            let children = parents::table
+                .filter(parents::parent_id.eq_any(parents))
+                .select(parents::child_id)
+                .get_results::<i32>(conn)?;
+
+            if children.is_empty() {
+                break;
+            }
+
+            let card_ids = cards::table
+                .filter(cards::deck_id.eq_any(&children))
+                .select(cards::id)
+                .get_results::<i32>(conn)?;
+            media_files.extend(card_media(conn, &card_ids)?);
+
+            // delete all descending entries; deletions cascade to parents and deck contents
+            delete(entries::table.filter(entries::id.eq_any(&children)))
+                .execute(conn)?;
+
+            parents = children;
+        }
+
+        // delete this entry; a deck has no children but cards of its own
+        let card_ids = cards::table
+            .filter(cards::deck_id.eq(entry_id))
+            .select(cards::id)
+            .get_results::<i32>(conn)?;
+        media_files.extend(card_media(conn, &card_ids)?);
+        delete(entries::table.filter(entries::id.eq(entry_id)))
+            .execute(conn)?;
+
+        remove_unreferenced_media(conn, media_dir, &media_files)?;
+        Ok(())
+    })
+}
+
+
+
+/** 
+ * Moves an entry to a new folder. Supported entry types are folder and deadline
+ * 
+ */
+pub fn move_entry(conn: &mut SqliteConnection, entry_id: i32, new_parent_id: i32) -> Result<()> {
+    use crate::schema::parents;
+
+    // update (parent_id, entry_id) in parents, setting parent_id to new_parent_id
+    let update_count = update(parents::table)
+        .filter(parents::child_id.eq(entry_id))
+        .set(parents::parent_id.eq(new_parent_id))
+        .execute(conn) ?;
+
+    if update_count == 0 {
+        return Err(Error::NotFound(format!("parent of entry {}", entry_id)));
+    }
+    if update_count != 1 {
+        eprintln!("Warning: moved multiple entries");
+    }
+    Ok(())
+}
+
+
+/** 
+ * Renames an entry `entry_id` to `new_name`
+ * 
+ */
+pub fn rename_entry(conn: &mut SqliteConnection, entry_id: i32, new_name: String) -> Result<()> {
+    use crate::schema::entries;
+
+    // update name attribute at entry_id from entries relation to new_name
+    let update_count = update(entries::table)
+        .filter(entries::id.eq(entry_id))
+        .set(entries::name.eq(new_name))
+        .execute(conn)?;
+
+    if update_count == 0 {
+        return Err(Error::NotFound(format!("entry {}", entry_id)));
+    }
+    if update_count != 1 {
+        eprintln!("Warning: renamed multiple entries");
+    }
+    Ok(())
+}
+
+
+// pub fn get_deck_quotas(data_dir: State<AppDataDirState>, deck_paths: Vec<String>
+//     ) -> Vec<EntryQuota> {
+
+// }
+
+pub fn is_duplicate_name(conn: &mut SqliteConnection, parent_id: Option<i32>, new_name: String) -> Result<bool> {
+    use crate::schema::{parents, entries};
+
+    // select children of `parent_id`
+    let Some(parent_id) = parent_id else {
+        return Ok(false);
+    };
+    let children = parents::table
+        .filter(parents::parent_id.eq(parent_id))
+        .select(parents::child_id)
+        .load::<i32>(conn)?;
+
+    // select those children with a name equal to `new_name`
+    let duplicates = entries::table
+        .filter(entries::id.eq_any(children).and(entries::name.eq(new_name)))
+        .select(entries::id)
+        .load::<i32>(conn)?;
+
+    Ok(!duplicates.is_empty())
+}
+
+
+
+/**
+ * Initializes root folder for first startup of app. Returns whether root folder was init
+ */
+pub fn init_root_folder(conn: &mut SqliteConnection) -> Result<bool> {
+    if !folder_system_is_empty(conn)? {
+        return Ok(false);
+    }
+    use crate::schema::{entries, folders, userconfig};
+
+    let entry_name = "My Trunk";
+    let is_expanded: Option<bool> = Some(true);
+
+    // create_entry(state, "My Trunk", None, md);
+    insert_into(entries::table)
+        .values((entries::name.eq(entry_name), entries::is_expanded.eq(is_expanded)))
+        .execute(conn)?;
+    let entry_id = entries::table
+        .filter(entries::name.eq(entry_name))
+        .order(entries::id.desc())
+        .select(entries::id)
+        .first::<i32>(conn)?;    
+
+    // insert into specialized relation `Folder`/`Deadline`/`Deck` using id
+    insert_into(folders::table)
+        .values(folders::id.eq(entry_id))
+        .execute(conn)?;
+
+
+    insert_into(userconfig::table)
+        .values((userconfig::is_dark_mode.eq(true), userconfig::is_text_field.eq(false)))
+        .execute(conn)?;
+
+    Ok(true)
+}
+
+
+pub fn init_getting_started(conn: &mut SqliteConnection) -> Result<()> {
+    use crate::schema::entries;
+
+    let parent_id = entries::table
+        .select(entries::id)
+        .get_result::<i32>(conn)?;
+
+    // in two days 
+    let now: DateTime<Local> = Local::now();
+    let next_week = now + Duration::days(1) + Duration::minutes(150);
+    let formatted_date = next_week.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    // insert starter deadline
+    let deadline_id = insert_entry(conn, Some(parent_id), "How to use Adam", "deadline")?;
+    insert_deadline(conn, deadline_id, "deadline", Some(formatted_date), Some(1), None)?;
+
+    // insert deck 1: create (folder -> deadline -> deck), edit (create cards), review (until deadline)
+    insert_starting_deck(conn, deadline_id, "1. Fundamentals")?;
+    
+    // insert deck 2: actions, textfield, prompt bar, reset deadline
+    insert_starting_deck(conn, deadline_id, "2. Advanced Features")?;
+
+    // insert deck 3: synthesis, rephrasing, explanations, instruction, upgrade, future
+    insert_starting_deck(conn, deadline_id, "3. AI Magic")?;
+
+    Ok(())
+}
+
+fn insert_starting_deck(conn: &mut SqliteConnection, deadline_id: i32, deck_name: &str) -> Result<()> {
+    let deck_id = create_deck(conn, deadline_id, deck_name)?;
+
+    let deck_contents: DeckNewContents = get_starting_deck_contents(deck_id, deck_name.to_string());
+    
+    let scheduler = get_scheduler(conn, deadline_id)?;
+    let state = scheduler.new_card_state(conn, deck_id)?;
+    let ids = insert_deck_contents(conn, deck_contents, &state)?;
+    scheduler.cards_created(conn, deck_id, ids.len() as i32)
+}
+
+fn get_starting_deck_contents(deck_id: i32, deck_name: String) -> DeckNewContents {
+    let cards: Vec<NewCard>;
+    if deck_name.starts_with("1") {
+        cards = vec![ 
+            NewCard { front: String::from("Adam's folder system hierarchy consists of three organizational levels: Folders (for organization), Deadlines (housing various Decks), and Decks (containing decks). How many entries in the folder hierarchy are needed to create a card?"), back: String::from("3 (one folder, one deadline, one deck)"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("Using the action tray on the home screen, you can create, rename, move, or delete entries in the folder system. What icon opens the action tray?"), back: String::from("the vertical ellipsis ⋮"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("Once you set a deadline and create a deck, you can make cards. How does Adam make sure you learn those cards by your deadline?"), back: String::from("Adam assigns card reviews each day up to your deadline using the AM-1 algorithm. This allows you to learn and remember your cards guaranteed, with the minimum time and effort possible"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+        ]
+    } else if deck_name.starts_with("2") {
+        cards = vec![
+            NewCard { front: String::from("What happens if you miss a day of reviews?"), back: String::from("Adam automatically adjusts card to spa"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("In addition to the standard Front/Back editor to create cards, Adam provides Textfield editor that create a card from each line with a double carrot like so: FRONT >> BACK. Why is this helpful?"), back: String::from("Allows you to create cards straight from your notes, saving time"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("Why does Adam prompt you to type out your answer to a card before revealing the back?"), back: String::from("The most effective way to study flashcards is to write your guess in your own words before revealing the card. It encourages active learning"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("Suppose you set a deadline for your midterm, and it has passed. How do you ensure you remember your cards for your final?"), back: String::from("reset the deadline on the home screen (a ⟳ button will appear on past deadlines to reset them)"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+        ]
+    } else { 
+        cards = vec![
+            NewCard { front: String::from("Adam is a free and open-source application. However, it provides powerful AI features, which you can access by getting an OpenAI API key. How much will the AI features cost you?"), back: String::from("Exactly as much as OpenAI costs (.3 cents per thousand words). Adam takes absolutely none of it"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("What four AI features does Adam offer to accelerate your learning?"), back: String::from("synthesizer (source → cards), rephraser (front + back → newFront + newBack), explainer (front + back → explanation), instruction (front + back + your answer → instruction)"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("Adam allows you to use the power of GPT to create cards. How do you use this feature?"), back: String::from("enter your notes or source text in the edit page; you can see created cards"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("With Adam, you can be certain to learn the concept rather than memorize the card. What AI feature enables this?"), back: String::from("Adam rephrases the card question every time using GPT"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+            NewCard { front: String::from("You don't have to worry about when and where to apply Adam's AI features. It's done for you behind the scenes. What are the only things you have to worry about?"), back: String::from("Coming with material to learn and returning to review your cards"), is_bidirectional: false, format: String::from(HTML_FORMAT) },
+
+        ];
+    }
+
+    DeckNewContents { deck_id, deck_name, cards }
+}
+
+
+
+
+/**
+ * Converts a local time in the format YYYY-MM-DD HH:MM:SS to the instant it
+ * stands for, with the offset in effect on that date
+ */
+fn string_to_chrono(datetime: &str) -> Result<DateTime<Local>> {
+    let format_str = "%Y-%m-%d %H:%M:%S";
+    let naive_date_time = NaiveDateTime::parse_from_str(datetime, format_str)
+        .map_err(|err| Error::InvalidInput(format!("cannot parse date {:?}: {}", datetime, err)))?;
+    Ok(utc_to_local(local_to_utc(naive_date_time)))
+    
+}
+
+pub fn entered_past_deadline(deadline: String) -> Result<bool> {
+    let datetime = string_to_chrono(&deadline)?;
+    Ok(datetime < Local::now())
+}
+
+
+// returns deadline date in MMM dd mm:ss format and whether it is complete
+pub fn get_deadline_date(conn: &mut SqliteConnection, deadline_id: i32) -> Result<Option<(String, bool)>> {
+    use crate::schema::deadlines;
+
+    let (deadline_date, deadline_tz, is_anki)  = deadlines::table
+        .filter(deadlines::id.eq(deadline_id))
+        .select((deadlines::deadline_date, deadlines::deadline_tz, deadlines::is_anki))
+        .get_result::<(Option<NaiveDateTime>, Option<String>, bool)>(conn)
+        .context(&format!("deadline {}", deadline_id))?;
+
+    if is_anki { 
+        return Ok(None); 
+    }
+
+    // deadline date is in UTC; it is shown at the time it was set for, in the
+    // zone it was set in, which is named when it is not the current one
+    let deadline_date = deadline_date
+        .ok_or_else(|| Error::IntegrityViolation(format!("deadline {} has no date", deadline_id)))?;
+    let mut formatted_date = in_deadline_zone(deadline_date, deadline_tz.as_deref()).format("%b %d %H:%M").to_string();
+    if let Some(deadline_tz) = deadline_tz.filter(|tz| Some(tz) != current_time_zone().as_ref()) {
+        formatted_date = format!("{} ({})", formatted_date, deadline_tz);
+    }
+
+    let is_complete = deadline_date < Utc::now().naive_utc();
+
+    Ok(Some((formatted_date, is_complete)))
+
+}
+
+pub fn compute_num_boxes_from_id(conn: &mut SqliteConnection, parent_id: i32) -> Result<i32> {
+    use crate::schema::deadlines;
+
+    let deadline_info = deadlines::table
+        .filter(deadlines::id.eq(parent_id))
+        .select((deadlines::study_intensity, deadlines::num_reset))
+        .get_result::<(Option<i32>, Option<i32>)>(conn)
+        .context(&format!("deadline {}", parent_id))?;
+
+
+    let (Some(study_intensity), Some(num_reset)) = deadline_info else {
+        return Err(Error::InvalidInput(format!("entry {} is not an AM-1 deadline", parent_id)));
+    };
+
+    let days_to_go = get_days_to_go(conn, parent_id)?;
+    get_num_boxes(days_to_go, study_intensity, num_reset)
+}
+
+
+pub fn reset_deadline(
+    conn: &mut SqliteConnection,
+    deadline_id: i32,
+    study_intensity: i32,
+    new_deadline_date: String
+) -> Result<()> {
+    use crate::schema::{quotas, parents, deadlines, cards};
+
+    // update deadline date and num_reset
+    let deadline = string_to_chrono(&new_deadline_date)?;
+
+    // quotas are rebuilt from scratch, so either all decks move to the new date or none
+    conn.transaction(|conn| {
+        update(deadlines::table)
+            .filter(deadlines::id.eq(deadline_id))
+            .set((
+                deadlines::num_reset.eq(deadlines::num_reset + 1), 
+                deadlines::study_intensity.eq(study_intensity), 
+                deadlines::deadline_date.eq(deadline.naive_utc()),
+                deadlines::deadline_tz.eq(current_time_zone())
+            ))
+            .execute(conn)?;
+
+        let deck_ids = parents::table
+            .filter(parents::parent_id.eq(deadline_id))
+            .select(parents::child_id)
+            .get_results::<i32>(conn)?;
+
+        // update deck num_boxes
+        for deck_id in deck_ids {
+            delete(quotas::table)
+                .filter(quotas::id.eq(deck_id))
+                .execute(conn)?;
+
+            let num_cards = cards::table
+                .filter(cards::deck_id.eq(deck_id))
+                .select(cards::id)
+                .get_results::<i32>(conn)?
+                .len() as i32;
+
+            write_quotas(conn, deadline_id, deck_id, num_cards)?;
+
+        }
+
+        Ok(())
+    })
+}
+
+
+pub fn toggle_is_expanded(conn: &mut SqliteConnection, entry_id: i32, is_expanded: bool) -> Result<()> {
+    use crate::schema::entries;
+
+    update(entries::table)
+        .filter(entries::id.eq(entry_id))
+        .set(entries::is_expanded.eq(is_expanded))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::{media_html, store_media};
+    use crate::tags::add_tag;
+    use crate::testing::{test_connection, root_id, create_test_entry, create_test_cards, temp_dir};
+
+    fn count_cards(conn: &mut SqliteConnection, deck_id: i32) -> i64 {
+        use crate::schema::cards;
+        cards::table.filter(cards::deck_id.eq(deck_id)).count().get_result(conn).unwrap()
+    }
+
+    #[test]
+    fn deleting_entries_removes_their_cards_and_media() {
+        use crate::schema::{card_tags, clozes, quotas};
+
+        let conn = &mut test_connection();
+        let media_dir = temp_dir("delete-entry");
+        let root_id = root_id(conn);
+        let deadline_id = create_test_entry(conn, "exam", root_id, "deadline", 10);
+        let deck_id = create_test_entry(conn, "deleted", deadline_id, "deck", 0);
+        let other_deck_id = create_test_entry(conn, "kept", deadline_id, "deck", 0);
+
+        let own = media_html(&store_media(conn, &media_dir, "own.png", b"own").unwrap());
+        let shared_file = store_media(conn, &media_dir, "shared.png", b"shared").unwrap();
+        let shared = media_html(&shared_file);
+        let card_ids = create_test_cards(conn, deadline_id, deck_id, &[(&own, "back"), ("front", &shared)]);
+        create_test_cards(conn, deadline_id, other_deck_id, &[(&shared, "back")]);
+        crate::cloze::create_cloze_cards(conn, deadline_id, deck_id, "{{c1::cloze}}").unwrap();
+        add_tag(conn, card_ids[0], "tagged").unwrap();
+
+        delete_entry(conn, deck_id, &media_dir).unwrap();
+        assert_eq!(count_cards(conn, deck_id), 0);
+        assert_eq!(count_cards(conn, other_deck_id), 1);
+        assert_eq!(card_tags::table.count().get_result::<i64>(conn).unwrap(), 0);
+        assert_eq!(clozes::table.count().get_result::<i64>(conn).unwrap(), 0);
+        assert_eq!(quotas::table.filter(quotas::id.eq(deck_id)).count().get_result::<i64>(conn).unwrap(), 0);
+        let mut files: Vec<String> = std::fs::read_dir(&media_dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec![shared_file.file_name]);
+
+        delete_entry(conn, deadline_id, &media_dir).unwrap();
+        assert_eq!(count_cards(conn, other_deck_id), 0);
+        assert_eq!(std::fs::read_dir(&media_dir).unwrap().count(), 0);
+        assert_eq!(quotas::table.count().get_result::<i64>(conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn cutoff_may_be_earlier_than_the_new_day_hour() {
+        use crate::utils::{read_day_hours, DayHours};
+
+        let conn = &mut test_connection();
+        write_day_hours(conn, 14, 6).unwrap();
+        assert_eq!(read_day_hours(conn).unwrap(), DayHours { new_day_hour: 14, deadline_cutoff_hour: 6 });
+        assert!(matches!(write_day_hours(conn, 24, 6), Err(Error::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,68 @@
+//! Headless core of Adam: the folder system, AM-1 quotas, SM-2 ankiboxes, FSRS
+//! boxes and review sessions, exposed as plain functions over a `SqliteConnection`.
+//!
+//! The Tauri commands in `app` are thin wrappers around this crate, so any
+//! other binary (or test) can drive the same engine without a webview.
+
+#![allow(clippy::needless_range_loop)]
+
+use std::error::Error;
+
+use diesel::connection::SimpleConnection;
+use diesel::Connection;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+pub use diesel::sqlite::SqliteConnection;
+
+pub mod db;
+pub mod error;
+pub mod schema;
+pub mod models;
+pub mod utils;
+pub mod scheduler;
+pub mod am1;
+pub mod anki;
+pub mod fsrs;
+pub mod home;
+pub mod edit;
+pub mod cloze;
+pub mod answer;
+pub mod review;
+pub mod review_log;
+pub mod apkg;
+pub mod csv_import;
+pub mod textfield;
+pub mod tags;
+pub mod backup;
+pub mod media;
+pub mod render;
+
+#[cfg(test)]
+mod testing;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("../../migrations");
+
+
+pub fn establish_connection(database_url: &str) -> SqliteConnection {
+    let mut conn = SqliteConnection::establish(database_url)
+        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url));
+
+    // SQLite leaves foreign keys off on every new connection; deleting an entry
+    // relies on their `ON DELETE CASCADE` to remove its decks' cards, clozes,
+    // tags, quotas and review logs
+    conn.batch_execute("PRAGMA foreign_keys = ON")
+        .unwrap_or_else(|_| panic!("Error enabling foreign keys on {}", database_url));
+    conn
+}
+
+
+pub fn run_migrations(connection: &mut impl MigrationHarness<diesel::sqlite::Sqlite>) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+
+    // This will run the necessary migrations.
+    //
+    // See the documentation for `MigrationHarness` for
+    // all available methods.
+    connection.run_pending_migrations(MIGRATIONS)?;
+
+    Ok(())
+}
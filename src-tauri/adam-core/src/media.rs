@@ -0,0 +1,273 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+use chrono::{Duration, Utc};
+use diesel::{insert_into, delete, update};
+use diesel::prelude::*;
+
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::models::Card;
+use crate::render::render_card;
+use crate::error::{Error, Result, Context};
+
+
+/**
+ * Prefix of media references in card HTML, e.g. `<img src="adam-media:<file_name>">`
+ */
+pub const MEDIA_SCHEME: &str = "adam-media:";
+
+// extensions accepted as media, with their MIME types
+const MEDIA_TYPES: [(&str, &str); 11] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("webm", "audio/webm"),
+];
+
+// the editor stores pasted media before its card is saved, so recent files
+// are not garbage yet
+const GRACE_PERIOD_HOURS: i64 = 24;
+
+
+/**
+ * A file in the media store
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MediaFile {
+    pub file_name: String,
+    pub mime_type: String
+}
+
+
+/**
+ * Stores `data` under the SHA-1 of its contents, keeping the extension of
+ * `original_name`, and returns the stored file. Storing the same contents
+ * twice returns the file stored the first time
+ */
+pub fn store_media(conn: &mut SqliteConnection, media_dir: &Path, original_name: &str, data: &[u8]) -> Result<MediaFile> {
+    use crate::schema::media;
+
+    let extension = Path::new(original_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mime_type = mime_type(&extension)
+        .ok_or_else(|| Error::InvalidInput(format!("{} is not an image or audio file", original_name)))?;
+
+    let file_name = format!("{}.{}", sha1_smol::Sha1::from(data).digest(), extension);
+
+    fs::create_dir_all(media_dir)?;
+    let path = media_dir.join(&file_name);
+    if !path.is_file() {
+        fs::write(&path, data)?;
+    }
+
+    insert_into(media::table)
+        .values((
+            media::file_name.eq(&file_name),
+            media::mime_type.eq(mime_type),
+            media::byte_size.eq(data.len() as i32),
+            media::created_at.eq(Utc::now().naive_utc())
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(MediaFile { file_name, mime_type: String::from(mime_type) })
+}
+
+
+/**
+ * Stores the image or audio file at `path` and appends it to the `side`
+ * ("front" or "back") of a card, returning the updated card
+ */
+pub fn attach_media(conn: &mut SqliteConnection, media_dir: &Path, card_id: i32, side: &str, path: &Path) -> Result<Card> {
+    use crate::schema::cards;
+
+    if side != "front" && side != "back" {
+        return Err(Error::InvalidInput(format!("unknown card side {}", side)));
+    }
+
+    let (mut front, mut back, format) = cards::table
+        .filter(cards::id.eq(card_id))
+        .select((cards::front, cards::back, cards::format))
+        .get_result::<(String, String, String)>(conn)
+        .context(&format!("card {}", card_id))?;
+
+    let data = fs::read(path)?;
+    let file = store_media(conn, media_dir, &path.to_string_lossy(), &data)?;
+
+    if side == "front" {
+        front.push_str(&media_html(&file));
+    } else {
+        back.push_str(&media_html(&file));
+    }
+
+    update(cards::table)
+        .filter(cards::id.eq(card_id))
+        .set((cards::front.eq(&front), cards::back.eq(&back)))
+        .execute(conn)?;
+
+    Ok(render_card(card_id, front, back, format))
+}
+
+
+/**
+ * HTML showing a stored file on a card
+ */
+pub fn media_html(file: &MediaFile) -> String {
+    if file.mime_type.starts_with("audio/") {
+        format!("<audio controls src=\"{}{}\"></audio>", MEDIA_SCHEME, file.file_name)
+    } else {
+        format!("<img src=\"{}{}\">", MEDIA_SCHEME, file.file_name)
+    }
+}
+
+
+/**
+ * File names of the media referenced in card HTML, in order of appearance
+ */
+pub fn media_references(html: &str) -> Vec<String> {
+    html.match_indices(MEDIA_SCHEME)
+        .filter_map(|(idx, _)| {
+            let rest = &html[idx + MEDIA_SCHEME.len()..];
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '.')).unwrap_or(rest.len());
+            let file_name = &rest[..end];
+            is_media_file_name(file_name).then(|| file_name.to_string())
+        })
+        .collect()
+}
+
+
+/**
+ * Reads a stored file, returning its contents and MIME type
+ */
+pub fn read_media(media_dir: &Path, file_name: &str) -> Result<(Vec<u8>, String)> {
+    // file names come from the webview, so nothing but `<sha1>.<ext>` may reach the file system
+    if !is_media_file_name(file_name) {
+        return Err(Error::InvalidInput(format!("{} is not a media file name", file_name)));
+    }
+    let mime_type = file_name.rsplit('.').next()
+        .and_then(mime_type)
+        .ok_or_else(|| Error::InvalidInput(format!("{} is not an image or audio file", file_name)))?;
+
+    let data = fs::read(media_dir.join(file_name)).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => Error::NotFound(format!("media file {}", file_name)),
+        _ => Error::from(err)
+    })?;
+    Ok((data, String::from(mime_type)))
+}
+
+
+/**
+ * Removes stored files no card refers to any more, returning how many were
+ * removed. Cards of decks whose entry was deleted do not count as references
+ */
+pub fn collect_garbage(conn: &mut SqliteConnection, media_dir: &Path) -> Result<usize> {
+    use crate::schema::{cards, entries, media};
+
+    let contents = cards::table
+        .filter(cards::deck_id.eq_any(entries::table.select(entries::id)))
+        .select((cards::front, cards::back))
+        .load::<(String, String)>(conn)?;
+    let referenced: HashSet<String> = contents.iter()
+        .flat_map(|(front, back)| media_references(front).into_iter().chain(media_references(back)))
+        .collect();
+
+    let cutoff = Utc::now().naive_utc() - Duration::hours(GRACE_PERIOD_HOURS);
+    let orphans: Vec<String> = media::table
+        .filter(media::created_at.lt(cutoff))
+        .select(media::file_name)
+        .load::<String>(conn)?
+        .into_iter()
+        .filter(|file_name| !referenced.contains(file_name))
+        .collect();
+
+    for file_name in &orphans {
+        remove_media_file(conn, media_dir, file_name)?;
+    }
+
+    Ok(orphans.len())
+}
+
+
+/**
+ * File names of the media the cards `card_ids` refer to
+ */
+pub fn card_media(conn: &mut SqliteConnection, card_ids: &[i32]) -> Result<Vec<String>> {
+    use crate::schema::cards;
+
+    let contents = cards::table
+        .filter(cards::id.eq_any(card_ids))
+        .select((cards::front, cards::back))
+        .load::<(String, String)>(conn)?;
+    Ok(contents.iter()
+        .flat_map(|(front, back)| media_references(front).into_iter().chain(media_references(back)))
+        .collect())
+}
+
+
+/**
+ * Removes those of `file_names` no card refers to any more, returning how many
+ * were removed. Deleting cards calls it with the media they referred to, so
+ * their files go at once rather than after the grace period
+ */
+pub fn remove_unreferenced_media(conn: &mut SqliteConnection, media_dir: &Path, file_names: &[String]) -> Result<usize> {
+    use crate::schema::{cards, entries};
+
+    let mut num_removed = 0;
+    for file_name in file_names.iter().collect::<BTreeSet<_>>() {
+        // media file names are hex digits and an extension, so hold no LIKE wildcards
+        let pattern = format!("%{}{}%", MEDIA_SCHEME, file_name);
+        let num_references = cards::table
+            .filter(cards::deck_id.eq_any(entries::table.select(entries::id)))
+            .filter(cards::front.like(&pattern).or(cards::back.like(&pattern)))
+            .count()
+            .get_result::<i64>(conn)?;
+        if num_references == 0 {
+            remove_media_file(conn, media_dir, file_name)?;
+            num_removed += 1;
+        }
+    }
+    Ok(num_removed)
+}
+
+fn remove_media_file(conn: &mut SqliteConnection, media_dir: &Path, file_name: &str) -> Result<()> {
+    use crate::schema::media;
+
+    match fs::remove_file(media_dir.join(file_name)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    delete(media::table.filter(media::file_name.eq(file_name)))
+        .execute(conn)?;
+    Ok(())
+}
+
+
+fn mime_type(extension: &str) -> Option<&'static str> {
+    MEDIA_TYPES.iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime_type)| *mime_type)
+}
+
+// `<40 hex digits>.<known extension>`
+fn is_media_file_name(file_name: &str) -> bool {
+    match file_name.split_once('.') {
+        Some((hash, extension)) => hash.len() == 40
+            && hash.chars().all(|c| c.is_ascii_hexdigit())
+            && mime_type(extension).is_some(),
+        None => false
+    }
+}
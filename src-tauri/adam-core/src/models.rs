@@ -0,0 +1,160 @@
+
+use diesel::prelude::*;
+use serde::{
+    Serialize, 
+    Deserialize
+};
+use crate::schema::{quotas, reviewlog};
+use crate::render::default_format;
+
+
+#[derive(Deserialize, Serialize, Insertable, AsChangeset, Debug)]
+#[diesel(table_name = quotas)]
+pub struct QuotaRecord {
+    pub days_to_go: i32,
+    pub new_assigned: i32,
+    pub review_assigned: i32,
+    pub new_quota_initial: i32,
+    pub review_quota_initial: i32,
+    pub new_practiced: i32,
+    pub review_practiced: i32,
+}
+
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Card {
+    pub id: i32,
+    pub front: String,
+    pub back: String,
+    // "html" or "markdown", see `render`
+    #[serde(default = "default_format")]
+    pub format: String,
+    // `front` and `back` rendered by `render::render_card`; ignored on updates
+    #[serde(default)]
+    pub front_html: String,
+    #[serde(default)]
+    pub back_html: String
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NewCard {
+    pub front: String,
+    pub back: String,
+    // also create a reverse card asking for the front, see `edit::insert_deck_contents`
+    #[serde(default)]
+    pub is_bidirectional: bool,
+    #[serde(default = "default_format")]
+    pub format: String
+}
+
+
+#[derive(Deserialize, Serialize, Queryable, Debug)]
+pub struct ReviewLog {
+    pub id: i32,
+    pub card_id: i32,
+    pub deck_id: i32,
+    pub reviewed_at: chrono::NaiveDateTime,
+    pub score: i32,
+    pub user_answer: String,
+    pub stack_before: String,
+    pub stack_after: String,
+    pub box_before: Option<i32>,
+    pub box_after: Option<i32>,
+    pub repetitions_before: Option<i32>,
+    pub repetitions_after: Option<i32>,
+    pub easiness_before: Option<f32>,
+    pub easiness_after: Option<f32>,
+    pub interval_before: Option<i32>,
+    pub interval_after: Option<i32>,
+    pub next_practice_before: Option<chrono::NaiveDate>,
+    pub next_practice_after: Option<chrono::NaiveDate>,
+    pub elapsed_ms: Option<i32>,
+    pub stability_before: Option<f32>,
+    pub stability_after: Option<f32>,
+    pub difficulty_before: Option<f32>,
+    pub difficulty_after: Option<f32>,
+    pub last_review_before: Option<chrono::NaiveDate>,
+    pub last_review_after: Option<chrono::NaiveDate>,
+    pub learning_step_before: Option<i32>,
+    pub learning_step_after: Option<i32>,
+    pub due_at_before: Option<chrono::NaiveDateTime>,
+    pub due_at_after: Option<chrono::NaiveDateTime>,
+    pub lapses_before: Option<i32>,
+    pub lapses_after: Option<i32>,
+    // when the answer was taken back in its review session; None while it stands
+    pub undone_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = reviewlog)]
+pub struct NewReviewLog {
+    pub card_id: i32,
+    pub deck_id: i32,
+    pub reviewed_at: chrono::NaiveDateTime,
+    pub score: i32,
+    pub user_answer: String,
+    pub stack_before: String,
+    pub stack_after: String,
+    pub box_before: Option<i32>,
+    pub box_after: Option<i32>,
+    pub repetitions_before: Option<i32>,
+    pub repetitions_after: Option<i32>,
+    pub easiness_before: Option<f32>,
+    pub easiness_after: Option<f32>,
+    pub interval_before: Option<i32>,
+    pub interval_after: Option<i32>,
+    pub next_practice_before: Option<chrono::NaiveDate>,
+    pub next_practice_after: Option<chrono::NaiveDate>,
+    pub elapsed_ms: Option<i32>,
+    pub stability_before: Option<f32>,
+    pub stability_after: Option<f32>,
+    pub difficulty_before: Option<f32>,
+    pub difficulty_after: Option<f32>,
+    pub last_review_before: Option<chrono::NaiveDate>,
+    pub last_review_after: Option<chrono::NaiveDate>,
+    pub learning_step_before: Option<i32>,
+    pub learning_step_after: Option<i32>,
+    pub due_at_before: Option<chrono::NaiveDateTime>,
+    pub due_at_after: Option<chrono::NaiveDateTime>,
+    pub lapses_before: Option<i32>,
+    pub lapses_after: Option<i32>,
+}
+
+
+
+
+
+#[derive(Queryable)]
+pub struct Deadline {
+    pub id: i32,
+    pub date_created: chrono::NaiveDateTime,
+    pub deadline_date: chrono::NaiveDateTime,
+    pub study_intensity: Option<i32>,
+    pub num_reset: i32,
+}
+
+#[derive(Queryable, PartialEq)]
+pub struct Deck {
+    pub id: i32,
+    pub date_created: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Debug)]
+pub struct Entry {
+    pub id: i32,
+    pub name: String,
+    pub is_expanded: Option<bool>,
+}
+
+
+#[derive(Queryable)]
+pub struct Folder {
+    pub id: i32,
+}
+
+
+#[derive(Queryable)]
+pub struct Parent {
+    pub parent_id: i32,
+    pub child_id: i32,
+}
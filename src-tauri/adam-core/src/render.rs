@@ -0,0 +1,272 @@
+use std::sync::OnceLock;
+
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+use crate::media::MEDIA_SCHEME;
+use crate::models::Card;
+use crate::error::{Error, Result};
+
+
+/**
+ * Card content as the editor writes it
+ */
+pub const HTML_FORMAT: &str = "html";
+
+/**
+ * Card content written as Markdown, with fenced code and `$...$` math
+ */
+pub const MARKDOWN_FORMAT: &str = "markdown";
+
+// theme of highlighted code; its colours are inlined so exports look the same
+const CODE_THEME: &str = "InspiredGitHub";
+
+// stands in for a highlighted code block until the rest is sanitized
+const CODE_PLACEHOLDER: char = '\u{FFFC}';
+
+
+// serde default of content formats, for cards written before formats existed
+pub(crate) fn default_format() -> String {
+    String::from(HTML_FORMAT)
+}
+
+
+/**
+ * Fails unless `format` is a known content format
+ */
+pub fn check_format(format: &str) -> Result<()> {
+    match format {
+        HTML_FORMAT | MARKDOWN_FORMAT => Ok(()),
+        _ => Err(Error::InvalidInput(format!("unknown card format {}", format)))
+    }
+}
+
+
+/**
+ * Renders card content to HTML that is safe to show: Markdown is converted
+ * first, then scripts, event handlers and unknown URL schemes are removed.
+ * Code blocks are highlighted and math is left as `<span class="math ...">`
+ * for the frontend to typeset
+ */
+pub fn render_html(content: &str, format: &str) -> String {
+    match format {
+        MARKDOWN_FORMAT => render_markdown(content),
+        _ => sanitize(content)
+    }
+}
+
+
+/**
+ * A card with its sides rendered, see `render_html`
+ */
+pub fn render_card(id: i32, front: String, back: String, format: String) -> Card {
+    Card {
+        id,
+        front_html: render_html(&front, &format),
+        back_html: render_html(&back, &format),
+        front,
+        back,
+        format
+    }
+}
+
+
+/**
+ * Plain text of rendered card HTML, for clients that cannot show HTML. Blocks
+ * start on a new line, `<br>` breaks one, media show as `[image: <file>]` or
+ * `[audio: <file>]` and entities are decoded
+ */
+pub fn html_to_plain_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut tag = String::new();
+    let mut in_tag = false;
+    // newlines between blocks are markup, but not inside code
+    let mut in_pre = 0;
+    for c in html.chars() {
+        match c {
+            '<' if !in_tag => {
+                in_tag = true;
+                tag.clear();
+            },
+            '>' if in_tag => {
+                in_tag = false;
+                let name = tag.trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next().unwrap_or("")
+                    .to_lowercase();
+                if name == "pre" {
+                    in_pre += if tag.starts_with('/') { -1 } else { 1 };
+                }
+                match name.as_str() {
+                    "img" | "audio" if !tag.starts_with('/') => {
+                        let kind = if name == "img" { "image" } else { "audio" };
+                        let src = attribute(&tag, "src").unwrap_or_default();
+                        text.push_str(&format!("[{}: {}]", kind, decode_entities(src.trim_start_matches(MEDIA_SCHEME))));
+                    },
+                    "br" => text.push('\n'),
+                    "div" | "p" | "li" | "tr" | "pre" | "blockquote" | "ul" | "ol" | "table"
+                        | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if !text.is_empty() && !text.ends_with('\n') => text.push('\n'),
+                    "td" | "th" => text.push(' '),
+                    _ => {}
+                }
+            },
+            c if in_tag => tag.push(c),
+            '\n' if in_pre <= 0 && (text.is_empty() || text.ends_with('\n')) => {},
+            c => text.push(c)
+        }
+    }
+
+    let decoded = decode_entities(&text);
+    let lines: Vec<&str> = decoded.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_start_matches('\n').trim_end().to_string()
+}
+
+// value of `name` in the inside of a tag, quoted or not
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=", name);
+    let start = tag.match_indices(&pattern)
+        .find(|(idx, _)| tag[..*idx].ends_with(char::is_whitespace))
+        .map(|(idx, _)| idx + pattern.len())?;
+    let rest = &tag[start..];
+    match rest.chars().next()? {
+        quote @ ('"' | '\'') => rest[1..].split(quote).next(),
+        _ => rest.split(char::is_whitespace).next()
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        decoded.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let entity = rest.find(';').filter(|end| *end <= 10).map(|end| &rest[1..end]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity.strip_prefix("#x").or(entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or(entity.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32)
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+
+fn render_markdown(source: &str) -> String {
+    let source = source.replace(CODE_PLACEHOLDER, "");
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_MATH;
+
+    // code blocks are highlighted apart from the rest and put back in after
+    // sanitizing, which would strip their inline styles
+    let mut code_blocks: Vec<String> = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    let mut events: Vec<Event> = Vec::new();
+    for event in Parser::new_ext(&source, options) {
+        match (&mut code, event) {
+            (None, Event::Start(Tag::CodeBlock(kind))) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new()
+                };
+                code = Some((language, String::new()));
+            },
+            (Some((_, text)), Event::Text(chunk)) => text.push_str(&chunk),
+            (Some((language, text)), Event::End(TagEnd::CodeBlock)) => {
+                events.push(Event::Html(format!("<div>{}{}{}</div>", CODE_PLACEHOLDER, code_blocks.len(), CODE_PLACEHOLDER).into()));
+                code_blocks.push(highlight(text, language));
+                code = None;
+            },
+            (Some(_), _) => {},
+            (None, event) => events.push(event)
+        }
+    }
+
+    let mut markdown_html = String::new();
+    html::push_html(&mut markdown_html, events.into_iter());
+
+    let mut rendered = sanitize(&markdown_html);
+    for (i, block) in code_blocks.iter().enumerate() {
+        rendered = rendered.replacen(&format!("<div>{}{}{}</div>", CODE_PLACEHOLDER, i, CODE_PLACEHOLDER), block, 1);
+    }
+    rendered
+}
+
+// `code` as a `<pre>` with inline colours; unknown languages stay plain
+fn highlight(code: &str, language: &str) -> String {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let themes = THEMES.get_or_init(ThemeSet::load_defaults);
+    let syntax = syntaxes.find_syntax_by_token(language)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+
+    highlighted_html_for_string(code, syntaxes, syntax, &themes.themes[CODE_THEME])
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", escape(code)))
+}
+
+fn sanitize(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tags(["audio"])
+        .add_tag_attributes("audio", ["controls", "src"])
+        .add_url_schemes([MEDIA_SCHEME.trim_end_matches(':')])
+        .add_allowed_classes("span", ["cloze", "math", "math-inline", "math-display"])
+        .clean(html)
+        .to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_breaks_blocks_and_decodes_entities() {
+        let html = render_html("<p>Tom &amp; Jerry</p><ul><li>a &lt; b</li><li>c&nbsp;&#233;</li></ul>", HTML_FORMAT);
+        assert_eq!(html_to_plain_text(&html), "Tom & Jerry\na < b\nc \u{e9}");
+    }
+
+    #[test]
+    fn plain_text_names_media() {
+        let html = render_html("<div>Look <img src=\"adam-media:cat.png\"></div><audio controls src=\"adam-media:meow.mp3\"></audio>", HTML_FORMAT);
+        assert_eq!(html_to_plain_text(&html), "Look [image: cat.png]\n[audio: meow.mp3]");
+    }
+
+    #[test]
+    fn plain_text_of_markdown_and_cloze() {
+        let html = render_html("# Title\n\nSome **bold** text", MARKDOWN_FORMAT);
+        assert_eq!(html_to_plain_text(&html), "Title\nSome bold text");
+
+        let html = render_html("```rust\nfn main() {\n    println!();\n}\n```", MARKDOWN_FORMAT);
+        assert_eq!(html_to_plain_text(&html), "fn main() {\n    println!();\n}");
+
+        let front = render_html("The <span class=\"cloze\">[...]</span> is red", HTML_FORMAT);
+        assert_eq!(html_to_plain_text(&front), "The [...] is red");
+    }
+}
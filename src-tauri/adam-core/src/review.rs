@@ -0,0 +1,863 @@
+
+// #![allow(unused_imports)]
+// #![allow(unused_variables)]
+// #![allow(dead_code)]
+
+use diesel::prelude::*;
+
+use crate::home::Quota;
+use crate::models::{Card, NewReviewLog, ReviewLog};
+use crate::render::{render_card, render_html};
+use crate::review_log::{read_card_state, write_card_state, write_review_log, mark_review_log_undone};
+use crate::error::{Error, Result, Context};
+
+use chrono::{Duration, Local, NaiveTime, Utc};
+use std::time::Instant;
+
+use serde::{
+    Serialize, 
+    Deserialize
+};
+use rand::Rng;
+
+
+use crate::scheduler::{Scheduler, get_scheduler};
+use crate::answer::{self, AnswerCheck};
+use crate::tags::matching_card_ids;
+use crate::utils::{get_is_anki, local_to_utc, read_day_hours, study_today};
+
+
+// once nothing else is due, learning cards due this soon are shown early
+const LEARN_AHEAD_MINUTES: i64 = 20;
+
+#[derive(Clone)]
+pub struct UserResponse {
+    pub card_id: i32,
+    pub box_pos_delta: Option<i32>,
+    pub user_answer: String,
+    pub stack_after: Option<String>,
+    pub stack_before: String,
+    pub deck_id: i32,
+    // row of this answer in `reviewlog`
+    pub log_id: Option<i32>,
+    // the logged answer, kept to re-apply it when redoing
+    pub log: Option<NewReviewLog>
+}
+
+/**
+ * In-memory state of one review session over a deadline or ankibox
+ */
+#[derive(Default)]
+pub struct ReviewSession {
+    pub response_stack: Vec<UserResponse>,
+    pub undo_response_stack: Vec<UserResponse>,
+    pub curr_card: Option<UserResponse>,
+    pub new_ids: Vec<i32>,
+    pub deadline_id: Option<i32>,
+    // cards matching the session's tag expression; None studies every card
+    pub tag_card_ids: Option<Vec<i32>>,
+    // scheduler of the deadline, kept so the session stays on the day it started
+    pub scheduler: Option<Box<dyn Scheduler>>,
+    // when the card being studied was shown, for `reviewlog::elapsed_ms`
+    pub card_shown_at: Option<Instant>
+}
+
+
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewCard {
+    pub stack_before: String,
+    pub deck_name: String,
+    pub card: Card
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CardResults {
+    pub stack_after: Option<String>,
+    pub user_answer: String,
+    pub card: ReviewCard
+}
+
+
+
+/**
+ * Starts a review session over a deadline or ankibox, returning what is
+ * left to study today. With `tag_expression`, only cards whose tags match
+ * it are studied, see `tags::matching_card_ids`
+ */
+pub fn init_review_session(
+    conn: &mut SqliteConnection, 
+    session: &mut ReviewSession, 
+    deadline_id: i32,
+    tag_expression: Option<&str>) -> Result<Quota> 
+{ 
+    // record deadline id
+    session.deadline_id = Some(deadline_id);
+    session.new_ids.clear();
+    restore_review_history(conn, session, deadline_id)?;
+    session.tag_card_ids = match tag_expression.filter(|expression| !expression.trim().is_empty()) {
+        Some(expression) => Some(matching_card_ids(conn, deadline_id, expression)?),
+        None => None
+    };
+
+    let scheduler = get_scheduler(conn, deadline_id)?;
+    let deck_ids = get_deck_ids(conn, deadline_id)?;
+    let mut quotas = get_deck_quotas(conn, scheduler.as_ref(), deadline_id, &deck_ids)?;
+
+    // select which new cards to memorize today
+    for i in 0..quotas.len() {
+        if quotas[i].new_left == 0 {
+            continue;
+        }
+
+        let new_ids_deck = match &session.tag_card_ids {
+            // the day's new cards are picked among the matching ones only
+            Some(tag_card_ids) => {
+                let mut new_ids_deck = scheduler.select_new_cards(conn, deck_ids[i], i32::MAX)?;
+                new_ids_deck.retain(|id| tag_card_ids.contains(id));
+                new_ids_deck.truncate(quotas[i].new_left.max(0) as usize);
+                new_ids_deck
+            },
+            None => scheduler.select_new_cards(conn, deck_ids[i], quotas[i].new_left)?
+        };
+        session.new_ids.extend_from_slice(&new_ids_deck);
+    }
+
+    if let Some(tag_card_ids) = &session.tag_card_ids {
+        cap_tag_quotas(conn, scheduler.as_ref(), &deck_ids, &mut quotas, tag_card_ids, &session.new_ids)?;
+    }
+
+    session.scheduler = Some(scheduler);
+
+    Ok(get_deadline_summed_quota(quotas))
+
+
+}
+
+
+
+
+
+/**
+ * Seeds the session's history with today's answers to cards of `deadline_id`
+ * that were not taken back, oldest first, so `get_last_card` can take back
+ * answers given before the app was restarted. Nothing is left to redo, as
+ * the next card drawn would start a new history anyway
+ */
+pub fn restore_review_history(conn: &mut SqliteConnection, session: &mut ReviewSession, deadline_id: i32) -> Result<()> {
+    use crate::schema::reviewlog;
+
+    let deck_ids = get_deck_ids(conn, deadline_id)?;
+    let hours = read_day_hours(conn)?;
+    let day_start = local_to_utc(study_today(conn)?.and_time(NaiveTime::MIN) + Duration::hours(i64::from(hours.new_day_hour)));
+
+    let logs = reviewlog::table
+        .filter(reviewlog::deck_id.eq_any(&deck_ids))
+        .filter(reviewlog::undone_at.is_null().and(reviewlog::reviewed_at.ge(day_start)))
+        .order(reviewlog::id.asc())
+        .load::<ReviewLog>(conn)?;
+
+    session.response_stack = logs.into_iter()
+        .map(|log| UserResponse {
+            card_id: log.card_id,
+            box_pos_delta: match (log.box_before, log.box_after) {
+                (Some(box_before), Some(box_after)) => Some(box_after - box_before),
+                _ => None
+            },
+            user_answer: log.user_answer.clone(),
+            stack_after: Some(log.stack_after.clone()),
+            stack_before: log.stack_before.clone(),
+            deck_id: log.deck_id,
+            log_id: Some(log.id),
+            log: Some(NewReviewLog::from(log))
+        })
+        .collect();
+    session.undo_response_stack.clear();
+    Ok(())
+}
+
+
+fn get_deck_ids(conn: &mut SqliteConnection, deadline_id: i32) -> Result<Vec<i32>> {
+    use crate::schema::parents;
+    Ok(parents::table
+        .filter(parents::parent_id.eq(deadline_id))
+        .select(parents::child_id)
+        .get_results::<i32>(conn)?)
+}
+
+// decks without a quota for today, e.g. empty decks, have nothing left to review
+fn get_deck_quotas(conn: &mut SqliteConnection, scheduler: &dyn Scheduler, deadline_id: i32, deck_ids: &[i32]) -> Result<Vec<Quota>> {
+    let mut quotas = Vec::new();
+    for deck_id in deck_ids {
+        let quota = scheduler.deck_quota(conn, *deck_id)?
+            .unwrap_or(Quota { new_left: 0, review_left: 0, num_progressed: 0 });
+        quotas.push(quota);
+    }
+    scheduler.cap_deadline_quotas(conn, deadline_id, deck_ids, &mut quotas)?;
+    Ok(quotas)
+}
+
+// a tag-filtered session studies no more cards of a deck than match: its new
+// cards are the selected ones not yet learned, its reviews the other matching cards
+fn cap_tag_quotas(
+    conn: &mut SqliteConnection,
+    scheduler: &dyn Scheduler,
+    deck_ids: &[i32],
+    quotas: &mut [Quota],
+    tag_card_ids: &[i32],
+    new_ids: &[i32]) -> Result<()>
+{
+    use crate::schema::cards;
+
+    for (deck_id, quota) in deck_ids.iter().zip(quotas.iter_mut()) {
+        let still_new = scheduler.select_new_cards(conn, *deck_id, i32::MAX)?;
+        let new_left = still_new.iter()
+            .filter(|id| new_ids.contains(id))
+            .count() as i32;
+        let matching = cards::table
+            .filter(cards::deck_id.eq(deck_id).and(cards::id.eq_any(tag_card_ids)))
+            .select(cards::id)
+            .get_results::<i32>(conn)?;
+        let review_left = matching.iter()
+            .filter(|id| !still_new.contains(id))
+            .count() as i32;
+
+        quota.new_left = quota.new_left.min(new_left);
+        quota.review_left = quota.review_left.min(review_left);
+    }
+    Ok(())
+}
+
+// deadline of the running review session
+fn get_session_deadline(session: &ReviewSession) -> Result<i32> {
+    session.deadline_id
+        .ok_or_else(|| Error::InvalidInput(String::from("no review session in progress")))
+}
+
+// scheduler of the running review session
+fn get_session_scheduler(scheduler: &Option<Box<dyn Scheduler>>) -> Result<&dyn Scheduler> {
+    scheduler.as_deref()
+        .ok_or_else(|| Error::InvalidInput(String::from("no review session in progress")))
+}
+
+fn get_deadline_summed_quota(quotas: Vec<Quota>) -> Quota {
+
+    let mut summed_quota = Quota { new_left: 0, review_left: 0, num_progressed: 0 };
+    for quota in quotas {
+        summed_quota.new_left += quota.new_left;
+        summed_quota.review_left += quota.review_left;
+        summed_quota.num_progressed += quota.num_progressed;
+    }
+
+    summed_quota
+
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CardInfo {
+    pub deck_id: String,
+    pub front: String,
+    pub repetitions: i32,
+    pub interval: i32
+}
+
+// (id, repetitions, interval, front) of a card
+pub type CardSummary = (i32, Option<i32>, Option<i32>, String);
+
+pub fn print_cards(conn: &mut SqliteConnection, deadline_id: i32) -> Result<Vec<CardSummary>> {
+    use crate::schema::cards;
+    
+    let deck_ids = get_deck_ids(conn, deadline_id)?;
+
+    let mut cards = Vec::new();
+    for deck_id in deck_ids {
+        let info = cards::table
+            .filter(cards::deck_id.eq(deck_id))
+            .select(cards::interval)
+            .select((cards::id, cards::repetitions, cards::interval, cards::front))
+            .get_results::<(i32, Option<i32>, Option<i32>, String)>(conn)?;
+         
+        cards.extend(info);
+    }
+
+    Ok(cards)
+}
+
+pub fn get_next_card(conn: &mut SqliteConnection, session: &mut ReviewSession) -> Result<Option<ReviewCard>> { 
+
+    let deadline_id = get_session_deadline(session)?;
+    let scheduler = get_session_scheduler(&session.scheduler)?;
+    let new_ids = &session.new_ids;
+    let tag_card_ids = session.tag_card_ids.as_deref();
+
+    // get deck ids and quotas
+    let deck_ids = get_deck_ids(conn, deadline_id)?;
+    let mut quotas = get_deck_quotas(conn, scheduler, deadline_id, &deck_ids)?;
+    if let Some(tag_card_ids) = tag_card_ids {
+        cap_tag_quotas(conn, scheduler, &deck_ids, &mut quotas, tag_card_ids, new_ids)?;
+    }
+
+    // a deck may run out of cards before its quota, e.g. when few of its
+    // cards match the session's tags; it is then skipped for the other decks
+    let (deck_id, popped_card) = loop {
+        // determine if drawing new card; None if no more cards in quota and review session done
+        let Some(is_new) = is_drawing_new(&quotas) else {
+            match pop_learn_ahead_card(conn, &deck_ids, tag_card_ids)? {
+                Some(learn_ahead) => break learn_ahead,
+                None => return Ok(None)
+            }
+        };
+
+        // choose deck
+        let deck_idx = choose_deck(&quotas, is_new)?;
+        let deck_id = deck_ids[deck_idx];
+
+        // choose card
+        let popped_card = if is_new {
+            pop_new_card(conn, new_ids, deck_id)?
+        } else {
+            scheduler.pop_review_card(conn, deck_id, tag_card_ids)?
+        };
+
+        match popped_card {
+            Some(popped_card) => break (deck_id, popped_card),
+            None if is_new => quotas[deck_idx].new_left = 0,
+            None => quotas[deck_idx].review_left = 0
+        }
+    };
+
+    // save current card for getLastCard and undoGetLastCard
+    session.curr_card = Some(UserResponse {
+        card_id: popped_card.card.id,
+        box_pos_delta: None,
+        user_answer: String::from(""),
+        stack_before: popped_card.stack_before.clone(),
+        stack_after: None,
+        deck_id,
+        log_id: None,
+        log: None
+    });
+    session.card_shown_at = Some(Instant::now());
+    // cards taken back and not answered again are dropped from the redo stack
+    session.undo_response_stack.clear();
+
+    Ok(Some(popped_card))
+
+ }
+
+fn pop_new_card(conn: &mut SqliteConnection, new_ids: &Vec<i32>, deck_id: i32) -> Result<Option<ReviewCard>> { 
+    use crate::schema::{cards, entries};
+    use diesel::prelude::*;
+
+    // get the first card in the chosen deck whose id is in new_ids, skipping
+    // cards waiting for their next learning step
+    let now = Utc::now().naive_utc();
+    let Some(new_card) = cards::table
+        .filter(cards::id.eq_any(new_ids).and(cards::deck_id.eq(deck_id)))
+        .filter(cards::due_at.is_null().or(cards::due_at.le(now)))
+        .select((cards::id, cards::front, cards::back, cards::format))
+        .order((cards::due_at.is_null(), cards::due_at.asc(), cards::queue_score.asc()))
+        // .order(cards::queue_score.asc().nulls_first()) // nulls_first means nulls come first with ascending order
+        .first::<(i32, String, String, String)>(conn)
+        .optional()? else {
+        return Ok(None);
+    };
+
+    let deck_name = entries::table
+        .filter(entries::id.eq(deck_id))
+        .select(entries::name)
+        .get_result::<String>(conn)
+        .context(&format!("deck {}", deck_id))?;
+
+
+    Ok(Some(ReviewCard {
+        deck_name,
+        stack_before: String::from("new"),
+        card: render_card(new_card.0, new_card.1, new_card.2, new_card.3)
+    }))
+
+    
+
+}
+
+// the (re)learning card due first in `deck_ids` if it is due within
+// LEARN_AHEAD_MINUTES, with its deck
+fn pop_learn_ahead_card(
+    conn: &mut SqliteConnection,
+    deck_ids: &[i32],
+    only: Option<&[i32]>
+) -> Result<Option<(i32, ReviewCard)>> {
+    use crate::schema::cards;
+
+    let learn_ahead = Utc::now().naive_utc() + Duration::minutes(LEARN_AHEAD_MINUTES);
+    let mut query = cards::table
+        .filter(cards::deck_id.eq_any(deck_ids))
+        .filter(cards::due_at.le(learn_ahead))
+        .order(cards::due_at.asc())
+        .select((cards::id, cards::deck_id, cards::repetitions))
+        .into_boxed();
+    if let Some(only) = only {
+        query = query.filter(cards::id.eq_any(only));
+    }
+    let Some((card_id, deck_id, repetitions)) = query.first::<(i32, i32, Option<i32>)>(conn).optional()? else {
+        return Ok(None);
+    };
+
+    let stack_before = if repetitions.unwrap_or(0) > 0 { "review" } else { "new" };
+    Ok(Some((deck_id, read_review_card(conn, card_id, deck_id, stack_before)?)))
+}
+
+// returns `is_new` if there are cards to review; otherwise None if finished session
+fn is_drawing_new(quotas_state: &[Quota]) -> Option<bool> {
+
+    let num_progressed = quotas_state.iter()
+        .fold(0, |acc, x| acc + x.num_progressed);
+    let in_new_interval = num_progressed % 15 < 5;
+    let new_exists = quotas_state.iter()
+        .fold(0, |acc, x| acc + x.new_left) > 0;
+    let review_exists = quotas_state.iter()
+        .fold(0, |acc, x| acc + x.review_left) > 0;
+    
+    // completed review session
+    if !new_exists && !review_exists {
+        return None;
+    }
+
+    let is_new = (in_new_interval && new_exists) || !review_exists;
+    Some(is_new)
+
+}
+
+// chooses deck to sample from
+fn choose_deck(quotas: &[Quota], is_new: bool) -> Result<usize> {
+
+    // initial deck_idx is sampled 
+    let mut range = rand::thread_rng();
+
+    let mut deck_idx;
+
+    // sample from a different deck if chosen deck has no new/review card quota
+    let mut counter = 0;
+    loop {
+        // repeatedly sample until we get a valid card
+        deck_idx = range.gen_range(0..quotas.len()) as usize;
+
+        if quotas[deck_idx].new_left > 0 && is_new || quotas[deck_idx].review_left > 0 && !is_new {
+            break;
+        }
+
+        counter += 1;
+        if counter >= 10000 {
+            return Err(Error::SchedulingError(String::from("no deck has cards left to draw")));
+        }
+    }
+
+    Ok(deck_idx)
+}
+
+
+// returns stack_after; score is -1, 0, or 1 for AM-1 deadlines and 1-5 otherwise
+pub fn record_response(
+    conn: &mut SqliteConnection, 
+    session: &mut ReviewSession,
+    score: i32, 
+    user_answer: String, 
+    card: ReviewCard
+) -> Result<String> {
+    use crate::schema::cards;
+
+    let scheduler = get_session_scheduler(&session.scheduler)?;
+    let elapsed_ms = session.card_shown_at
+        .and_then(|shown_at| i32::try_from(shown_at.elapsed().as_millis()).ok());
+
+    let deck_id = cards::table
+        .filter(cards::id.eq(card.card.id))
+        .select(cards::deck_id)
+        .get_result::<i32>(conn)
+        .context(&format!("card {}", card.card.id))?;
+
+    // the answer and its log row are written together or not at all
+    let (stack_after, box_pos_delta, log_id, log) = conn.transaction(|conn| {
+        let before = read_card_state(conn, card.card.id)?;
+
+        let stack_after = scheduler.grade(conn, &card, score)?;
+
+        let after = read_card_state(conn, card.card.id)?;
+        let box_pos_delta = match (before.box_position, after.box_position) {
+            (Some(box_before), Some(box_after)) => Some(box_after - box_before),
+            _ => None
+        };
+
+        let log = NewReviewLog {
+            card_id: card.card.id,
+            deck_id,
+            reviewed_at: Utc::now().naive_utc(),
+            score,
+            user_answer: user_answer.clone(),
+            stack_before: card.stack_before.clone(),
+            stack_after: stack_after.clone(),
+            box_before: before.box_position,
+            box_after: after.box_position,
+            repetitions_before: before.repetitions,
+            repetitions_after: after.repetitions,
+            easiness_before: before.easiness,
+            easiness_after: after.easiness,
+            interval_before: before.interval,
+            interval_after: after.interval,
+            next_practice_before: before.next_practice,
+            next_practice_after: after.next_practice,
+            elapsed_ms,
+            stability_before: before.stability,
+            stability_after: after.stability,
+            difficulty_before: before.difficulty,
+            difficulty_after: after.difficulty,
+            last_review_before: before.last_review,
+            last_review_after: after.last_review,
+            learning_step_before: before.learning_step,
+            learning_step_after: after.learning_step,
+            due_at_before: before.due_at,
+            due_at_after: after.due_at,
+            lapses_before: before.lapses,
+            lapses_after: after.lapses
+        };
+        let log_id = write_review_log(conn, &log)?;
+
+        Ok::<_, Error>((stack_after, box_pos_delta, log_id, log))
+    })?;
+
+    // return stack after
+    session.curr_card = None;
+    session.card_shown_at = None;
+    let response = UserResponse {
+        card_id: card.card.id,
+        user_answer,
+        stack_before: card.stack_before.clone(),
+        stack_after: Some(stack_after.clone()),
+        box_pos_delta,
+        deck_id,
+        log_id: Some(log_id),
+        log: Some(log)
+    };
+    session.response_stack.push(response);
+    // answering a card taken back starts a new history
+    session.undo_response_stack.clear();
+
+    Ok(stack_after)
+    
+}
+
+
+/**
+ * Compares a typed answer to the back of the card under review and suggests
+ * a score on the scale `record_response` takes for the session's deadline.
+ * Returns None when nothing was typed
+ */
+pub fn check_answer(conn: &mut SqliteConnection, session: &ReviewSession, card: &ReviewCard, user_answer: &str) -> Result<Option<AnswerCheck>> {
+    let deadline_id = session.deadline_id
+        .ok_or_else(|| Error::InvalidInput(String::from("no review session in progress")))?;
+    let is_anki = get_is_anki(conn, deadline_id)?;
+    let back = render_html(&card.card.back, &card.card.format);
+    Ok(answer::check_answer(&back, user_answer, is_anki))
+}
+
+
+// returns queue score (epoch time in seconds plus or minus 15 minutes)
+pub fn get_queue_score() -> Option<i32> {
+    let dt = Local::now().timestamp();
+    let mut range = rand::thread_rng();
+    let noise = range.gen_range(-30..30); // +-30 secs
+    let queue_score = dt + noise;
+    Some(queue_score as i32)
+}
+
+
+// returns previous card; String indicates stack_after
+pub fn get_last_card(conn: &mut SqliteConnection, session: &mut ReviewSession) -> Result<Option<CardResults>> { 
+    let scheduler = get_session_scheduler(&session.scheduler)?;
+
+    let Some(curr) = session.curr_card.clone() else {
+        return Ok(None);
+    };
+    let Some(response) = session.response_stack.pop() else {
+        return Ok(None);
+    };
+    let Some(log) = &response.log else {
+        let card_id = response.card_id;
+        session.response_stack.push(response);
+        return Err(Error::IntegrityViolation(format!("answer to card {} was not logged", card_id)));
+    };
+
+    // put the card back in the state it was in before it was answered
+    let review_card = conn.transaction(|conn| {
+        write_card_state(conn, response.card_id, &log.state_before())?;
+        scheduler.shift_quota(conn, log, -1)?;
+
+        // the answer is taken back; its row stays, marked, for a redo or a restart
+        if let Some(log_id) = response.log_id {
+            mark_review_log_undone(conn, log_id, true)?;
+        }
+
+        read_review_card(conn, response.card_id, response.deck_id, &response.stack_before)
+    });
+    let review_card = match review_card {
+        Ok(review_card) => review_card,
+        Err(err) => {
+            session.response_stack.push(response);
+            return Err(err);
+        }
+    };
+
+    let card_results = CardResults {
+        stack_after: response.stack_after.clone(),
+        user_answer: response.user_answer.clone(),
+        card: review_card
+    };
+
+    session.undo_response_stack.push(curr);
+    session.curr_card = Some(response);
+    session.card_shown_at = Some(Instant::now());
+    Ok(Some(card_results))
+
+ }
+
+
+/**
+ * Undoes `get_last_card`: answers the card taken back the same way it was
+ * answered before and returns the card that was shown after it. Returns None 
+ * if there is nothing to redo
+ */
+pub fn undo_get_last_card(conn: &mut SqliteConnection, session: &mut ReviewSession) -> Result<Option<CardResults>> { 
+    let scheduler = get_session_scheduler(&session.scheduler)?;
+
+    let Some(next) = session.undo_response_stack.pop() else {
+        return Ok(None);
+    };
+    let Some(mut curr) = session.curr_card.clone() else {
+        session.undo_response_stack.push(next);
+        return Ok(None);
+    };
+    let (Some(log_id), Some(log)) = (curr.log_id, curr.log.take()) else {
+        session.undo_response_stack.push(next);
+        return Err(Error::IntegrityViolation(format!("answer to card {} was not logged", curr.card_id)));
+    };
+
+    // re-apply the answer to the card taken back, then show the card after it
+    let redone = conn.transaction(|conn| {
+        write_card_state(conn, curr.card_id, &log.state_after())?;
+        scheduler.shift_quota(conn, &log, 1)?;
+        mark_review_log_undone(conn, log_id, false)?;
+
+        read_review_card(conn, next.card_id, next.deck_id, &next.stack_before)
+    });
+    let review_card = match redone {
+        Ok(redone) => redone,
+        Err(err) => {
+            session.undo_response_stack.push(next);
+            return Err(err);
+        }
+    };
+
+    let card_results = CardResults {
+        stack_after: curr.stack_after.clone(),
+        user_answer: next.user_answer.clone(),
+        card: review_card
+    };
+
+    curr.log = Some(log);
+    session.response_stack.push(curr);
+    session.curr_card = Some(next);
+    session.card_shown_at = Some(Instant::now());
+    Ok(Some(card_results))
+}
+
+
+fn read_review_card(conn: &mut SqliteConnection, card_id: i32, deck_id: i32, stack_before: &str) -> Result<ReviewCard> {
+    use crate::schema::{cards, entries};
+
+    let deck_name = entries::table
+        .filter(entries::id.eq(deck_id))
+        .select(entries::name)
+        .get_result::<String>(conn)
+        .context(&format!("deck {}", deck_id))?;
+
+    let card = cards::table
+        .filter(cards::id.eq(card_id))
+        .select((cards::id, cards::front, cards::back, cards::format))
+        .get_result::<(i32, String, String, String)>(conn)
+        .context(&format!("card {}", card_id))?;
+
+    Ok(ReviewCard { 
+        stack_before: String::from(stack_before), 
+        deck_name, 
+        card: render_card(card.0, card.1, card.2, card.3)
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use crate::edit::get_days_to_go;
+    use crate::review_log::read_card_review_log;
+    use crate::testing::{create_test_cards, create_test_entry, root_id, test_connection};
+
+    // (id, undone_at) of the rows logged for a card, oldest first
+    fn log_rows(conn: &mut SqliteConnection, card_id: i32) -> Vec<(i32, Option<NaiveDateTime>)> {
+        use crate::schema::reviewlog;
+
+        reviewlog::table
+            .filter(reviewlog::card_id.eq(card_id))
+            .order(reviewlog::id.asc())
+            .select((reviewlog::id, reviewlog::undone_at))
+            .load(conn)
+            .unwrap()
+    }
+
+    // a session over `deadline_id` as the app starts one, with a card drawn
+    fn start_session(conn: &mut SqliteConnection, deadline_id: i32) -> (ReviewSession, ReviewCard) {
+        let mut session = ReviewSession::default();
+        init_review_session(conn, &mut session, deadline_id, None).unwrap();
+        let card = get_next_card(conn, &mut session).unwrap().unwrap();
+        (session, card)
+    }
+
+    // (new_practiced, review_practiced) of a deck in today's AM-1 quota
+    fn am1_practiced(conn: &mut SqliteConnection, deadline_id: i32, deck_id: i32) -> (i32, i32) {
+        use crate::schema::quotas;
+
+        let days_to_go = get_days_to_go(conn, deadline_id).unwrap();
+        quotas::table
+            .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(days_to_go)))
+            .select((quotas::new_practiced, quotas::review_practiced))
+            .get_result(conn)
+            .unwrap()
+    }
+
+    // (new_practiced, review_practiced) of a deck in today's ankiquotas row
+    fn anki_practiced(conn: &mut SqliteConnection, deck_id: i32) -> (i32, i32) {
+        use crate::schema::ankiquotas;
+
+        let today = study_today(conn).unwrap();
+        ankiquotas::table
+            .filter(ankiquotas::deck_id.eq(deck_id).and(ankiquotas::date_practiced.eq(today)))
+            .select((ankiquotas::new_practiced, ankiquotas::review_practiced))
+            .get_result(conn)
+            .unwrap()
+    }
+
+    // answers the first new card of `deadline_id` with `score`, takes the
+    // answer back and redoes it, checking the card and `practiced` counters
+    // after each step; returns the counters after the answer
+    fn answer_undo_redo(
+        conn: &mut SqliteConnection,
+        deadline_id: i32,
+        score: i32,
+        practiced: impl Fn(&mut SqliteConnection) -> (i32, i32)
+    ) -> (i32, i32) {
+        let (mut session, card) = start_session(conn, deadline_id);
+        let card_id = card.card.id;
+        assert_eq!(card.stack_before, "new");
+        let state_before = read_card_state(conn, card_id).unwrap();
+        assert_eq!(practiced(conn), (0, 0));
+
+        let stack_after = record_response(conn, &mut session, score, String::new(), card).unwrap();
+        assert_eq!(stack_after, "done");
+        let state_after = read_card_state(conn, card_id).unwrap();
+        assert_ne!(state_after, state_before);
+        let practiced_after = practiced(conn);
+        assert!(practiced_after.0 > 0);
+
+        // the next card is shown before going back
+        get_next_card(conn, &mut session).unwrap().unwrap();
+        let results = get_last_card(conn, &mut session).unwrap().unwrap();
+        assert_eq!(results.card.card.id, card_id);
+        assert_eq!(read_card_state(conn, card_id).unwrap(), state_before);
+        assert_eq!(practiced(conn), (0, 0));
+
+        let results = undo_get_last_card(conn, &mut session).unwrap().unwrap();
+        assert_ne!(results.card.card.id, card_id);
+        assert_eq!(read_card_state(conn, card_id).unwrap(), state_after);
+        assert_eq!(practiced(conn), practiced_after);
+        practiced_after
+    }
+
+    #[test]
+    fn am1_answers_are_undone_and_redone() {
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let deadline_id = create_test_entry(conn, "exam", root, "deadline", 10);
+        let deck_id = create_test_entry(conn, "deck", deadline_id, "deck", 0);
+        create_test_cards(conn, deadline_id, deck_id, &[("one", "1"), ("two", "2")]);
+
+        // a known card moves up a box, which is one progression of the new quota
+        let practiced = answer_undo_redo(conn, deadline_id, 1, |conn| am1_practiced(conn, deadline_id, deck_id));
+        assert_eq!(practiced, (1, 0));
+    }
+
+    #[test]
+    fn sm2_answers_are_undone_and_redone() {
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let box_id = create_test_entry(conn, "box", root, "ankibox", 0);
+        let deck_id = create_test_entry(conn, "deck", box_id, "deck", 0);
+        create_test_cards(conn, box_id, deck_id, &[("one", "1"), ("two", "2")]);
+
+        // easy skips the learning steps, so the card is done for today
+        let practiced = answer_undo_redo(conn, box_id, 5, |conn| anki_practiced(conn, deck_id));
+        assert_eq!(practiced, (1, 0));
+    }
+
+    #[test]
+    fn fsrs_answers_are_undone_and_redone() {
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let box_id = create_test_entry(conn, "box", root, "fsrsbox", 0);
+        let deck_id = create_test_entry(conn, "deck", box_id, "deck", 0);
+        create_test_cards(conn, box_id, deck_id, &[("one", "1"), ("two", "2")]);
+
+        let practiced = answer_undo_redo(conn, box_id, 5, |conn| anki_practiced(conn, deck_id));
+        assert_eq!(practiced, (1, 0));
+    }
+
+    #[test]
+    fn answers_can_be_taken_back_after_a_restart() {
+        let conn = &mut test_connection();
+        let root = root_id(conn);
+        let deadline_id = create_test_entry(conn, "exam", root, "deadline", 10);
+        let deck_id = create_test_entry(conn, "deck", deadline_id, "deck", 0);
+        create_test_cards(conn, deadline_id, deck_id, &[("one", "1"), ("two", "2"), ("three", "3")]);
+
+        let (mut session, card) = start_session(conn, deadline_id);
+        let card_id = card.card.id;
+        let before = read_card_state(conn, card_id).unwrap();
+        record_response(conn, &mut session, 1, String::from("1"), card).unwrap();
+        let after = read_card_state(conn, card_id).unwrap();
+        assert_ne!(before, after);
+
+        // a session started anew, as after a restart, can take the answer back
+        let (mut session, _) = start_session(conn, deadline_id);
+        assert_eq!(session.response_stack.len(), 1);
+        let results = get_last_card(conn, &mut session).unwrap().unwrap();
+        assert_eq!(results.card.card.id, card_id);
+        assert_eq!(results.user_answer, "1");
+        assert_eq!(read_card_state(conn, card_id).unwrap(), before);
+        let rows = log_rows(conn, card_id);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].1.is_some());
+        assert!(read_card_review_log(conn, card_id).unwrap().is_empty());
+
+        // redoing gives the answer its row back
+        undo_get_last_card(conn, &mut session).unwrap().unwrap();
+        assert_eq!(read_card_state(conn, card_id).unwrap(), after);
+        assert_eq!(log_rows(conn, card_id), vec![(rows[0].0, None)]);
+        assert_eq!(read_card_review_log(conn, card_id).unwrap().len(), 1);
+
+        // an answer taken back before a restart stays taken back
+        get_last_card(conn, &mut session).unwrap().unwrap();
+        let (session, _) = start_session(conn, deadline_id);
+        assert!(session.response_stack.is_empty());
+        assert_eq!(read_card_state(conn, card_id).unwrap(), before);
+        assert_eq!(log_rows(conn, card_id).len(), 1);
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+
+use diesel::{insert_into, update};
+use diesel::prelude::*;
+
+use crate::models::{ReviewLog, NewReviewLog};
+use crate::error::Result;
+
+
+/**
+ * Scheduling state of a card, recorded in `reviewlog` before and after each
+ * answer. AM-1 cards only have a box position, ankibox cards SM-2 state and 
+ * FSRS box cards FSRS state. `due_at` is the UTC time a (re)learning card
+ * becomes due again
+ */
+#[derive(Queryable, Clone, Copy, Debug, PartialEq)]
+pub struct CardState {
+    pub box_position: Option<i32>,
+    pub repetitions: Option<i32>,
+    pub easiness: Option<f32>,
+    pub interval: Option<i32>,
+    pub next_practice: Option<NaiveDate>,
+    pub stability: Option<f32>,
+    pub difficulty: Option<f32>,
+    pub last_review: Option<NaiveDate>,
+    pub learning_step: Option<i32>,
+    pub due_at: Option<NaiveDateTime>,
+    pub lapses: Option<i32>
+}
+
+
+pub fn read_card_state(conn: &mut SqliteConnection, card_id: i32) -> Result<CardState> {
+    use crate::schema::cards;
+    use crate::error::Context;
+
+    let state = cards::table
+        .filter(cards::id.eq(card_id))
+        .select((
+            cards::box_position, cards::repetitions, cards::easiness, cards::interval, cards::next_practice,
+            cards::stability, cards::difficulty, cards::last_review,
+            cards::learning_step, cards::due_at, cards::lapses
+        ))
+        .get_result::<CardState>(conn)
+        .context(&format!("card {}", card_id))?;
+
+    Ok(state)
+}
+
+
+pub fn write_card_state(conn: &mut SqliteConnection, card_id: i32, state: &CardState) -> Result<()> {
+    use crate::schema::cards;
+
+    update(cards::table)
+        .filter(cards::id.eq(card_id))
+        .set((
+            cards::box_position.eq(state.box_position),
+            cards::repetitions.eq(state.repetitions),
+            cards::easiness.eq(state.easiness),
+            cards::interval.eq(state.interval),
+            cards::next_practice.eq(state.next_practice),
+            cards::stability.eq(state.stability),
+            cards::difficulty.eq(state.difficulty),
+            cards::last_review.eq(state.last_review),
+            cards::learning_step.eq(state.learning_step),
+            cards::due_at.eq(state.due_at),
+            cards::lapses.eq(state.lapses)
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+impl NewReviewLog {
+    pub fn state_before(&self) -> CardState {
+        CardState {
+            box_position: self.box_before,
+            repetitions: self.repetitions_before,
+            easiness: self.easiness_before,
+            interval: self.interval_before,
+            next_practice: self.next_practice_before,
+            stability: self.stability_before,
+            difficulty: self.difficulty_before,
+            last_review: self.last_review_before,
+            learning_step: self.learning_step_before,
+            due_at: self.due_at_before,
+            lapses: self.lapses_before
+        }
+    }
+
+    pub fn state_after(&self) -> CardState {
+        CardState {
+            box_position: self.box_after,
+            repetitions: self.repetitions_after,
+            easiness: self.easiness_after,
+            interval: self.interval_after,
+            next_practice: self.next_practice_after,
+            stability: self.stability_after,
+            difficulty: self.difficulty_after,
+            last_review: self.last_review_after,
+            learning_step: self.learning_step_after,
+            due_at: self.due_at_after,
+            lapses: self.lapses_after
+        }
+    }
+}
+
+
+impl From<ReviewLog> for NewReviewLog {
+    fn from(log: ReviewLog) -> Self {
+        NewReviewLog {
+            card_id: log.card_id,
+            deck_id: log.deck_id,
+            reviewed_at: log.reviewed_at,
+            score: log.score,
+            user_answer: log.user_answer,
+            stack_before: log.stack_before,
+            stack_after: log.stack_after,
+            box_before: log.box_before,
+            box_after: log.box_after,
+            repetitions_before: log.repetitions_before,
+            repetitions_after: log.repetitions_after,
+            easiness_before: log.easiness_before,
+            easiness_after: log.easiness_after,
+            interval_before: log.interval_before,
+            interval_after: log.interval_after,
+            next_practice_before: log.next_practice_before,
+            next_practice_after: log.next_practice_after,
+            elapsed_ms: log.elapsed_ms,
+            stability_before: log.stability_before,
+            stability_after: log.stability_after,
+            difficulty_before: log.difficulty_before,
+            difficulty_after: log.difficulty_after,
+            last_review_before: log.last_review_before,
+            last_review_after: log.last_review_after,
+            learning_step_before: log.learning_step_before,
+            learning_step_after: log.learning_step_after,
+            due_at_before: log.due_at_before,
+            due_at_after: log.due_at_after,
+            lapses_before: log.lapses_before,
+            lapses_after: log.lapses_after
+        }
+    }
+}
+
+
+// returns id of the written row
+pub fn write_review_log(conn: &mut SqliteConnection, log: &NewReviewLog) -> Result<i32> {
+    use crate::schema::reviewlog;
+
+    insert_into(reviewlog::table)
+        .values(log)
+        .execute(conn)?;
+
+    let log_id = reviewlog::table
+        .filter(reviewlog::card_id.eq(log.card_id))
+        .select(reviewlog::id)
+        .order(reviewlog::id.desc())
+        .first::<i32>(conn)?;
+
+    Ok(log_id)
+}
+
+
+/**
+ * Marks an answer as taken back in a review session, or as given again when
+ * `undone` is false. The row is kept either way, so the session's history can
+ * be read back after a restart
+ */
+pub fn mark_review_log_undone(conn: &mut SqliteConnection, log_id: i32, undone: bool) -> Result<()> {
+    use crate::schema::reviewlog;
+
+    let undone_at = undone.then(|| Utc::now().naive_utc());
+    update(reviewlog::table.filter(reviewlog::id.eq(log_id)))
+        .set(reviewlog::undone_at.eq(undone_at))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+// returns every answer given to `card_id` and not taken back, oldest first
+pub fn read_card_review_log(conn: &mut SqliteConnection, card_id: i32) -> Result<Vec<ReviewLog>> {
+    use crate::schema::reviewlog;
+
+    Ok(reviewlog::table
+        .filter(reviewlog::card_id.eq(card_id).and(reviewlog::undone_at.is_null()))
+        .order(reviewlog::id.asc())
+        .load::<ReviewLog>(conn)?)
+}
+
+
+// returns every answer given to cards of `deck_id` and not taken back, oldest first
+pub fn read_deck_review_log(conn: &mut SqliteConnection, deck_id: i32) -> Result<Vec<ReviewLog>> {
+    use crate::schema::reviewlog;
+
+    Ok(reviewlog::table
+        .filter(reviewlog::deck_id.eq(deck_id).and(reviewlog::undone_at.is_null()))
+        .order(reviewlog::id.asc())
+        .load::<ReviewLog>(conn)?)
+}
@@ -0,0 +1,108 @@
+use diesel::prelude::*;
+
+use crate::home::Quota;
+use crate::models::NewReviewLog;
+use crate::review::ReviewCard;
+use crate::review_log::CardState;
+use crate::error::{Result, Context};
+
+use crate::am1::Am1Scheduler;
+use crate::anki::Sm2Scheduler;
+use crate::fsrs::FsrsScheduler;
+
+
+/**
+ * A spaced repetition algorithm behind a deadline or box. Review sessions,
+ * card creation and card deletion only go through this trait, so each
+ * algorithm keeps its queueing, grading and quota bookkeeping in one place
+ */
+pub trait Scheduler: Send {
+    /**
+     * Scheduling state given to cards created in `deck_id`
+     */
+    fn new_card_state(&self, conn: &mut SqliteConnection, deck_id: i32) -> Result<CardState>;
+
+    /**
+     * Accounts for `num_cards` cards just created in `deck_id`
+     */
+    fn cards_created(&self, conn: &mut SqliteConnection, deck_id: i32, num_cards: i32) -> Result<()>;
+
+    /**
+     * Accounts for a card of `deck_id` just deleted while in `state`
+     */
+    fn card_deleted(&self, conn: &mut SqliteConnection, deck_id: i32, state: &CardState) -> Result<()>;
+
+    /**
+     * Cards of `deck_id` still to study today; None if the deck has no quota
+     */
+    fn deck_quota(&self, conn: &mut SqliteConnection, deck_id: i32) -> Result<Option<Quota>>;
+
+    /**
+     * Caps the `quotas` of the decks `deck_ids` of `deadline_id` by limits
+     * the deadline puts on all of its decks together; none by default
+     */
+    fn cap_deadline_quotas(&self, _conn: &mut SqliteConnection, _deadline_id: i32, _deck_ids: &[i32], _quotas: &mut [Quota]) -> Result<()> {
+        Ok(())
+    }
+
+    /**
+     * Ids of at most `limit` new cards of `deck_id` to learn today
+     */
+    fn select_new_cards(&self, conn: &mut SqliteConnection, deck_id: i32, limit: i32) -> Result<Vec<i32>>;
+
+    /**
+     * Picks the next card to review from `deck_id`, only among `only`
+     * when given; None if no card is left to review
+     */
+    fn pop_review_card(&self, conn: &mut SqliteConnection, deck_id: i32, only: Option<&[i32]>) -> Result<Option<ReviewCard>>;
+
+    /**
+     * Applies `score` to `card` and counts it towards today's quota,
+     * returning the stack the card moves to
+     */
+    fn grade(&self, conn: &mut SqliteConnection, card: &ReviewCard, score: i32) -> Result<String>;
+
+    /**
+     * Moves the quota counters of a logged answer forward (`sign` = 1) or
+     * back (`sign` = -1); card state itself is restored from the log
+     */
+    fn shift_quota(&self, conn: &mut SqliteConnection, log: &NewReviewLog, sign: i32) -> Result<()>;
+}
+
+
+/**
+ * Returns the scheduler of a deadline, ankibox or FSRS box
+ */
+pub fn get_scheduler(conn: &mut SqliteConnection, deadline_id: i32) -> Result<Box<dyn Scheduler>> {
+    use crate::schema::deadlines;
+
+    let (is_anki, is_fsrs) = deadlines::table
+        .find(deadline_id)
+        .select((deadlines::is_anki, deadlines::is_fsrs))
+        .get_result::<(bool, bool)>(conn)
+        .context(&format!("deadline {}", deadline_id))?;
+
+    if is_fsrs {
+        Ok(Box::new(FsrsScheduler))
+    } else if is_anki {
+        Ok(Box::new(Sm2Scheduler))
+    } else {
+        Ok(Box::new(Am1Scheduler::new(conn, deadline_id)?))
+    }
+}
+
+
+/**
+ * Returns the scheduler of the deadline a deck belongs to
+ */
+pub fn get_deck_scheduler(conn: &mut SqliteConnection, deck_id: i32) -> Result<Box<dyn Scheduler>> {
+    use crate::schema::parents;
+
+    let deadline_id = parents::table
+        .filter(parents::child_id.eq(deck_id))
+        .select(parents::parent_id)
+        .get_result::<i32>(conn)
+        .context(&format!("deadline of deck {}", deck_id))?;
+
+    get_scheduler(conn, deadline_id)
+}
@@ -0,0 +1,465 @@
+use std::collections::BTreeMap;
+
+use diesel::{insert_into, delete, update};
+use diesel::prelude::*;
+
+use crate::error::{Error, Result, Context};
+
+
+// words of tag expressions, which tags may not be named
+const KEYWORDS: [&str; 3] = ["and", "or", "not"];
+
+// parsed tag expression, see `matching_card_ids`
+enum TagExpr {
+    // tag name, with `*` matching any run of characters
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Vec<TagExpr>),
+    Or(Vec<TagExpr>)
+}
+
+
+/**
+ * Tags a card. The tag is created if no tag of that name exists; names are
+ * compared without case. A cloze is tagged as a whole and a bidirectional
+ * card together with its reverse card
+ */
+pub fn add_tag(conn: &mut SqliteConnection, card_id: i32, name: &str) -> Result<()> {
+    use crate::schema::{card_tags, tags};
+
+    let name = check_tag_name(name)?;
+    let card_ids = linked_card_ids(conn, card_id)?;
+
+    conn.transaction(|conn| {
+        insert_into(tags::table)
+            .values(tags::name.eq(name))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        let tag_id = find_tag(conn, name)?
+            .ok_or_else(|| Error::IntegrityViolation(format!("tag {} was not inserted", name)))?;
+
+        for card_id in card_ids {
+            insert_into(card_tags::table)
+                .values((card_tags::card_id.eq(card_id), card_tags::tag_id.eq(tag_id)))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+
+/**
+ * Removes a tag from a card and the cards tagged with it, see `add_tag`.
+ * A tag no card has any more is deleted
+ */
+pub fn remove_tag(conn: &mut SqliteConnection, card_id: i32, name: &str) -> Result<()> {
+    use crate::schema::card_tags;
+
+    let card_ids = linked_card_ids(conn, card_id)?;
+    let Some(tag_id) = find_tag(conn, name.trim())? else {
+        return Ok(());
+    };
+
+    conn.transaction(|conn| {
+        delete(card_tags::table.filter(card_tags::tag_id.eq(tag_id).and(card_tags::card_id.eq_any(card_ids))))
+            .execute(conn)?;
+        delete_unused_tags(conn)
+    })
+}
+
+
+/**
+ * Renames a tag on every card. Renaming to the name of another tag merges
+ * the two
+ */
+pub fn rename_tag(conn: &mut SqliteConnection, old_name: &str, new_name: &str) -> Result<()> {
+    use crate::schema::{card_tags, tags};
+
+    let new_name = check_tag_name(new_name)?;
+    let tag_id = find_tag(conn, old_name.trim())?
+        .ok_or_else(|| Error::NotFound(format!("tag {}", old_name.trim())))?;
+
+    conn.transaction(|conn| {
+        match find_tag(conn, new_name)? {
+            Some(other_id) if other_id != tag_id => {
+                let card_ids = card_tags::table
+                    .filter(card_tags::tag_id.eq(tag_id))
+                    .select(card_tags::card_id)
+                    .get_results::<i32>(conn)?;
+                for card_id in card_ids {
+                    insert_into(card_tags::table)
+                        .values((card_tags::card_id.eq(card_id), card_tags::tag_id.eq(other_id)))
+                        .on_conflict_do_nothing()
+                        .execute(conn)?;
+                }
+                delete(card_tags::table.filter(card_tags::tag_id.eq(tag_id)))
+                    .execute(conn)?;
+                delete(tags::table.find(tag_id))
+                    .execute(conn)?;
+            },
+            // a new name, or the same name in another case
+            _ => {
+                update(tags::table.find(tag_id))
+                    .set(tags::name.eq(new_name))
+                    .execute(conn)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+
+/**
+ * Removes a tag from every card and deletes it
+ */
+pub fn delete_tag(conn: &mut SqliteConnection, name: &str) -> Result<()> {
+    use crate::schema::{card_tags, tags};
+
+    let tag_id = find_tag(conn, name.trim())?
+        .ok_or_else(|| Error::NotFound(format!("tag {}", name.trim())))?;
+
+    conn.transaction(|conn| {
+        delete(card_tags::table.filter(card_tags::tag_id.eq(tag_id)))
+            .execute(conn)?;
+        delete(tags::table.find(tag_id))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+
+/**
+ * Names of all tags, in alphabetical order
+ */
+pub fn read_tags(conn: &mut SqliteConnection) -> Result<Vec<String>> {
+    use crate::schema::tags;
+
+    Ok(tags::table
+        .select(tags::name)
+        .order(tags::name.asc())
+        .get_results::<String>(conn)?)
+}
+
+
+/**
+ * Tags of each of `card_ids`, in alphabetical order; cards without tags are
+ * left out
+ */
+pub fn read_card_tags(conn: &mut SqliteConnection, card_ids: &[i32]) -> Result<BTreeMap<i32, Vec<String>>> {
+    use crate::schema::{card_tags, tags};
+
+    let rows = card_tags::table
+        .inner_join(tags::table)
+        .filter(card_tags::card_id.eq_any(card_ids))
+        .select((card_tags::card_id, tags::name))
+        .order(tags::name.asc())
+        .get_results::<(i32, String)>(conn)?;
+
+    let mut card_tags: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+    for (card_id, name) in rows {
+        card_tags.entry(card_id).or_default().push(name);
+    }
+    Ok(card_tags)
+}
+
+
+/**
+ * Ids of the cards of a deadline or ankibox whose tags match `expression`:
+ *
+ *   lecture7                cards tagged lecture7
+ *   lecture7 quiz           both tags; `and` may be written out
+ *   lecture7 or lecture8    either tag
+ *   -hard, not hard         cards without the tag
+ *   lecture*                any tag starting with "lecture"
+ *   (a or b) -c             parentheses group
+ *
+ * Tags are compared without case
+ */
+pub fn matching_card_ids(conn: &mut SqliteConnection, deadline_id: i32, expression: &str) -> Result<Vec<i32>> {
+    use crate::schema::{cards, card_tags, parents, tags};
+
+    let expr = parse_tag_expression(expression)?;
+
+    let deck_ids = parents::table
+        .filter(parents::parent_id.eq(deadline_id))
+        .select(parents::child_id)
+        .get_results::<i32>(conn)?;
+    let card_ids = cards::table
+        .filter(cards::deck_id.eq_any(&deck_ids))
+        .select(cards::id)
+        .order(cards::id.asc())
+        .get_results::<i32>(conn)?;
+
+    let mut card_tags: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+    let rows = card_tags::table
+        .inner_join(tags::table)
+        .inner_join(cards::table)
+        .filter(cards::deck_id.eq_any(&deck_ids))
+        .select((card_tags::card_id, tags::name))
+        .get_results::<(i32, String)>(conn)?;
+    for (card_id, name) in rows {
+        card_tags.entry(card_id).or_default().push(name.to_lowercase());
+    }
+
+    Ok(card_ids.into_iter()
+        .filter(|card_id| expr.matches(card_tags.get(card_id).map_or(&[], |tags| tags.as_slice())))
+        .collect())
+}
+
+
+/**
+ * Removes `card_ids` from their tags before the cards are deleted. Tags no
+ * card has any more are deleted
+ */
+pub fn delete_card_tags(conn: &mut SqliteConnection, card_ids: &[i32]) -> Result<()> {
+    use crate::schema::card_tags;
+
+    delete(card_tags::table.filter(card_tags::card_id.eq_any(card_ids)))
+        .execute(conn)?;
+    delete_unused_tags(conn)
+}
+
+
+/**
+ * Tags an imported card with `names`, skipping names `add_tag` would refuse,
+ * e.g. Anki tags with parentheses
+ */
+pub fn add_imported_tags(conn: &mut SqliteConnection, card_id: i32, names: &[String]) -> Result<()> {
+    for name in names {
+        if check_tag_name(name).is_ok() {
+            add_tag(conn, card_id, name)?;
+        }
+    }
+    Ok(())
+}
+
+
+fn find_tag(conn: &mut SqliteConnection, name: &str) -> Result<Option<i32>> {
+    use crate::schema::tags;
+
+    // `name` is COLLATE NOCASE, so this ignores case
+    Ok(tags::table
+        .filter(tags::name.eq(name))
+        .select(tags::id)
+        .first::<i32>(conn)
+        .optional()?)
+}
+
+fn delete_unused_tags(conn: &mut SqliteConnection) -> Result<()> {
+    use crate::schema::{card_tags, tags};
+
+    delete(tags::table.filter(tags::id.ne_all(card_tags::table.select(card_tags::tag_id))))
+        .execute(conn)?;
+    Ok(())
+}
+
+// a card with its cloze siblings, its reverse card or the card it reverses
+fn linked_card_ids(conn: &mut SqliteConnection, card_id: i32) -> Result<Vec<i32>> {
+    use crate::schema::cards;
+
+    let (cloze_id, reverse_of) = cards::table
+        .filter(cards::id.eq(card_id))
+        .select((cards::cloze_id, cards::reverse_of))
+        .get_result::<(Option<i32>, Option<i32>)>(conn)
+        .context(&format!("card {}", card_id))?;
+
+    let mut card_ids = match cloze_id {
+        Some(cloze_id) => cards::table
+            .filter(cards::cloze_id.eq(cloze_id))
+            .select(cards::id)
+            .get_results::<i32>(conn)?,
+        None => vec![reverse_of.unwrap_or(card_id)]
+    };
+    let reverse_ids = cards::table
+        .filter(cards::reverse_of.eq_any(&card_ids))
+        .select(cards::id)
+        .get_results::<i32>(conn)?;
+    card_ids.extend(reverse_ids);
+    Ok(card_ids)
+}
+
+// tag names are single words that cannot be mistaken for expression syntax
+fn check_tag_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidInput(String::from("tag name is empty")));
+    }
+    if name.contains(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '*') || name.starts_with('-') {
+        return Err(Error::InvalidInput(format!("tag {:?} may not contain spaces, parentheses or `*`, or start with `-`", name)));
+    }
+    if KEYWORDS.contains(&name.to_lowercase().as_str()) {
+        return Err(Error::InvalidInput(format!("{:?} is a keyword of tag expressions", name)));
+    }
+    Ok(name)
+}
+
+
+fn parse_tag_expression(expression: &str) -> Result<TagExpr> {
+    // `(`, `)` and a leading `-` are tokens of their own
+    let mut tokens: Vec<String> = Vec::new();
+    for word in expression.split_whitespace() {
+        let mut rest = word;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix(['(', ')', '-']) {
+                tokens.push(rest[..1].to_string());
+                rest = after;
+            } else {
+                let end = rest.find(['(', ')']).unwrap_or(rest.len());
+                tokens.push(rest[..end].to_lowercase());
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    let mut pos = 0;
+    let expr = parse_or(&tokens, &mut pos)?;
+    match tokens.get(pos) {
+        None => Ok(expr),
+        Some(token) => Err(Error::InvalidInput(format!("unexpected {:?} in tag expression", token)))
+    }
+}
+
+fn parse_or(tokens: &[String], pos: &mut usize) -> Result<TagExpr> {
+    let mut terms = vec![parse_and(tokens, pos)?];
+    while tokens.get(*pos).map(String::as_str) == Some("or") {
+        *pos += 1;
+        terms.push(parse_and(tokens, pos)?);
+    }
+    Ok(if terms.len() == 1 { terms.remove(0) } else { TagExpr::Or(terms) })
+}
+
+fn parse_and(tokens: &[String], pos: &mut usize) -> Result<TagExpr> {
+    let mut terms = vec![parse_unary(tokens, pos)?];
+    loop {
+        match tokens.get(*pos).map(String::as_str) {
+            None | Some(")") | Some("or") => break,
+            Some("and") => *pos += 1,
+            Some(_) => {}
+        }
+        terms.push(parse_unary(tokens, pos)?);
+    }
+    Ok(if terms.len() == 1 { terms.remove(0) } else { TagExpr::And(terms) })
+}
+
+fn parse_unary(tokens: &[String], pos: &mut usize) -> Result<TagExpr> {
+    let token = tokens.get(*pos)
+        .ok_or_else(|| Error::InvalidInput(String::from("tag expression ends too early")))?;
+    *pos += 1;
+    match token.as_str() {
+        "-" | "not" => Ok(TagExpr::Not(Box::new(parse_unary(tokens, pos)?))),
+        "(" => {
+            let expr = parse_or(tokens, pos)?;
+            if tokens.get(*pos).map(String::as_str) != Some(")") {
+                return Err(Error::InvalidInput(String::from("missing `)` in tag expression")));
+            }
+            *pos += 1;
+            Ok(expr)
+        },
+        ")" | "and" | "or" => Err(Error::InvalidInput(format!("unexpected {:?} in tag expression", token))),
+        tag => Ok(TagExpr::Tag(tag.to_string()))
+    }
+}
+
+impl TagExpr {
+    // `tags` are lowercase
+    fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(pattern) => tags.iter().any(|tag| glob_match(pattern, tag)),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(exprs) => exprs.iter().all(|expr| expr.matches(tags)),
+            TagExpr::Or(exprs) => exprs.iter().any(|expr| expr.matches(tags))
+        }
+    }
+}
+
+// whether `text` matches `pattern`, where `*` stands for any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || !text[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false
+        }
+    }
+    true
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // whether a card tagged with the space-separated `tags` matches `expression`
+    fn matches(expression: &str, tags: &str) -> bool {
+        let tags: Vec<String> = tags.split_whitespace().map(String::from).collect();
+        parse_tag_expression(expression).unwrap().matches(&tags)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // a or (b and c)
+        assert!(matches("a or b and c", "a"));
+        assert!(matches("a or b and c", "b c"));
+        assert!(!matches("a or b and c", "b"));
+        // (a and b) or c
+        assert!(matches("a and b or c", "c"));
+        assert!(!matches("a and b or c", "a"));
+        // juxtaposition is `and`
+        assert!(matches("a b or c", "a b"));
+        assert!(!matches("a b or c", "b"));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert!(matches("not a and b", "b"));
+        assert!(!matches("not a and b", "a b"));
+        assert!(!matches("-a b", "a b"));
+        assert!(matches("not not a", "a"));
+        assert!(matches("-a or b", "a b"));
+        assert!(!matches("-a or b", "a"));
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert!(!matches("(a or b) and c", "a"));
+        assert!(matches("(a or b) and c", "b c"));
+        assert!(matches("not (a or b)", "c"));
+        assert!(!matches("not (a or b)", "b"));
+        assert!(matches("-(a b)", "a"));
+        assert!(matches("((a))", "a"));
+        assert!(matches("(a)(b)", "a b"));
+    }
+
+    #[test]
+    fn keywords_and_tags_ignore_case() {
+        assert!(matches("Verbs AND NOT Irregular", "verbs"));
+        assert!(!matches("Verbs AND NOT Irregular", "verbs irregular"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("verb*", "verbs::irregular"));
+        assert!(matches("*::irregular", "verbs::irregular"));
+        assert!(matches("v*b*r", "verbs::irregular"));
+        assert!(!matches("v*x", "verbs"));
+        assert!(!matches("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn malformed_expressions() {
+        for expression in ["", "a or", "and a", "(a", "a)", "a ()", "not", "a or or b"] {
+            assert!(parse_tag_expression(expression).is_err(), "{:?}", expression);
+        }
+    }
+}
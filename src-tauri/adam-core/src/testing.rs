@@ -0,0 +1,69 @@
+// fixtures shared by the tests of the modules: a migrated in-memory database
+// with a trunk, entries in it, and scratch directories for media and files
+
+use std::path::PathBuf;
+
+use chrono::{Duration, Local};
+use diesel::prelude::*;
+
+use crate::{establish_connection, run_migrations, SqliteConnection};
+use crate::edit::{create_cards, DeckNewContents};
+use crate::home::{create_entry, init_root_folder, EntryMetadata};
+use crate::models::NewCard;
+use crate::render::HTML_FORMAT;
+
+
+// migrated database without any entries, not even the trunk
+pub fn empty_connection() -> SqliteConnection {
+    let mut conn = establish_connection(":memory:");
+    run_migrations(&mut conn).unwrap();
+    conn
+}
+
+// empty database with its root folder, the trunk
+pub fn test_connection() -> SqliteConnection {
+    let mut conn = empty_connection();
+    init_root_folder(&mut conn).unwrap();
+    conn
+}
+
+pub fn root_id(conn: &mut SqliteConnection) -> i32 {
+    use crate::schema::folders;
+
+    folders::table.select(folders::id).order(folders::id).first::<i32>(conn).unwrap()
+}
+
+// creates an entry of `entry_type` under `parent_id`, returning its id; a
+// deadline is due in `days` days
+pub fn create_test_entry(conn: &mut SqliteConnection, name: &str, parent_id: i32, entry_type: &str, days: i64) -> i32 {
+    use crate::schema::entries;
+
+    let deadline_date = (entry_type == "deadline")
+        .then(|| (Local::now() + Duration::days(days)).format("%Y-%m-%d %H:%M:%S").to_string());
+    let md = EntryMetadata {
+        entry_type: entry_type.to_string(),
+        deadline_date,
+        study_intensity: Some(1),
+        target_retention: None
+    };
+    create_entry(conn, name, Some(parent_id), md).unwrap();
+    entries::table.select(entries::id).order(entries::id.desc()).first::<i32>(conn).unwrap()
+}
+
+pub fn new_card(front: &str, back: &str) -> NewCard {
+    NewCard { front: front.to_string(), back: back.to_string(), is_bidirectional: false, format: String::from(HTML_FORMAT) }
+}
+
+// creates HTML cards of (front, back) pairs in a deck, returning their ids
+pub fn create_test_cards(conn: &mut SqliteConnection, deadline_id: i32, deck_id: i32, sides: &[(&str, &str)]) -> Vec<i32> {
+    let cards = sides.iter().map(|(front, back)| new_card(front, back)).collect();
+    create_cards(conn, deadline_id, DeckNewContents { deck_id, deck_name: String::new(), cards }).unwrap()
+}
+
+// empty directory of its own for a test
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adam-core-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
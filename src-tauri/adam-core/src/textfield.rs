@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use diesel::prelude::*;
+
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::edit::{create_cards, DeckNewContents};
+use crate::home::{create_deck, find_deck};
+use crate::models::NewCard;
+use crate::render::HTML_FORMAT;
+use crate::error::{Error, Result};
+
+
+/**
+ * Cards written under one heading of a textfield, or before the first heading
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TextfieldDeck {
+    pub heading: Option<String>,
+    pub cards: Vec<NewCard>
+}
+
+// card being parsed, as lines of text
+struct PendingCard {
+    front: Vec<String>,
+    back: Vec<String>
+}
+
+
+/**
+ * Parses the textfield format into cards, grouped by deck heading:
+ *
+ *   # Deck name             a heading starts a deck
+ *   // comment              ignored
+ *   front >> back           one card; `»` works like `>>`
+ *   more of the back        lines under a card continue its back
+ *
+ *   first line of a front   lines that start a block continue the front
+ *   >> back
+ *
+ * A blank line ends a card. `\>>`, `\»`, `\#` and `\\` stand for the
+ * characters themselves. Front and back are returned as the editor's HTML
+ */
+pub fn parse_textfield(text: &str) -> Result<Vec<TextfieldDeck>> {
+    let mut decks = vec![TextfieldDeck { heading: None, cards: Vec::new() }];
+    // lines of a block before its first card, with the number of the first one
+    let mut leading: Vec<String> = Vec::new();
+    let mut leading_line = 0;
+    let mut card: Option<PendingCard> = None;
+
+    for (i, raw_line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+
+        if line.starts_with("//") {
+            continue;
+        }
+
+        if line.is_empty() || heading(line).is_some() {
+            finish_block(&mut decks, &mut card, &leading, leading_line)?;
+            if let Some(heading) = heading(line) {
+                decks.push(TextfieldDeck { heading: Some(heading), cards: Vec::new() });
+            }
+            continue;
+        }
+
+        // bullets typed in the editor are not part of the card
+        let line = line.strip_prefix('•').unwrap_or(line).trim_start();
+
+        let mut segments = split_unescaped(line);
+        match segments.len() {
+            1 => {
+                let text = segments.remove(0);
+                match card.as_mut() {
+                    Some(card) => card.back.push(text),
+                    None => {
+                        if leading.is_empty() {
+                            leading_line = line_no;
+                        }
+                        leading.push(text);
+                    }
+                }
+            },
+            2 => {
+                if let Some(card) = card.take() {
+                    push_card(&mut decks, card);
+                }
+                // `front >>` and `>> back` leave the other side to the lines around them
+                let back: Vec<String> = segments.pop().filter(|back| !back.is_empty()).into_iter().collect();
+                let mut front = std::mem::take(&mut leading);
+                front.extend(segments.pop().filter(|front| !front.is_empty()));
+
+                if front.is_empty() {
+                    return Err(Error::InvalidInput(format!("line {}: card has no front", line_no)));
+                }
+                card = Some(PendingCard { front, back });
+            },
+            _ => return Err(Error::InvalidInput(format!("line {}: more than one `>>` on a line; write `\\>>` for a literal one", line_no)))
+        }
+    }
+    finish_block(&mut decks, &mut card, &leading, leading_line)?;
+
+    // text before the first heading only makes a deck when it holds cards
+    if decks[0].cards.is_empty() {
+        decks.remove(0);
+    }
+    Ok(decks)
+}
+
+
+/**
+ * Imports a notes file in the textfield format into a deadline or ankibox,
+ * returning the ids of the created cards. Each heading becomes a deck, reusing
+ * a deck of the same name; cards before the first heading go to a deck named
+ * after the file
+ */
+pub fn import_textfield_file(conn: &mut SqliteConnection, path: &Path, deadline_id: i32) -> Result<Vec<i32>> {
+    let text = std::fs::read_to_string(path)?;
+    let parsed = parse_textfield(&text)?;
+
+    let default_deck = path.file_stem()
+        .map_or_else(|| String::from("Imported"), |stem| stem.to_string_lossy().to_string());
+
+    // merge decks whose headings repeat, keeping the order of their cards
+    let mut decks: BTreeMap<String, Vec<NewCard>> = BTreeMap::new();
+    for deck in parsed {
+        let deck_name = deck.heading.unwrap_or_else(|| default_deck.clone());
+        decks.entry(deck_name).or_default().extend(deck.cards);
+    }
+
+    // a failed import leaves no half-filled decks behind
+    conn.transaction(|conn| {
+        let mut card_ids = Vec::new();
+        for (deck_name, cards) in decks {
+            let deck_id = match find_deck(conn, deadline_id, &deck_name)? {
+                Some(deck_id) => deck_id,
+                None => create_deck(conn, deadline_id, &deck_name)?
+            };
+
+            let deck_new_contents = DeckNewContents { deck_id, deck_name, cards };
+            card_ids.extend(create_cards(conn, deadline_id, deck_new_contents)?);
+        }
+        Ok(card_ids)
+    })
+}
+
+
+// ends the block at a blank line or heading; text outside any card is an error
+fn finish_block(decks: &mut [TextfieldDeck], card: &mut Option<PendingCard>, leading: &[String], leading_line: usize) -> Result<()> {
+    if !leading.is_empty() {
+        return Err(Error::InvalidInput(format!("line {}: text without `>>` is not part of a card", leading_line)));
+    }
+    if let Some(card) = card.take() {
+        push_card(decks, card);
+    }
+    Ok(())
+}
+
+fn push_card(decks: &mut [TextfieldDeck], card: PendingCard) {
+    if let Some(deck) = decks.last_mut() {
+        deck.cards.push(NewCard { front: to_html(&card.front), back: to_html(&card.back), is_bidirectional: false, format: String::from(HTML_FORMAT) });
+    }
+}
+
+// `# name`, `## name`, ...; `#tag` without a space is text
+fn heading(line: &str) -> Option<String> {
+    let rest = line.trim_start_matches('#');
+    if rest.len() == line.len() || !rest.starts_with(' ') {
+        return None;
+    }
+    let mut segments = split_unescaped(rest.trim());
+    (segments.len() == 1).then(|| segments.remove(0))
+}
+
+// splits a line at unescaped `>>` and `»`, resolving escapes; any other
+// backslash, as in `\frac` or `C:\Users`, is kept
+fn split_unescaped(line: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next_if(|next| matches!(next, '>' | '»' | '#' | '\\'));
+                if let Some(segment) = segments.last_mut() {
+                    segment.push(escaped.unwrap_or(c));
+                }
+            },
+            '>' if chars.peek() == Some(&'>') => {
+                chars.next();
+                segments.push(String::new());
+            },
+            '»' => segments.push(String::new()),
+            c => {
+                if let Some(segment) = segments.last_mut() {
+                    segment.push(c);
+                }
+            }
+        }
+    }
+
+    segments.iter().map(|segment| segment.trim().to_string()).collect()
+}
+
+// plain text, e.g. a spreadsheet cell, as the editor stores it
+pub(crate) fn plain_to_html(text: &str) -> String {
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    to_html(&lines)
+}
+
+// lines as the editor stores them: escaped text in a div, lines split by <br>
+fn to_html(lines: &[String]) -> String {
+    let lines: Vec<String> = lines.iter()
+        .map(|line| line
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"))
+        .collect();
+    format!("<div>{}</div>", lines.join("<br>"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(text: &str) -> Vec<(String, String)> {
+        parse_textfield(text).unwrap()
+            .into_iter()
+            .flat_map(|deck| deck.cards)
+            .map(|card| (card.front, card.back))
+            .collect()
+    }
+
+    fn card(front: &str, back: &str) -> (String, String) {
+        (String::from(front), String::from(back))
+    }
+
+    #[test]
+    fn one_card_per_line() {
+        assert_eq!(cards("a >> b\nc » d"), vec![card("<div>a</div>", "<div>b</div>"), card("<div>c</div>", "<div>d</div>")]);
+    }
+
+    #[test]
+    fn multi_line_cards() {
+        let text = "first line\nsecond line\n>> back\nmore back\n\nq >>\nanswer";
+        assert_eq!(cards(text), vec![
+            card("<div>first line<br>second line</div>", "<div>back<br>more back</div>"),
+            card("<div>q</div>", "<div>answer</div>")
+        ]);
+    }
+
+    #[test]
+    fn headings_and_comments() {
+        let decks = parse_textfield("// note\n# Deck one\na >> b\n## Deck two\n#tag >> c").unwrap();
+        let headings: Vec<Option<String>> = decks.iter().map(|deck| deck.heading.clone()).collect();
+        assert_eq!(headings, vec![Some(String::from("Deck one")), Some(String::from("Deck two"))]);
+        assert_eq!(decks[1].cards[0].front, "<div>#tag</div>");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(cards(r"a \>> b >> c \» d"), vec![card("<div>a &gt;&gt; b</div>", "<div>c » d</div>")]);
+        assert_eq!(cards(r"\# not a heading >> back\\slash"), vec![card("<div># not a heading</div>", "<div>back\\slash</div>")]);
+    }
+
+    #[test]
+    fn other_backslashes_are_kept() {
+        assert_eq!(cards(r"\frac{a}{b} >> C:\Users"), vec![card("<div>\\frac{a}{b}</div>", "<div>C:\\Users</div>")]);
+        assert_eq!(cards(r"trailing \ >> x\"), vec![card("<div>trailing \\</div>", "<div>x\\</div>")]);
+    }
+
+    #[test]
+    fn text_outside_a_card_is_an_error() {
+        let err = parse_textfield("a >> b\n\nprose without a card").unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(message) if message.starts_with("line 3")));
+        assert!(parse_textfield(">> no front").is_err());
+        assert!(parse_textfield("a >> b >> c").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use diesel::update;
+use diesel::prelude::*;
+
+use crate::error::{Error, Result, Context};
+
+use std::cmp::max;
+use chrono::{ 
+  DateTime,
+  Duration,
+  prelude::*,
+};
+use chrono_tz::Tz;
+
+/*
+ * Algo helpers
+ */
+
+pub fn get_num_boxes(days_to_go: i32, study_intensity: i32, num_reset: i32) -> Result<i32> {
+  let t = days_to_go;
+  // bins generated by recursive equation x_n = x_{n-1} + 2^n + 1 
+  // applied 5 times to (2, 6)
+  let bins = vec![
+        (0, 1), (2, 6), (7, 15), (16, 32), (33, 65), (66, 130), (131, 259)];
+  let mut i = 0;
+    let mut found_bin = false;
+  for (a, b) in bins {
+    if a <= t && t <= b {
+            found_bin = true;
+      break;
+    }
+    i += 1;
+  }
+  if !found_bin {
+    return Err(Error::SchedulingError(String::from("deadline must be between 0 and 259 days in the future")));
+  }
+  let mut num_boxes = 2 + i;
+
+  // discount num_boxes based on study_intensity and num_reset
+  num_boxes = max(2, num_boxes - (2 - study_intensity));
+  num_boxes = max(2, num_boxes - num_reset);
+
+  Ok(num_boxes)
+}
+
+/**
+ * Returns number of study days until `deadline`, a UTC time, counting
+ * `new_day_hour` as the hour at which one day switches to the next. The
+ * deadline's own study day counts if the deadline is at or after
+ * `deadline_cutoff_hour` of it, which may be on the next morning: with days
+ * starting at 14:00 and the cutoff at 6:00, a deadline at 9:00 falls after
+ * the cutoff of the day that began at 14:00 the day before. Returns -1 if
+ * now is past the deadline.
+ *
+ * Days are counted on the calendar of `deadline_tz`, the zone the deadline
+ * was set in, so neither DST changes nor travel shift them
+ */
+pub fn days_until_deadline(
+    deadline: NaiveDateTime,
+    deadline_tz: Option<&str>,
+    new_day_hour: i32,
+    deadline_cutoff_hour: i32
+    ) -> Result<i64> {
+    let now = Utc::now().naive_utc();
+    if deadline <= now {
+      return Ok(-1);
+    }
+    let deadline = in_deadline_zone(deadline, deadline_tz);
+    let now = in_deadline_zone(now, deadline_tz);
+    Ok(study_days_between(now, deadline, new_day_hour, deadline_cutoff_hour))
+}
+
+// study days from `now` to a later `deadline`, both wall-clock times
+fn study_days_between(now: NaiveDateTime, deadline: NaiveDateTime, new_day_hour: i32, deadline_cutoff_hour: i32) -> i64 {
+    // day switches between now and the deadline
+    let deadline_day = study_date(deadline, new_day_hour);
+    let days = (deadline_day - study_date(now, new_day_hour)).num_days();
+    if days == 0 {
+      // on exam day
+      return 0;
+    }
+
+    // time before the cutoff on the deadline's day does not count as a day;
+    // the cutoff is that many hours after the day starts, wrapping past midnight
+    let day_start = deadline_day.and_time(NaiveTime::MIN) + Duration::hours(i64::from(new_day_hour));
+    let cutoff = day_start + Duration::hours(i64::from((deadline_cutoff_hour - new_day_hour).rem_euclid(24)));
+    days - (deadline < cutoff) as i64
+}
+
+/**
+ * The instant a local wall-clock time stands for, as a UTC time. A time that
+ * occurs twice when clocks go back is read as the first, and one skipped when
+ * clocks go forward as an hour later
+ */
+pub fn local_to_utc(local: NaiveDateTime) -> NaiveDateTime {
+  match Local.from_local_datetime(&local).earliest() {
+    Some(datetime) => datetime.naive_utc(),
+    None => Local.from_local_datetime(&(local + Duration::hours(1)))
+      .earliest()
+      .map_or(local, |datetime| datetime.naive_utc())
+  }
+}
+
+/**
+ * Local time of a UTC instant, with the offset in effect at that instant
+ */
+pub fn utc_to_local(utc: NaiveDateTime) -> DateTime<Local> {
+  Local.from_utc_datetime(&utc)
+}
+
+/**
+ * Wall-clock time of a UTC instant in the IANA zone `deadline_tz` a deadline
+ * was set in; in the local zone if that is unknown, as for deadlines set
+ * before zones were recorded
+ */
+pub fn in_deadline_zone(utc: NaiveDateTime, deadline_tz: Option<&str>) -> NaiveDateTime {
+  match deadline_tz.and_then(|tz| tz.parse::<Tz>().ok()) {
+    Some(tz) => tz.from_utc_datetime(&utc).naive_local(),
+    None => utc_to_local(utc).naive_local()
+  }
+}
+
+/**
+ * IANA name of the time zone local times are in, e.g. "Europe/Paris": `TZ`
+ * if it names one, as it overrides the system zone, else the system zone.
+ * None if unknown
+ */
+pub fn current_time_zone() -> Option<String> {
+  match std::env::var("TZ") {
+    Ok(tz) if tz.trim_start_matches(':').contains('/') => Some(String::from(tz.trim_start_matches(':'))),
+    _ => iana_time_zone::get_timezone().ok()
+  }
+}
+
+/**
+ * Hours (0-23, local time) the user's days are cut at, from `userconfig`: a
+ * study day starts at `new_day_hour`, and the day of a deadline at or after
+ * `deadline_cutoff_hour` is a day to study on
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayHours {
+  pub new_day_hour: i32,
+  pub deadline_cutoff_hour: i32
+}
+
+impl Default for DayHours {
+  fn default() -> Self {
+    DayHours { new_day_hour: 2, deadline_cutoff_hour: 14 }
+  }
+}
+
+pub fn read_day_hours(conn: &mut SqliteConnection) -> Result<DayHours> {
+  use crate::schema::userconfig;
+
+  // databases being set up have no config yet
+  let hours = userconfig::table
+    .select((userconfig::new_day_hour, userconfig::deadline_cutoff_hour))
+    .first::<(i32, i32)>(conn)
+    .optional()?
+    .map_or_else(DayHours::default, |(new_day_hour, deadline_cutoff_hour)| DayHours { new_day_hour, deadline_cutoff_hour });
+  Ok(hours)
+}
+
+/**
+ * The study day it is now: until `new_day_hour` it is still the previous day.
+ * Ankibox due dates and daily counts use it in place of the calendar date
+ */
+pub fn study_today(conn: &mut SqliteConnection) -> Result<NaiveDate> {
+  let hours = read_day_hours(conn)?;
+  Ok(study_date(Local::now().naive_local(), hours.new_day_hour))
+}
+
+fn study_date(local_now: NaiveDateTime, new_day_hour: i32) -> NaiveDate {
+  (local_now - Duration::hours(i64::from(new_day_hour))).date()
+}
+
+pub fn get_is_anki(conn: &mut SqliteConnection, deadline_id: i32) -> Result<bool> {
+  use crate::schema::deadlines;
+    deadlines::table
+        .find(deadline_id)
+        .select(deadlines::is_anki)
+        .first::<bool>(conn)
+        .context(&format!("deadline {}", deadline_id))
+}
+
+/**
+ * Count days in past where quota is not fulfilled, add unfilfilled progressions
+ * to today's quota, and redistribute quotas to even out study cost over days
+ */
+#[derive(Debug)]
+pub struct QuotaTempRecord {
+  pub dtg: i32, // days_to_go
+  pub nq: i32,  // new_quota
+  pub rq: i32,  // review_quota
+  pub nqp: i32, // new_quota_practiced
+  pub rqp: i32  // review_quota_practiced
+}
+
+pub fn handle_missed_days(conn: &mut SqliteConnection, deck_id: i32, days_to_go: &i32) -> Result<()> {
+    use crate::schema::quotas;
+    let mut curr_idx = *days_to_go as usize;
+
+    // read quotas 
+    let quota_tuples = quotas::table
+        .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.lt(days_to_go + 1)))
+        .select((quotas::days_to_go, quotas::new_assigned, quotas::review_assigned, quotas::review_practiced, quotas::new_practiced))
+        .get_results::<(i32, i32, i32, i32, i32)>(conn)?;
+
+    let mut quotas = Vec::new();
+    for t in quota_tuples {
+        quotas.push(
+            QuotaTempRecord { dtg: t.0, nq: t.1, rq: t.2, nqp: t.3, rqp: t.4 }
+        )
+    }
+
+    // if deadline has passed, act as if last day
+    if (curr_idx as i32) < 0 {
+        curr_idx = 0;
+    }
+
+    // return if no previous days
+    if curr_idx + 1 == quotas.len() {
+        return Ok(());
+    }
+
+    let (mut nq_missed, mut rq_missed) = (0, 0);
+    for i in (curr_idx + 1)..quotas.len() {
+        // count up number of progressions missed in the past
+        nq_missed += quotas[i].nq - quotas[i].nqp;
+        rq_missed += quotas[i].rq - quotas[i].rqp;
+
+        // set past quota to the amount that was practiced
+        quotas[i].nq = quotas[i].nqp;
+        quotas[i].rq = quotas[i].rqp;
+    }
+
+    // return if no missed days
+    if nq_missed == 0 && rq_missed == 0 {
+        return Ok(());
+    }
+
+    // add missed cards to today if it is the last day
+    if curr_idx == 0 {
+        quotas[curr_idx].nq += nq_missed;
+        quotas[curr_idx].rq += rq_missed;
+        return Ok(());
+    }
+
+
+
+    // add missed cards to days up to and including current day, without deadline day
+    let num_days = curr_idx as i32;
+    let new_per_day = nq_missed / num_days;
+    let new_rmdr = nq_missed - new_per_day * num_days;
+    let review_per_day = rq_missed / num_days;
+    let review_rmdr = rq_missed - review_per_day * num_days;
+
+    // distribute cards up to and including current day, skipping day of exam
+    for dtg in 1..=curr_idx {
+
+        // distribute burden for missed quotas on past days
+        quotas[dtg].nq += new_per_day;
+        quotas[dtg].rq += review_per_day;
+
+        // add remainder to proper days (semi-arbirarily chosen)
+        if dtg == 1 {
+            quotas[dtg].rq += review_rmdr;
+        } else if dtg == curr_idx {
+            quotas[dtg].nq += new_rmdr;
+        }
+
+    }
+
+    // update quotas database with new quotas
+    for quota in quotas {
+        update(quotas::table)
+            .filter(quotas::id.eq(deck_id).and(quotas::days_to_go.eq(quota.dtg)))
+            .set((quotas::new_assigned.eq(quota.nq), quotas::review_assigned.eq(quota.rq)))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(day: u32, hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+  }
+
+  #[test]
+  fn cutoff_on_the_same_calendar_day() {
+    // days start at 2:00 and a deadline's day counts from 14:00
+    assert_eq!(study_days_between(at(18, 10), at(27, 9), 2, 14), 8);
+    assert_eq!(study_days_between(at(18, 10), at(27, 15), 2, 14), 9);
+    assert_eq!(study_days_between(at(18, 10), at(18, 20), 2, 14), 0);
+    // before 2:00 it is still the previous study day
+    assert_eq!(study_days_between(at(18, 1), at(27, 15), 2, 14), 10);
+  }
+
+  #[test]
+  fn cutoff_wrapping_past_midnight() {
+    // night shift: days start at 14:00 and a deadline's day counts from 6:00 the next morning
+    assert_eq!(study_days_between(at(18, 15), at(27, 9), 14, 6), 8);
+    assert_eq!(study_days_between(at(18, 15), at(27, 5), 14, 6), 7);
+    assert_eq!(study_days_between(at(18, 15), at(27, 15), 14, 6), 8);
+    // on the morning of the 19th it is still the study day that began on the 18th
+    assert_eq!(study_days_between(at(18, 15), at(19, 9), 14, 6), 0);
+    assert_eq!(study_days_between(at(18, 12), at(19, 9), 14, 6), 1);
+    assert_eq!(study_days_between(at(18, 12), at(19, 5), 14, 6), 0);
+  }
+
+  #[test]
+  fn cutoff_at_the_start_of_the_day() {
+    assert_eq!(study_days_between(at(18, 10), at(27, 2), 2, 2), 9);
+    assert_eq!(study_days_between(at(18, 10), at(27, 1), 2, 2), 8);
+  }
+
+  #[test]
+  fn days_until_deadline_in_its_zone() {
+    let now = Utc::now().naive_utc();
+    assert_eq!(days_until_deadline(now - Duration::hours(1), Some("Europe/Paris"), 2, 14).unwrap(), -1);
+
+    let deadline = now + Duration::days(5);
+    let local_now = in_deadline_zone(now, Some("Asia/Tokyo"));
+    let local_deadline = in_deadline_zone(deadline, Some("Asia/Tokyo"));
+    for (new_day_hour, cutoff_hour) in [(2, 14), (14, 6), (0, 0), (23, 22)] {
+      assert_eq!(
+        days_until_deadline(deadline, Some("Asia/Tokyo"), new_day_hour, cutoff_hour).unwrap(),
+        study_days_between(local_now, local_deadline, new_day_hour, cutoff_hour)
+      );
+    }
+  }
+}
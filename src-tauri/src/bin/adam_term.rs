@@ -0,0 +1,244 @@
+/*
+ * Terminal review client. Opens the same database as the desktop app, lists
+ * its deadlines and ankiboxes, and runs a review session on the chosen one
+ * with the same engine the Tauri commands use.
+ *
+ * Usage: adam-term [--database path/to/adam.db]
+ *
+ * The database is located like the app's: `--database`, then `DATABASE_URL`,
+ * then the app data directory.
+ */
+
+use std::io::{self, Write, BufRead};
+
+use adam_core::{establish_connection, run_migrations, SqliteConnection};
+use adam_core::db::resolve_database_path;
+use adam_core::error::Result;
+use adam_core::home::{read_folder_system, get_deadline_date};
+use adam_core::render::html_to_plain_text;
+use adam_core::review::{
+    ReviewSession,
+    ReviewCard,
+    init_review_session,
+    get_next_card,
+    record_response,
+    get_last_card,
+    undo_get_last_card
+};
+use adam_core::utils::get_is_anki;
+
+use dotenvy::dotenv;
+
+
+struct ReviewTarget {
+    id: i32,
+    name: String,
+    is_anki: bool
+}
+
+enum Action {
+    Score(i32),
+    Back,
+    Forward,
+    Quit
+}
+
+
+fn main() {
+    dotenv().ok();
+
+    let args: Vec<String> = std::env::args().collect();
+    let database = resolve_database_path(&args).expect("failed to locate database");
+    if let Some(legacy_path) = &database.migrated_from {
+        println!("Migrated database {} to {}", legacy_path.display(), database.path.display());
+    }
+    let database_url = database.path.to_string_lossy();
+
+    let mut conn = establish_connection(&database_url);
+    run_migrations(&mut conn).expect("Error embedding migrations");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    let targets = match list_targets(&mut conn) {
+        Ok(targets) => targets,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if targets.is_empty() {
+        println!("No deadlines or ankiboxes in {}", database_url);
+        return;
+    }
+
+    let target = loop {
+        let choice = match prompt(&mut lines, "\nChoose a deadline or ankibox (q to quit): ") {
+            Some(choice) => choice,
+            None => return
+        };
+        if choice == "q" {
+            return;
+        }
+        match choice.parse::<usize>() {
+            Ok(i) if i >= 1 && i <= targets.len() => break &targets[i - 1],
+            _ => println!("Enter a number between 1 and {}", targets.len())
+        }
+    };
+
+    if let Err(err) = review(&mut conn, &mut lines, target) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+
+/**
+ * Prints every deadline and ankibox with today's quota, returning them in
+ * the order they were numbered
+ */
+fn list_targets(conn: &mut SqliteConnection) -> Result<Vec<ReviewTarget>> {
+    let folder_system = read_folder_system(conn)?;
+
+    let mut targets = Vec::new();
+    for entry in folder_system.data {
+        if !["deadline", "ankibox", "fsrsbox"].contains(&entry.entry_type.as_str()) {
+            continue;
+        }
+
+        let due = match get_deadline_date(conn, entry.entry_id)? {
+            Some((date, true)) => format!("passed {}", date),
+            Some((date, false)) => format!("due {}", date),
+            None => entry.entry_type.clone()
+        };
+        let (new_left, review_left) = match &entry.entry_quota {
+            Some(quota) => (quota.new_left, quota.review_left),
+            None => (0, 0)
+        };
+
+        println!("{:>3}. {} ({}) - {} new, {} review", 
+            targets.len() + 1, entry.entry_name, due, new_left, review_left);
+
+        targets.push(ReviewTarget {
+            id: entry.entry_id,
+            is_anki: get_is_anki(conn, entry.entry_id)?,
+            name: entry.entry_name
+        });
+    }
+
+    Ok(targets)
+}
+
+
+fn review(conn: &mut SqliteConnection, lines: &mut impl Iterator<Item = io::Result<String>>, target: &ReviewTarget) -> Result<()> {
+    let mut session = ReviewSession::default();
+
+    let quota = init_review_session(conn, &mut session, target.id, None)?;
+    println!("\n{}: {} new and {} review cards today", target.name, quota.new_left, quota.review_left);
+
+    // a card brought back by `b` or `f` is answered again instead of drawing a new one
+    let mut revisited: Option<ReviewCard> = None;
+    let mut num_answered = 0;
+    loop {
+        let card = match revisited.take() {
+            Some(card) => card,
+            None => match get_next_card(conn, &mut session)? {
+                Some(card) => card,
+                None => {
+                    println!("\nSession complete: {} responses recorded", num_answered);
+                    return Ok(());
+                }
+            }
+        };
+
+        println!("\n[{}] {} card", card.deck_name, card.stack_before);
+        println!("{}", html_to_plain_text(&card.card.front_html));
+
+        let user_answer = match prompt(lines, "answer> ") {
+            Some(answer) => answer,
+            None => return Ok(())
+        };
+
+        println!("---");
+        println!("{}", html_to_plain_text(&card.card.back_html));
+
+        match read_action(lines, target.is_anki) {
+            Action::Score(score) => {
+                let stack_after = record_response(conn, &mut session, score, user_answer, card)?;
+                println!("-> {}", stack_after);
+                num_answered += 1;
+            },
+            Action::Back => {
+                match get_last_card(conn, &mut session)? {
+                    Some(results) => {
+                        println!("\nBack to previous card (you answered: {})", results.user_answer);
+                        revisited = Some(results.card);
+                        num_answered -= 1;
+                    },
+                    None => {
+                        println!("No previous card");
+                        revisited = Some(card);
+                    }
+                }
+            },
+            Action::Forward => {
+                match undo_get_last_card(conn, &mut session)? {
+                    Some(results) => {
+                        println!("\nForward to next card");
+                        revisited = Some(results.card);
+                        num_answered += 1;
+                    },
+                    None => {
+                        println!("No card to go forward to");
+                        revisited = Some(card);
+                    }
+                }
+            },
+            Action::Quit => return Ok(())
+        }
+    }
+}
+
+
+/**
+ * Reads a grade from the keyboard. AM-1 deadlines are graded -1, 0, 1 as
+ * Hard/Okay/Good and ankiboxes (FSRS boxes included) 1-5 as Again/Hard/Okay/Good/Easy, matching
+ * what the review page sends to `record_response`
+ */
+fn read_action(lines: &mut impl Iterator<Item = io::Result<String>>, is_anki: bool) -> Action {
+    let options = if is_anki {
+        "[1] again  [2] hard  [3] okay  [4] good  [5] easy  [b] back  [f] forward  [q] quit"
+    } else {
+        "[1] hard  [2] okay  [3] good  [b] back  [f] forward  [q] quit"
+    };
+
+    loop {
+        let key = match prompt(lines, &format!("{}\n> ", options)) {
+            Some(key) => key,
+            None => return Action::Quit
+        };
+
+        match (key.as_str(), is_anki) {
+            ("q", _) => return Action::Quit,
+            ("b", _) => return Action::Back,
+            ("f", _) => return Action::Forward,
+            (k, true) => if let Ok(score @ 1..=5) = k.parse::<i32>() {
+                return Action::Score(score);
+            },
+            (k, false) => if let Ok(score @ 1..=3) = k.parse::<i32>() {
+                return Action::Score(score - 2);
+            }
+        }
+    }
+}
+
+
+// returns the next trimmed line of input, or None at end of input
+fn prompt(lines: &mut impl Iterator<Item = io::Result<String>>, message: &str) -> Option<String> {
+    print!("{}", message);
+    io::stdout().flush().ok();
+    match lines.next() {
+        Some(Ok(line)) => Some(line.trim().to_string()),
+        _ => None
+    }
+}
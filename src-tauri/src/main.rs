@@ -10,18 +10,20 @@ use std::sync::{
     Arc
 };
 
-mod models;
-mod anki;
-mod schema;
+use adam_core::{establish_connection, run_migrations};
+use adam_core::db::{resolve_database_path, media_dir};
+use adam_core::media::{read_media, collect_garbage, MEDIA_SCHEME};
+use adam_core::home::{init_root_folder, init_getting_started};
+use adam_core::review::ReviewSession;
+
 mod home_db;
 use crate::home_db::{ 
-  init_root_folder,
-  init_getting_started,
   delete_entry,
   create_entry, 
   rename_entry,
   move_entry,
   write_dark_mode,
+  write_day_hours,
   read_folder_system, 
   DatabaseState, 
   is_duplicate_name,
@@ -29,7 +31,16 @@ use crate::home_db::{
   get_deadline_date,
   entered_past_deadline,
   reset_deadline,
-  toggle_is_expanded
+  toggle_is_expanded,
+  export_backup,
+  import_backup,
+  read_anki_options,
+  write_anki_options,
+  reset_anki_options,
+  read_daily_limits,
+  write_daily_limits,
+  read_target_retention,
+  write_target_retention
 };
 
 mod edit_db;
@@ -38,7 +49,23 @@ use edit_db::{
   write_text_field,
   create_cards,
   update_card,
+  set_bidirectional,
   delete_card,
+  create_cloze_cards,
+  get_cloze_id,
+  import_apkg,
+  export_apkg,
+  preview_delimited,
+  import_delimited,
+  parse_textfield,
+  import_textfield_file,
+  store_media,
+  attach_media,
+  add_tag,
+  remove_tag,
+  rename_tag,
+  delete_tag,
+  read_tags,
 };
 
 mod utils_db;
@@ -50,91 +77,71 @@ use review_db::{
   init_review_session,
   get_next_card,
   record_response,
+  check_answer,
   get_last_card,
+  undo_get_last_card,
   print_cards
 };
 
-
-// use diesel::pg::SqliteConnection;
-use diesel::sqlite::SqliteConnection;
-use diesel::Connection;
-
-// use dotenvy::dotenv;
-use std::env;
 use dotenvy::dotenv;
 
-// ../migrations
-
-// pub fn establish_connection() -> SqliteConnection {
-//   let database_url = "./trunk.db";
- 
-//   SqliteConnection::establish(&database_url)
-//     .expect(&format!("Error connecting to {}", database_url))
-// }
-
-// #[macro_use]
-// extern crate diesel_migrations;
-
-// use diesel_migrations::{embed_migrations};
-// embed_migrations!("../migrations");
-
 
-pub fn establish_connection() -> SqliteConnection {
+fn main() {
   dotenv().ok();
 
-  // let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-  // let database_url = "./trunk.db";
-  let database_url = "adam.db";
-  SqliteConnection::establish(&database_url)
-      .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
-}
-
-
-// Postgres is not working for me. What do I need to do to use SqLite now?
-/*
- * Run builder code
- */
-// #[macro_use]
-// extern crate diesel_migrations;
-
-
-// embed_migrations!("migrations");
-
-fn main() {
-  let mut conn = establish_connection();
+  // database is in the app data directory unless overridden by --database or DATABASE_URL
+  let args: Vec<String> = std::env::args().collect();
+  let database = resolve_database_path(&args).expect("failed to locate database");
+  if let Some(legacy_path) = &database.migrated_from {
+    println!("Migrated database {} to {}", legacy_path.display(), database.path.display());
+  }
+  let database_path = database.path;
+  let mut conn = establish_connection(&database_path.to_string_lossy());
   run_migrations(&mut conn).expect("Error embedding migrations");
 
   
-  if init_root_folder(&mut conn) {
-    init_getting_started(&mut conn);
+  if init_root_folder(&mut conn).expect("failed to create root folder") {
+    init_getting_started(&mut conn).expect("failed to create starter deadline");
   }
 
+  // media pasted but never saved on a card are swept at startup
+  let media_dir = media_dir(&database_path);
+  if let Err(err) = collect_garbage(&mut conn, &media_dir) {
+    eprintln!("Warning: failed to collect unused media: {}", err);
+  }
   let database_state = DatabaseState {
-    conn: Arc::new(Mutex::new(conn))
+    conn: Arc::new(Mutex::new(conn)),
+    media_dir: media_dir.clone()
   };
 
   let review_session_state = ReviewSessionState {
-    response_stack: Arc::new(Mutex::new(Vec::new())),
-    undo_response_stack: Arc::new(Mutex::new(Vec::new())),
-    curr_card: Arc::new(Mutex::new(None)),
-    new_ids: Arc::new(Mutex::new(Vec::new())),
-    days_to_go: Arc::new(Mutex::new(None)),
-    deadline_id: Arc::new(Mutex::new(None))
+    session: Arc::new(Mutex::new(ReviewSession::default()))
   };
 
 
   tauri::Builder::default()
     // define what variables will be in the state of the backend
     .setup(|app| {
-      // let handle = app.handle();
-      // let data_dir = handle.path_resolver().app_data_dir().unwrap();
-
       app.manage(database_state);
       app.manage(review_session_state);
 
 
       Ok(())
     })
+    // serves `adam-media:<file_name>` references in card HTML from the media store
+    .register_uri_scheme_protocol("adam-media", move |_app, request| {
+      let file_name = request.uri()
+        .trim_start_matches(MEDIA_SCHEME)
+        .trim_start_matches("//localhost/");
+      match read_media(&media_dir, file_name) {
+        Ok((data, mime_type)) => tauri::http::ResponseBuilder::new()
+          .mimetype(&mime_type)
+          .body(data),
+        Err(_) => tauri::http::ResponseBuilder::new()
+          .status(404)
+          .body(Vec::new())
+      }
+    })
     // define what backend functions are callable from the frontend
     .invoke_handler(tauri::generate_handler![
       // home_db
@@ -145,11 +152,21 @@ fn main() {
       move_entry,
       is_duplicate_name,
       write_dark_mode,
+      write_day_hours,
       read_user_config,
       get_deadline_date,
       entered_past_deadline,
       reset_deadline,
       toggle_is_expanded,
+      export_backup,
+      import_backup,
+      read_anki_options,
+      write_anki_options,
+      reset_anki_options,
+      read_daily_limits,
+      write_daily_limits,
+      read_target_retention,
+      write_target_retention,
 
       print_cards,
 
@@ -158,13 +175,31 @@ fn main() {
       write_text_field,
       create_cards,
       update_card,
+      set_bidirectional,
       delete_card,
+      create_cloze_cards,
+      get_cloze_id,
+      import_apkg,
+      export_apkg,
+      preview_delimited,
+      import_delimited,
+      parse_textfield,
+      import_textfield_file,
+      store_media,
+      attach_media,
+      add_tag,
+      remove_tag,
+      rename_tag,
+      delete_tag,
+      read_tags,
 
       // review_db
       init_review_session,
       get_next_card,
       record_response,
+      check_answer,
       get_last_card,
+      undo_get_last_card,
 
       // utils_db
       get_is_anki_frontend
@@ -177,20 +212,3 @@ fn main() {
 
 
 }
-
-
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
-use std::error::Error;
-
-
-fn run_migrations(connection: &mut impl MigrationHarness<diesel::sqlite::Sqlite>) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-
-    // This will run the necessary migrations.
-    //
-    // See the documentation for `MigrationHarness` for
-    // all available methods.
-    connection.run_pending_migrations(MIGRATIONS)?;
-
-    Ok(())
-}
\ No newline at end of file
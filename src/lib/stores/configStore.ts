@@ -4,12 +4,16 @@ import { writable } from "svelte/store";
 
 type AppConfig = {
     is_dark_mode: boolean,
-    is_textfield: boolean
+    is_textfield: boolean,
+    new_day_hour: number,
+    deadline_cutoff_hour: number
 };
 
 const config: AppConfig = {
     is_dark_mode: false,
-    is_textfield: false
+    is_textfield: false,
+    new_day_hour: 2,
+    deadline_cutoff_hour: 14
 };
 
 const configStore = writable(